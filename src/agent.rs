@@ -1,15 +1,19 @@
 use crate::config::RetryConfig;
-use crate::llm::LlmClient;
+use crate::history::History;
+use crate::llm::{ErrorClass, LlmClient, backoff_delay, classify_error, error_category};
 use crate::logger::AgentLogger;
 use crate::observer::{AgentObserver, ConsoleObserver};
 #[cfg(not(feature = "tiktoken"))]
 use crate::token::ApproxEstimator;
-use crate::token::TokenEstimator;
-use crate::tools::{Tool, base::ToolResult};
-use colored::*;
+use crate::token::{TokenCalibration, TokenEstimator};
+use crate::tools::{OutputSink, Tool, base::ToolResult};
 use serde_json::json;
+use siumai::LlmError;
 use siumai::traits::ChatCapability;
-use siumai::types::{ChatMessage, ChatRequest, ContentPart, MessageContent, Tool as SiumaiTool};
+use siumai::types::{
+    ChatMessage, ChatRequest, ChatResponse, ContentPart, MessageContent, MessageRole,
+    Tool as SiumaiTool,
+};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -17,18 +21,26 @@ use std::sync::Arc;
 pub struct Agent {
     llm: LlmClient,
     tools: HashMap<String, Arc<dyn Tool>>,
-    pub messages: Vec<ChatMessage>,
+    pub history: History,
     pub max_steps: usize,
     pub token_limit: usize,
     pub completion_reserve: usize,
     pub workspace: PathBuf,
     logger: AgentLogger,
     estimator: Box<dyn TokenEstimator>,
+    /// The next step's request: the history's messages plus the tool definitions. New
+    /// messages are appended as they arrive; it is only rebuilt when the history is rewritten.
+    request: ChatRequest,
+    /// Cached estimate for the tool definitions
+    tool_tokens: usize,
+    calibration: TokenCalibration,
     retry: RetryConfig,
+    summary: SummaryOptions,
     observer: Arc<dyn AgentObserver>,
 }
 
 impl Agent {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         llm: LlmClient,
         system_prompt: String,
@@ -39,8 +51,6 @@ impl Agent {
         workspace_dir: PathBuf,
         retry: RetryConfig,
     ) -> Self {
-        let mut msg = Vec::new();
-        msg.push(ChatMessage::system(system_prompt).build());
         let mut map = HashMap::new();
         for t in tools {
             map.insert(t.name().to_string(), t);
@@ -50,27 +60,72 @@ impl Agent {
             Box::new(crate::token::TiktokenEstimator::cl100k());
         #[cfg(not(feature = "tiktoken"))]
         let estimator: Box<dyn TokenEstimator> = Box::new(ApproxEstimator);
+        let mut history = History::new();
+        history.push(ChatMessage::system(system_prompt).build(), &*estimator);
+        let tools_schema: Vec<SiumaiTool> = map.values().map(|t| t.to_siumai_tool()).collect();
+        let tool_tokens = estimator.count_tools(&tools_schema);
+        let request = ChatRequest::new(history.to_messages()).with_tools(tools_schema);
         Self {
             llm,
             tools: map,
-            messages: msg,
+            history,
             max_steps,
             token_limit,
             completion_reserve,
             workspace: workspace_dir,
             logger: AgentLogger::new(),
             estimator,
+            request,
+            tool_tokens,
+            calibration: TokenCalibration::default(),
             retry,
+            summary: SummaryOptions::default(),
             observer: Arc::new(ConsoleObserver::new()),
         }
     }
 
     pub fn add_user_message(&mut self, text: String) {
-        self.messages.push(ChatMessage::user(text).build());
+        self.push_message(ChatMessage::user(text).build());
     }
 
-    fn to_siumai_tools(&self) -> Vec<SiumaiTool> {
-        self.tools.values().map(|t| t.to_siumai_tool()).collect()
+    fn push_message(&mut self, message: ChatMessage) {
+        self.request.messages.push(message.clone());
+        self.history.push(message, &*self.estimator);
+    }
+
+    /// Replace `history` and rebuild the request from it.
+    fn set_history(&mut self, history: History) {
+        self.request.messages = history.to_messages();
+        self.history = history;
+    }
+
+    /// Append `text` to the system prompt (the first history entry), keeping the rest of the
+    /// history; used for context that is only known once the first user message is in.
+    pub fn extend_system_prompt(&mut self, text: &str) {
+        let current = match self.history.get(0).map(|m| &m.content) {
+            Some(MessageContent::Text(t)) => t.clone(),
+            _ => String::new(),
+        };
+        let mut rebuilt = History::new();
+        rebuilt.push(
+            ChatMessage::system(format!("{}{}", current, text)).build(),
+            &*self.estimator,
+        );
+        for i in 1..self.history.len() {
+            rebuilt.push_from(&self.history, i);
+        }
+        self.set_history(rebuilt);
+    }
+
+    /// Drop everything but the system prompt.
+    pub fn clear_history(&mut self) {
+        self.history.truncate(1);
+        self.request.messages.truncate(1);
+    }
+
+    /// Tool definitions sent with every request (fixed for the agent's lifetime).
+    fn tools_schema(&self) -> &[SiumaiTool] {
+        self.request.tools.as_deref().unwrap_or_default()
     }
 
     pub fn tool_names(&self) -> Vec<String> {
@@ -105,6 +160,30 @@ impl Agent {
         self.observer = obs;
     }
 
+    pub fn set_summary_options(&mut self, summary: SummaryOptions) {
+        self.summary = summary;
+    }
+
+    pub fn set_estimator(&mut self, estimator: Box<dyn TokenEstimator>) {
+        self.estimator = estimator;
+        self.history.recount(&*self.estimator);
+        self.tool_tokens = self.estimator.count_tools(self.tools_schema());
+    }
+
+    /// Raw local estimate of the next prompt: history plus tool definitions.
+    fn raw_token_estimate(&self) -> usize {
+        self.history.total_tokens() + self.tool_tokens
+    }
+
+    /// Estimated prompt tokens for the current history, corrected by provider-reported usage.
+    pub fn estimated_tokens(&self) -> usize {
+        self.calibration.apply(self.raw_token_estimate())
+    }
+
+    pub fn token_calibration(&self) -> &TokenCalibration {
+        &self.calibration
+    }
+
     pub fn builder(llm: LlmClient, system_prompt: String) -> AgentBuilder {
         AgentBuilder::new(llm, system_prompt)
     }
@@ -119,7 +198,7 @@ impl Agent {
         loop {
             // summarize if tokens exceed limit
             let threshold = self.token_limit.saturating_sub(self.completion_reserve);
-            if self.estimator.count_messages(&self.messages) > threshold {
+            if self.estimated_tokens() > threshold {
                 self.summarize_history().await?;
             }
             if step >= self.max_steps {
@@ -128,29 +207,37 @@ impl Agent {
             }
 
             // Log request
-            let tools_schema: Vec<_> = self.to_siumai_tools().into_iter().collect();
+            let estimate = self.raw_token_estimate();
             let req_json = json!({
-                "messages": self.messages.iter().map(|m| {
+                "messages": self.history.iter().map(|m| {
                     json!({"role": format!("{:?}", m.role), "content": m.content_text().unwrap_or("")})
                 }).collect::<Vec<_>>(),
-                "tools": tools_schema.iter().map(|t| match t { SiumaiTool::Function { function } => function.name.clone(), _ => String::from("provider_tool")}).collect::<Vec<_>>()
+                "tools": self.tools_schema().iter().map(|t| match t { SiumaiTool::Function { function } => function.name.clone(), _ => String::from("provider_tool")}).collect::<Vec<_>>(),
+                "estimated_prompt_tokens": estimate,
             });
             self.logger.log_request(&req_json);
 
-            // Call LLM (built-in retry is configured on the Siumai client via builder)
-            let tools_vec = self
-                .tools
-                .values()
-                .map(|t| t.to_siumai_tool())
-                .collect::<Vec<_>>();
-            let req = ChatRequest::new(self.messages.clone()).with_tools(tools_vec);
-            let response = self.llm.inner().chat_request(req).await?;
+            // Call LLM (transient failures are retried per `RetryConfig`)
+            let llm = self.llm.clone();
+            let response = self
+                .chat_with_retry(&llm, |agent| agent.request.clone())
+                .await?;
+
+            // Calibrate future estimates against the provider's own count
+            if let Some(usage) = &response.usage {
+                self.calibration
+                    .observe(estimate, usage.prompt_tokens as usize);
+            }
 
             // Log response
             let resp_json = json!({
                 "content": response.content_text(),
                 "has_tool_calls": response.has_tool_calls(),
                 "finish_reason": response.finish_reason,
+                "usage": response.usage.as_ref().map(|u| json!({
+                    "prompt_tokens": u.prompt_tokens,
+                    "completion_tokens": u.completion_tokens,
+                })),
             });
             self.logger.log_response(&resp_json);
 
@@ -160,8 +247,7 @@ impl Agent {
                     if !t.is_empty() {
                         self.observer.on_assistant_text(t);
                     }
-                    self.messages
-                        .push(ChatMessage::assistant(t.clone()).build());
+                    self.push_message(ChatMessage::assistant(t.clone()).build());
                 }
                 MessageContent::MultiModal(parts) => {
                     // Print reasoning if present
@@ -170,14 +256,13 @@ impl Agent {
                     }
                     // Print assistant text parts
                     for p in parts {
-                        if let ContentPart::Text { text } = p {
-                            if !text.is_empty() {
-                                self.observer.on_assistant_text(text);
-                            }
+                        if let ContentPart::Text { text } = p
+                            && !text.is_empty()
+                        {
+                            self.observer.on_assistant_text(text);
                         }
                     }
-                    self.messages
-                        .push(ChatMessage::assistant_with_content(parts.clone()).build());
+                    self.push_message(ChatMessage::assistant_with_content(parts.clone()).build());
                 }
             }
 
@@ -218,7 +303,13 @@ impl Agent {
                     self.observer.on_tool_call(&tool_name, &display_args);
 
                     let result: ToolResult = match self.tools.get(&tool_name) {
-                        Some(t) => t.execute(args.clone()).await,
+                        Some(t) => {
+                            let observer = self.observer.clone();
+                            let name = tool_name.clone();
+                            let sink: OutputSink =
+                                Arc::new(move |chunk| observer.on_tool_output(&name, chunk));
+                            t.execute_streaming(args.clone(), sink).await
+                        }
                         None => ToolResult {
                             success: false,
                             content: String::new(),
@@ -244,7 +335,7 @@ impl Agent {
                             result.content.clone()
                         };
                         self.observer.on_tool_result(&tool_name, true, &preview);
-                        self.messages.push(
+                        self.push_message(
                             ChatMessage::tool_result_text(
                                 info.tool_call_id,
                                 tool_name,
@@ -257,8 +348,14 @@ impl Agent {
                             .error
                             .unwrap_or_else(|| "Tool execution failed".to_string());
                         self.observer.on_tool_result(&tool_name, false, &err);
-                        self.messages.push(
-                            ChatMessage::tool_error(info.tool_call_id, tool_name, err).build(),
+                        // keep whatever the tool produced (e.g. stderr of a failed command)
+                        let message = if result.content.is_empty() {
+                            err
+                        } else {
+                            format!("{}\n\n{}", err, result.content)
+                        };
+                        self.push_message(
+                            ChatMessage::tool_error(info.tool_call_id, tool_name, message).build(),
                         );
                     }
                 }
@@ -268,18 +365,54 @@ impl Agent {
         }
     }
 
+    /// Send a chat request, retrying retryable failures with exponential backoff.
+    /// Every failed attempt is reported to the observer and written to the run log.
+    ///
+    /// The provider takes the request by value, so each attempt sends a copy made by `request`.
+    async fn chat_with_retry(
+        &mut self,
+        llm: &LlmClient,
+        request: impl Fn(&Self) -> ChatRequest,
+    ) -> Result<ChatResponse, LlmError> {
+        let mut attempt = 0u32;
+        loop {
+            attempt += 1;
+            let err = match llm.inner().chat_request(request(self)).await {
+                Ok(resp) => return Ok(resp),
+                Err(e) => e,
+            };
+            let class = classify_error(&err, &self.retry);
+            let category = error_category(&err);
+            let delay = (self.retry.enabled
+                && class == ErrorClass::Retryable
+                && attempt <= self.retry.max_retries)
+                .then(|| backoff_delay(&self.retry, attempt));
+            self.logger.log_llm_error(&json!({
+                "attempt": attempt,
+                "error": err.to_string(),
+                "category": category,
+                "retryable": class == ErrorClass::Retryable,
+                "next_delay_secs": delay.map(|d| d.as_secs_f32()),
+            }));
+            let Some(delay) = delay else {
+                return Err(err);
+            };
+            self.observer
+                .on_retry(attempt, delay.as_secs_f32(), &category, &err.to_string());
+            tokio::time::sleep(delay).await;
+        }
+    }
+
     async fn summarize_history(&mut self) -> anyhow::Result<()> {
         // strategy: keep system + every user; summarize assistant/tool blocks between user pairs
-        let before = self.estimator.count_messages(&self.messages);
-        let mut new_msgs = Vec::<ChatMessage>::new();
-        if let Some(first) = self.messages.first().cloned() {
-            new_msgs.push(first);
-        }
+        let before = self.estimated_tokens();
+        let mut new_history = History::new();
+        new_history.push_from(&self.history, 0);
 
         // collect indices of user messages beyond system
         let mut user_idxs = Vec::new();
-        for (i, m) in self.messages.iter().enumerate().skip(1) {
-            if matches!(m.role, siumai::types::MessageRole::User) {
+        for (i, m) in self.history.iter().enumerate().skip(1) {
+            if matches!(m.role, MessageRole::User) {
                 user_idxs.push(i);
             }
         }
@@ -287,23 +420,23 @@ impl Agent {
             return Ok(());
         }
         for (pos, &u_idx) in user_idxs.iter().enumerate() {
-            new_msgs.push(self.messages[u_idx].clone());
+            new_history.push_from(&self.history, u_idx);
             let end = user_idxs
                 .get(pos + 1)
                 .cloned()
-                .unwrap_or(self.messages.len());
-            let segment = &self.messages[u_idx + 1..end];
+                .unwrap_or(self.history.len());
+            let segment = self.history.slice(u_idx + 1..end);
             if !segment.is_empty() {
                 let summary = self
-                    .create_summary(segment, pos + 1)
+                    .create_summary(&segment, pos + 1)
                     .await
                     .unwrap_or_else(|_| String::new());
                 let content = format!("[Assistant Execution Summary]\n\n{}", summary);
-                new_msgs.push(ChatMessage::user(content).build());
+                new_history.push(ChatMessage::user(content).build(), &*self.estimator);
             }
         }
-        self.messages = new_msgs;
-        let after = self.estimator.count_messages(&self.messages);
+        self.set_history(new_history);
+        let after = self.estimated_tokens();
         let threshold = self.token_limit;
         self.observer.on_summarize_start(before, threshold);
         self.observer.on_summarize_done(after);
@@ -311,92 +444,140 @@ impl Agent {
     }
 
     async fn create_summary(
-        &self,
-        messages: &[ChatMessage],
+        &mut self,
+        messages: &[Arc<ChatMessage>],
         round: usize,
     ) -> anyhow::Result<String> {
-        // build plain text
-        let mut buf = String::new();
-        buf.push_str(&format!("Round {} execution process:\n\n", round));
-        for m in messages {
-            match m.role {
-                siumai::types::MessageRole::Assistant => match &m.content {
-                    MessageContent::Text(t) => buf.push_str(&format!("Assistant: {}\n", t)),
-                    MessageContent::MultiModal(parts) => {
-                        let text = parts
-                            .iter()
-                            .filter_map(|p| {
-                                if let ContentPart::Text { text } = p {
-                                    Some(text.as_str())
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect::<Vec<_>>()
-                            .join(" ");
-                        if !text.is_empty() {
-                            buf.push_str(&format!("Assistant: {}\n", text));
+        let transcript = summary_transcript(messages, round, self.summary.max_tool_output_chars);
+        let prompt = summary_prompt(
+            &self.summary.prompt_template,
+            self.summary.max_words,
+            &transcript,
+        );
+        let request = |_: &Self| {
+            ChatRequest::new(vec![
+                ChatMessage::system(
+                    "You are an assistant skilled at summarizing Agent execution processes.",
+                )
+                .build(),
+                ChatMessage::user(prompt.clone()).build(),
+            ])
+        };
+        let llm = self.summary.llm.clone().unwrap_or_else(|| self.llm.clone());
+        let resp = self.chat_with_retry(&llm, request).await?;
+        Ok(resp.content_text().unwrap_or("").to_string())
+    }
+}
+
+/// Default summary prompt; `{EXECUTION}` and `{MAX_WORDS}` are filled in per segment.
+pub const DEFAULT_SUMMARY_PROMPT: &str = concat!(
+    "Please provide a concise summary of the following Agent execution process:\n\n",
+    "{EXECUTION}\n\n",
+    "Requirements:\n",
+    "1. Focus on what tasks were completed and which tools were called\n",
+    "2. Keep key execution results and important findings (file contents, command output, errors)\n",
+    "3. Be concise and clear, within {MAX_WORDS} words\n",
+    "4. Use the same language as the execution process\n",
+    "5. Do not include user content, only summarize the Agent's execution process\n"
+);
+
+/// How history segments are turned into summaries.
+#[derive(Clone)]
+pub struct SummaryOptions {
+    pub prompt_template: String,
+    pub max_words: usize,
+    pub max_tool_output_chars: usize,
+    /// Separate (usually cheaper) client for summaries; the main client is used if `None`.
+    pub llm: Option<LlmClient>,
+}
+
+impl Default for SummaryOptions {
+    fn default() -> Self {
+        Self {
+            prompt_template: DEFAULT_SUMMARY_PROMPT.to_string(),
+            max_words: 1000,
+            max_tool_output_chars: 1500,
+            llm: None,
+        }
+    }
+}
+
+/// Keep the first `max` characters of `s`, noting how much was cut.
+fn truncate_chars(s: &str, max: usize) -> String {
+    let total = s.chars().count();
+    if total <= max {
+        return s.to_string();
+    }
+    let head: String = s.chars().take(max).collect();
+    format!("{}... [{} more chars]", head, total - max)
+}
+
+/// Fill in a summary prompt template. A template without `{EXECUTION}` gets the transcript
+/// appended, so the summarizer always sees what it is summarizing.
+fn summary_prompt(template: &str, max_words: usize, transcript: &str) -> String {
+    let prompt = template.replace("{MAX_WORDS}", &max_words.to_string());
+    if prompt.contains("{EXECUTION}") {
+        prompt.replace("{EXECUTION}", transcript)
+    } else {
+        format!("{}\n\n{}", prompt.trim_end(), transcript)
+    }
+}
+
+/// Plain-text transcript of an assistant/tool segment, including tool arguments and
+/// (truncated) tool outputs so the summarizer can keep the facts they contained.
+fn summary_transcript(messages: &[Arc<ChatMessage>], round: usize, max_chars: usize) -> String {
+    let mut buf = format!("Round {} execution process:\n\n", round);
+    for m in messages {
+        match (&m.role, &m.content) {
+            (MessageRole::Assistant, MessageContent::Text(t)) => {
+                buf.push_str(&format!("Assistant: {}\n", t))
+            }
+            (MessageRole::Assistant, MessageContent::MultiModal(parts)) => {
+                for p in parts {
+                    match p {
+                        ContentPart::Text { text } if !text.is_empty() => {
+                            buf.push_str(&format!("Assistant: {}\n", text))
                         }
-                        let tool_names: Vec<_> = parts
-                            .iter()
-                            .filter_map(|p| {
-                                if let ContentPart::ToolCall { tool_name, .. } = p {
-                                    Some(tool_name.as_str())
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect();
-                        if !tool_names.is_empty() {
+                        ContentPart::ToolCall {
+                            tool_name,
+                            arguments,
+                            ..
+                        } => {
+                            let args = serde_json::to_string(arguments).unwrap_or_default();
                             buf.push_str(&format!(
-                                "  -> Called tools: {}\n",
-                                tool_names.join(", ")
+                                "  -> Called {}({})\n",
+                                tool_name,
+                                truncate_chars(&args, max_chars)
                             ));
                         }
+                        _ => {}
                     }
-                },
-                siumai::types::MessageRole::Tool => match &m.content {
-                    MessageContent::MultiModal(parts) => {
-                        let preview = parts
-                            .iter()
-                            .filter_map(|p| {
-                                if let ContentPart::ToolResult { .. } = p {
-                                    Some("[tool-result]")
-                                } else {
-                                    None
-                                }
-                            })
-                            .count();
-                        buf.push_str(&format!("  -> Tool returned: {} result(s)\n", preview));
+                }
+            }
+            (MessageRole::Tool, MessageContent::MultiModal(parts)) => {
+                for p in parts {
+                    if let ContentPart::ToolResult {
+                        tool_name, output, ..
+                    } = p
+                    {
+                        let verb = if output.is_error() {
+                            "failed"
+                        } else {
+                            "returned"
+                        };
+                        buf.push_str(&format!(
+                            "  -> {} {}: {}\n",
+                            tool_name,
+                            verb,
+                            truncate_chars(&output.to_string_lossy(), max_chars)
+                        ));
                     }
-                    _ => {}
-                },
-                _ => {}
+                }
             }
+            _ => {}
         }
-        let prompt = format!(
-            concat!(
-                "Please provide a concise summary of the following Agent execution process:\n\n",
-                "{}\n\n",
-                "Requirements:\n",
-                "1. Focus on what tasks were completed and which tools were called\n",
-                "2. Keep key execution results and important findings\n",
-                "3. Be concise and clear, within 1000 words\n",
-                "4. Use English\n",
-                "5. Do not include user content, only summarize the Agent's execution process\n"
-            ),
-            buf
-        );
-        let req = vec![
-            ChatMessage::system(
-                "You are an assistant skilled at summarizing Agent execution processes.",
-            )
-            .build(),
-            ChatMessage::user(prompt).build(),
-        ];
-        let resp = self.llm.inner().chat(req).await?;
-        Ok(resp.content_text().unwrap_or("").to_string())
     }
+    buf
 }
 
 pub struct AgentBuilder {
@@ -409,6 +590,8 @@ pub struct AgentBuilder {
     workspace: PathBuf,
     retry: RetryConfig,
     observer: Arc<dyn AgentObserver>,
+    estimator: Option<Box<dyn TokenEstimator>>,
+    summary: SummaryOptions,
 }
 
 impl AgentBuilder {
@@ -423,6 +606,8 @@ impl AgentBuilder {
             workspace: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
             retry: RetryConfig::default(),
             observer: Arc::new(ConsoleObserver::new()),
+            estimator: None,
+            summary: SummaryOptions::default(),
         }
     }
 
@@ -458,6 +643,14 @@ impl AgentBuilder {
         self.observer = o;
         self
     }
+    pub fn with_estimator(mut self, e: Box<dyn TokenEstimator>) -> Self {
+        self.estimator = Some(e);
+        self
+    }
+    pub fn with_summary(mut self, s: SummaryOptions) -> Self {
+        self.summary = s;
+        self
+    }
 
     pub fn build(self) -> Agent {
         let mut agent = Agent::new(
@@ -471,6 +664,87 @@ impl AgentBuilder {
             self.retry,
         );
         agent.set_observer(self.observer);
+        agent.set_summary_options(self.summary);
+        if let Some(e) = self.estimator {
+            agent.set_estimator(e);
+        }
         agent
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_prompt_always_has_transcript() {
+        assert_eq!(
+            summary_prompt("Sum up in {MAX_WORDS} words:\n{EXECUTION}\nThanks", 50, "T"),
+            "Sum up in 50 words:\nT\nThanks"
+        );
+        assert_eq!(
+            summary_prompt("Sum up in {MAX_WORDS} words.\n", 50, "T"),
+            "Sum up in 50 words.\n\nT"
+        );
+    }
+
+    fn text_ptr(message: &ChatMessage) -> *const u8 {
+        match &message.content {
+            MessageContent::Text(t) => t.as_ptr(),
+            _ => std::ptr::null(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_grows_with_the_history() {
+        let llm = LlmClient::from_config(&crate::config::LlmConfig {
+            provider: "openai".into(),
+            api_key: "test".into(),
+            model: "gpt-4o".into(),
+            base_url: None,
+            retry: RetryConfig::default(),
+        })
+        .await
+        .unwrap();
+        let mut agent = Agent::new(
+            llm,
+            "system".into(),
+            Vec::new(),
+            10,
+            100_000,
+            1_000,
+            PathBuf::from("."),
+            RetryConfig::default(),
+        );
+        agent.add_user_message("first".into());
+        let earlier: Vec<_> = agent.request.messages.iter().map(text_ptr).collect();
+        agent.add_user_message("second".into());
+        // the new message is appended; the earlier ones are not copied again
+        let now: Vec<_> = agent.request.messages.iter().map(text_ptr).collect();
+        assert_eq!(now[..2], earlier[..]);
+        assert_eq!(agent.request.messages[2].content_text(), Some("second"));
+
+        agent.extend_system_prompt(" extended");
+        assert_eq!(agent.request.messages.len(), 3);
+        assert_eq!(
+            agent.request.messages[0].content_text(),
+            Some("system extended")
+        );
+        agent.clear_history();
+        assert_eq!(agent.request.messages.len(), 1);
+    }
+
+    #[test]
+    fn test_summary_transcript_includes_tool_io() {
+        let call = ChatMessage::assistant_with_content(vec![
+            ContentPart::text("Let me look."),
+            ContentPart::tool_call("call_1", "read_file", json!({"path": "notes.txt"}), None),
+        ])
+        .build();
+        let result = ChatMessage::tool_result_text("call_1", "read_file", "x".repeat(50)).build();
+        let out = summary_transcript(&[Arc::new(call), Arc::new(result)], 1, 10);
+        assert!(out.contains("Assistant: Let me look."));
+        assert!(out.contains("Called read_file({\"path\":\"n... [10 more chars]"));
+        assert!(out.contains("read_file returned: xxxxxxxxxx... [40 more chars]"));
+    }
+}
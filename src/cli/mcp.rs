@@ -1,4 +1,5 @@
 use crate::config::Config;
+use crate::tools::env::EnvPolicy;
 use crate::tools::mcp::{cleanup_mcp, load_mcp_tools};
 use clap::Subcommand;
 use std::path::PathBuf;
@@ -15,7 +16,7 @@ pub async fn mcp_cmd(_workspace: PathBuf, cmd: McpCmd) -> anyhow::Result<()> {
             let cfg_path = Config::default_config_path();
             let cfg = Config::load_from_yaml(&cfg_path)?;
             if let Some(mcp_path) = Config::find_config_file(&cfg.tools.mcp_config_path) {
-                match load_mcp_tools(&mcp_path).await {
+                match load_mcp_tools(&mcp_path, &EnvPolicy::from_config(&cfg.tools.env)).await {
                     Ok(tools) => {
                         if tools.is_empty() {
                             println!("No MCP tools found");
@@ -1,19 +1,34 @@
 use clap::{Parser, Subcommand};
 use colored::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use crate::agent::Agent;
+use crate::agent::{Agent, DEFAULT_SUMMARY_PROMPT, SummaryOptions};
 use crate::cli::skills::fetch_or_update_skills;
-use crate::config::Config;
+use crate::config::{Config, NotesConfig};
 use crate::llm::LlmClient;
+use crate::token::{ApproxEstimator, TokenEstimator, estimator_for};
 use crate::tools::Tool;
-use crate::tools::mcp::load_mcp_tools;
-use crate::tools::note::{RecallNotesTool, RecordNoteTool};
+use crate::tools::background::{BashKillTool, BashListTool, BashOutputTool, cleanup_background};
+use crate::tools::env::EnvPolicy;
+use crate::tools::mcp::{cleanup_mcp, load_mcp_tools};
+use crate::tools::note::{
+    DeleteNoteTool, RecallNotesTool, RecordNoteTool, SearchNotesTool, UpdateNoteTool, memory_prompt,
+};
+use crate::tools::note_store::{NoteStore, NoteStores};
+use crate::tools::paths::{PathPolicy, expand_path};
+use crate::tools::read_tracker::ReadTracker;
+use crate::tools::sandbox::Sandbox;
+use crate::tools::shell::cleanup_shells;
 use crate::tools::{
     bash::BashTool,
+    fetch::FetchTool,
     file::{EditTool, ReadTool, WriteTool},
+    notebook::{EditNotebookTool, ReadNotebookTool},
+    patch::ApplyPatchTool,
+    search::{GlobTool, GrepTool},
     skills::{GetSkillTool, SkillLoader},
+    tree::ListDirTool,
 };
 #[cfg(feature = "embed-skills")]
 use include_dir::{Dir, include_dir};
@@ -21,6 +36,7 @@ use include_dir::{Dir, include_dir};
 use which::which;
 
 mod mcp;
+mod notes;
 mod repl;
 mod run;
 mod skills;
@@ -49,6 +65,9 @@ pub struct Cli {
     /// Conflicts with --workspace
     #[arg(value_name = "WORKSPACE", conflicts_with = "workspace")]
     pub workspace_pos: Option<PathBuf>,
+    /// Additional directory the tools may read and write (repeatable)
+    #[arg(long = "add-dir", value_name = "DIR", global = true)]
+    pub add_dirs: Vec<PathBuf>,
 
     /// Command to run (default: repl)
     #[command(subcommand)]
@@ -76,6 +95,11 @@ pub enum Command {
         #[command(subcommand)]
         cmd: mcp::McpCmd,
     },
+    /// Notes (agent memory) operations
+    Notes {
+        #[command(subcommand)]
+        cmd: notes::NotesCmd,
+    },
     /// Config operations
     Config {
         #[command(subcommand)]
@@ -90,19 +114,78 @@ pub async fn run_cli() -> anyhow::Result<()> {
         .or(cli.workspace_pos)
         .unwrap_or(std::env::current_dir()?);
     tokio::fs::create_dir_all(&workspace).await.ok();
+    // relative to where miniagent was started, not to the workspace
+    let add_dirs: Vec<PathBuf> = cli
+        .add_dirs
+        .iter()
+        .map(|d| std::path::absolute(d).unwrap_or_else(|_| d.clone()))
+        .collect();
 
-    match cli.command.unwrap_or(Command::Repl) {
-        Command::Repl => repl::repl(workspace).await,
-        Command::Run { prompt } => run::run_once(workspace, prompt).await,
-        Command::Tools { cmd } => tools::tools_cmd(workspace, cmd).await,
+    let result = match cli.command.unwrap_or(Command::Repl) {
+        Command::Repl => repl::repl(workspace, &add_dirs).await,
+        Command::Run { prompt } => run::run_once(workspace, &add_dirs, prompt).await,
+        Command::Tools { cmd } => tools::tools_cmd(workspace, &add_dirs, cmd).await,
         Command::Skills { cmd } => skills::skills_cmd(workspace, cmd).await,
         Command::Mcp { cmd } => mcp::mcp_cmd(workspace, cmd).await,
+        Command::Notes { cmd } => notes::notes_cmd(workspace, cmd).await,
         Command::Config { cmd } => userconfig::config_cmd(cmd).await,
+    };
+    shutdown().await;
+    result
+}
+
+/// Stop child processes started during the session (background commands, persistent shells,
+/// MCP servers).
+pub(super) async fn shutdown() {
+    cleanup_background().await;
+    cleanup_shells().await;
+    cleanup_mcp().await;
+}
+
+/// The workspace note store plus the global one from `tools.notes.global_path`.
+pub(super) fn note_stores(notes: &NotesConfig, workspace: &Path) -> NoteStores {
+    let global = notes.global_path.trim();
+    NoteStores {
+        workspace: NoteStore::new(workspace.join(".agent_memory.json")),
+        global: (!global.is_empty()).then(|| NoteStore::new(expand_path(workspace, global))),
     }
 }
 
+/// The "## Memory" system prompt section for a session starting with `first_message`, or
+/// `None` when notes or `tools.notes.auto_recall` are disabled or nothing was recorded.
+pub(super) async fn recall_memory(
+    cfg: &Config,
+    workspace: &Path,
+    first_message: &str,
+) -> Option<String> {
+    if !cfg.tools.enable_note || !cfg.tools.notes.auto_recall {
+        return None;
+    }
+    let notes = match note_stores(&cfg.tools.notes, workspace).load(None).await {
+        Ok(n) => n,
+        Err(e) => {
+            eprintln!("{} {}", "Skipping memory recall:".yellow(), e);
+            return None;
+        }
+    };
+    let estimator: Box<dyn TokenEstimator> = match cfg.model_info() {
+        Some(info) => estimator_for(info.tokenizer),
+        None => Box::new(ApproxEstimator),
+    };
+    memory_prompt(
+        &notes,
+        Some(first_message),
+        cfg.tools.notes.recall_tokens,
+        &*estimator,
+    )
+}
+
+/// `add_dirs` come from `--add-dir` and extend `tools.files.allowed_dirs`. With a
+/// `first_message`, notes relevant to it are recalled into the system prompt.
 pub(super) async fn build_agent(
     workspace: PathBuf,
+    add_dirs: &[PathBuf],
+    first_message: Option<&str>,
 ) -> anyhow::Result<(Agent, Option<Arc<tokio::sync::RwLock<SkillLoader>>>, Config)> {
     let cfg_path = Config::default_config_path();
     if !cfg_path.exists() {
@@ -127,10 +210,7 @@ pub(super) async fn build_agent(
                         .await
                 {
                     eprintln!("{} {}", "Auto-fetch of Claude Skills failed:".yellow(), e);
-                    eprintln!(
-                        "{}",
-                        "Tip: run 'miniagent skills fetch' later to install skills."
-                    );
+                    eprintln!("Tip: run 'miniagent skills fetch' later to install skills.");
                 } else {
                     println!(
                         "{} {}",
@@ -163,21 +243,86 @@ pub(super) async fn build_agent(
     let llm_primary = LlmClient::from_config(&cfg.llm).await?;
 
     // Tools
+    let env_policy = EnvPolicy::from_config(&cfg.tools.env);
+    let allowed_dirs: Vec<PathBuf> = cfg
+        .tools
+        .files
+        .allowed_dirs
+        .iter()
+        .map(|d| expand_path(&workspace, d))
+        .chain(add_dirs.iter().cloned())
+        .collect();
+    let read_only_dirs: Vec<PathBuf> = cfg
+        .tools
+        .files
+        .read_only_dirs
+        .iter()
+        .map(|d| expand_path(&workspace, d))
+        .collect();
+    let paths = Arc::new(
+        PathPolicy::new(&workspace)
+            .with_dirs(&allowed_dirs)
+            .with_read_only_dirs(&read_only_dirs),
+    );
     let mut toolset: Vec<Arc<dyn Tool>> = Vec::new();
     if cfg.tools.enable_bash {
-        toolset.push(Arc::new(BashTool {
-            workspace: workspace.clone(),
-        }));
+        // the sandbox follows the file tools: their directories are writable or read-only
+        // for sandboxed commands too
+        let mut bash_cfg = cfg.tools.bash.clone();
+        bash_cfg
+            .sandbox
+            .writable_paths
+            .extend(allowed_dirs.iter().map(|d| d.display().to_string()));
+        bash_cfg
+            .sandbox
+            .read_only_paths
+            .extend(read_only_dirs.iter().map(|d| d.display().to_string()));
+        toolset.push(Arc::new(
+            BashTool::new(workspace.clone(), bash_cfg)?.with_env_policy(env_policy.clone()),
+        ));
+        toolset.push(Arc::new(BashOutputTool));
+        toolset.push(Arc::new(BashListTool));
+        toolset.push(Arc::new(BashKillTool));
     }
     if cfg.tools.enable_file_tools {
+        // shared so a read by one tool allows writes by the others
+        let tracker = Arc::new(ReadTracker::new(cfg.tools.files.require_read_before_write));
         toolset.push(Arc::new(ReadTool {
-            workspace: workspace.clone(),
+            paths: paths.clone(),
+            tracker: tracker.clone(),
         }));
         toolset.push(Arc::new(WriteTool {
-            workspace: workspace.clone(),
+            paths: paths.clone(),
+            tracker: tracker.clone(),
+            max_write_bytes: cfg.tools.files.max_write_bytes,
         }));
         toolset.push(Arc::new(EditTool {
-            workspace: workspace.clone(),
+            paths: paths.clone(),
+            tracker: tracker.clone(),
+            max_write_bytes: cfg.tools.files.max_write_bytes,
+        }));
+        toolset.push(Arc::new(ApplyPatchTool {
+            paths: paths.clone(),
+            tracker: tracker.clone(),
+            max_write_bytes: cfg.tools.files.max_write_bytes,
+        }));
+        toolset.push(Arc::new(ReadNotebookTool {
+            paths: paths.clone(),
+            tracker: tracker.clone(),
+        }));
+        toolset.push(Arc::new(EditNotebookTool {
+            paths: paths.clone(),
+            tracker: tracker.clone(),
+            max_write_bytes: cfg.tools.files.max_write_bytes,
+        }));
+        toolset.push(Arc::new(GlobTool {
+            paths: paths.clone(),
+        }));
+        toolset.push(Arc::new(GrepTool {
+            paths: paths.clone(),
+        }));
+        toolset.push(Arc::new(ListDirTool {
+            paths: paths.clone(),
         }));
     }
     let mut skill_loader: Option<Arc<tokio::sync::RwLock<SkillLoader>>> = None;
@@ -235,24 +380,16 @@ pub(super) async fn build_agent(
                 {
                     if let Some(home) = dirs::home_dir() {
                         let target = home.join(".miniagent").join("skills");
-                        if !target.exists() {
-                            if let Err(e) = fetch_or_update_skills(
+                        if !target.exists()
+                            && let Err(e) = fetch_or_update_skills(
                                 "https://github.com/anthropics/skills",
                                 &target,
                                 false,
                             )
                             .await
-                            {
-                                eprintln!(
-                                    "{} {}",
-                                    "Failed to auto-fetch Claude Skills:".yellow(),
-                                    e
-                                );
-                                eprintln!(
-                                    "{}",
-                                    "You can also run 'miniagent skills fetch' manually."
-                                );
-                            }
+                        {
+                            eprintln!("{} {}", "Failed to auto-fetch Claude Skills:".yellow(), e);
+                            eprintln!("You can also run 'miniagent skills fetch' manually.");
                         }
                         if target.exists() {
                             println!(
@@ -274,20 +411,31 @@ pub(super) async fn build_agent(
         }));
         skill_loader = Some(loader);
     }
+    if cfg.tools.enable_fetch {
+        toolset.push(Arc::new(FetchTool::new(cfg.tools.fetch.clone())?));
+    }
     if cfg.tools.enable_note {
-        let mem = workspace.join(".agent_memory.json");
+        let stores = note_stores(&cfg.tools.notes, &workspace);
         toolset.push(Arc::new(RecordNoteTool {
-            memory_file: mem.clone(),
+            stores: stores.clone(),
+        }));
+        toolset.push(Arc::new(RecallNotesTool {
+            stores: stores.clone(),
         }));
-        toolset.push(Arc::new(RecallNotesTool { memory_file: mem }));
+        toolset.push(Arc::new(SearchNotesTool {
+            stores: stores.clone(),
+        }));
+        toolset.push(Arc::new(UpdateNoteTool {
+            stores: stores.clone(),
+        }));
+        toolset.push(Arc::new(DeleteNoteTool { stores }));
     }
-    if cfg.tools.enable_mcp {
-        if let Some(mcp_path) = Config::find_config_file(&cfg.tools.mcp_config_path) {
-            if let Ok(mcp_tools) = load_mcp_tools(&mcp_path).await {
-                for t in mcp_tools {
-                    toolset.push(t);
-                }
-            }
+    if cfg.tools.enable_mcp
+        && let Some(mcp_path) = Config::find_config_file(&cfg.tools.mcp_config_path)
+        && let Ok(mcp_tools) = load_mcp_tools(&mcp_path, &env_policy).await
+    {
+        for t in mcp_tools {
+            toolset.push(t);
         }
     }
 
@@ -325,37 +473,108 @@ pub(super) async fn build_agent(
         }
     };
     #[cfg(not(target_os = "windows"))]
-    let default_shell: String = "bash -lc".to_string();
+    let default_shell: String = if cfg.tools.bash.persistent_shell {
+        "persistent bash session (cd/export persist between calls; restart=true resets it)"
+            .to_string()
+    } else {
+        "bash -lc".to_string()
+    };
     let path_sep = if cfg!(target_os = "windows") {
         "\\"
     } else {
         "/"
     };
     let env_section = format!(
-        "\n\n## Execution Environment\n- OS: {} ({})\n- Default shell for tool 'bash': {}\n- Path separator: {}\n- Tip: On Windows, prefer PowerShell-friendly commands (e.g., Get-ChildItem -Force instead of 'ls -la').",
+        "\n\n## Execution Environment\n- OS: {} ({})\n- Default shell for tool 'bash': {}\n- Path separator: {}\n- Long-running commands (dev servers, watchers): run them with bash run_in_background=true, then poll with bash_output and stop with bash_kill.\n- Tip: On Windows, prefer PowerShell-friendly commands (e.g., Get-ChildItem -Force instead of 'ls -la').",
         os, arch, default_shell, path_sep
     );
     if !system_prompt.contains("## Execution Environment") {
         system_prompt.push_str(&env_section);
+        if cfg.tools.enable_bash
+            && let Some(sb) = Sandbox::from_config(&cfg.tools.bash.sandbox, &workspace)
+        {
+            system_prompt.push_str(&format!(
+                "\n- Sandbox for tool 'bash': {}. Commands that need more will fail; explain this to the user instead of retrying.",
+                sb.describe()
+            ));
+        }
     }
 
     if !system_prompt.contains("Current Workspace") {
         let abs = workspace.canonicalize().unwrap_or(workspace.clone());
-        let appendix = format!(
+        let mut appendix = format!(
             "\n\n## Current Workspace\nYou are currently working in: `{}`\nAll relative paths will be resolved relative to this directory.",
             abs.display()
         );
+        if cfg.tools.enable_file_tools {
+            appendix.push_str("\nFile tools can only access the workspace");
+            let extra: Vec<String> = paths
+                .extra_dirs()
+                .map(|(d, ro)| {
+                    if ro {
+                        format!("`{}` (read-only)", d.display())
+                    } else {
+                        format!("`{}`", d.display())
+                    }
+                })
+                .collect();
+            if extra.is_empty() {
+                appendix.push('.');
+            } else {
+                appendix.push_str(&format!(" and: {}.", extra.join(", ")));
+            }
+            appendix.push_str(
+                "\nTo get an overview use list_dir; to find files or search their contents, use the glob and grep tools rather than find/grep/Get-ChildItem through bash; they respect .gitignore and behave the same on every OS. Use read_notebook and edit_notebook for Jupyter notebooks (.ipynb).",
+            );
+        }
         system_prompt.push_str(&appendix);
     }
+    if let Some(message) = first_message
+        && let Some(memory) = recall_memory(&cfg, &workspace, message).await
+    {
+        system_prompt.push_str(&memory);
+    }
 
-    let agent = Agent::builder(llm_primary.clone(), system_prompt)
+    // Summarization: optional prompt template file and cheaper model
+    let summary_cfg = &cfg.agent.summary;
+    let prompt_template = match &summary_cfg.prompt_path {
+        Some(p) => {
+            let path = Config::find_config_file(p).unwrap_or_else(|| PathBuf::from(p));
+            std::fs::read_to_string(&path).map_err(|e| {
+                anyhow::anyhow!("failed to read summary prompt {}: {}", path.display(), e)
+            })?
+        }
+        None => DEFAULT_SUMMARY_PROMPT.to_string(),
+    };
+    let summary_llm = match &summary_cfg.model {
+        Some(m) if *m != cfg.llm.model => {
+            let mut llm_cfg = cfg.llm.clone();
+            llm_cfg.model = m.clone();
+            Some(LlmClient::from_config(&llm_cfg).await?)
+        }
+        _ => None,
+    };
+    let summary = SummaryOptions {
+        prompt_template,
+        max_words: summary_cfg.max_words,
+        max_tool_output_chars: summary_cfg.max_tool_output_chars,
+        llm: summary_llm,
+    };
+
+    // Context budget and tokenizer come from the model registry unless set explicitly
+    let model_info = cfg.model_info();
+    let mut builder = Agent::builder(llm_primary.clone(), system_prompt)
         .with_tools(toolset)
         .with_max_steps(cfg.agent.max_steps)
-        .with_token_limit(cfg.agent.token_limit)
-        .with_completion_reserve(cfg.agent.completion_reserve)
+        .with_token_limit(cfg.agent.effective_token_limit(model_info.as_ref()))
+        .with_completion_reserve(cfg.agent.effective_completion_reserve(model_info.as_ref()))
         .with_workspace(workspace)
         .with_retry(cfg.llm.retry.clone())
-        .build();
+        .with_summary(summary);
+    if let Some(info) = &model_info {
+        builder = builder.with_estimator(estimator_for(info.tokenizer));
+    }
+    let agent = builder.build();
 
     Ok((agent, skill_loader, cfg))
 }
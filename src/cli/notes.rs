@@ -0,0 +1,467 @@
+use super::note_stores;
+use crate::config::{Config, NotesConfig};
+use crate::tools::note::format_note;
+use crate::tools::note_store::{
+    Note, NoteScope, NoteStore, NoteStores, NoteUpdate, from_markdown, parse_notes, search,
+    to_markdown,
+};
+use anyhow::{Context, anyhow, bail};
+use clap::Subcommand;
+use colored::*;
+use std::path::{Path, PathBuf};
+
+#[derive(Subcommand, Debug)]
+pub enum NotesCmd {
+    /// List notes, oldest first (or best match first with --search)
+    List {
+        /// workspace or global (default: both)
+        #[arg(long, value_parser = NoteScope::parse)]
+        scope: Option<NoteScope>,
+        #[arg(long)]
+        category: Option<String>,
+        #[arg(long)]
+        tag: Option<String>,
+        /// Keep notes matching these keywords, best match first
+        #[arg(long)]
+        search: Option<String>,
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Show a note with all its fields
+    Show {
+        id: u64,
+        #[arg(long)]
+        global: bool,
+    },
+    /// Record a note
+    Add {
+        content: String,
+        #[arg(long, default_value = "general")]
+        category: String,
+        /// Tag the note (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        #[arg(long)]
+        global: bool,
+    },
+    /// Change a note; without --content, --category or --tag the content opens in $EDITOR
+    Edit {
+        id: u64,
+        #[arg(long)]
+        content: Option<String>,
+        #[arg(long)]
+        category: Option<String>,
+        /// Replace the tags (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Remove all tags
+        #[arg(long, conflicts_with = "tags")]
+        clear_tags: bool,
+        #[arg(long)]
+        global: bool,
+    },
+    /// Delete a note
+    Delete {
+        id: u64,
+        #[arg(long)]
+        global: bool,
+    },
+    /// Export notes as Markdown
+    Export {
+        /// workspace or global (default: both)
+        #[arg(long, value_parser = NoteScope::parse)]
+        scope: Option<NoteScope>,
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Import notes from a Markdown export or a note store JSON file, skipping duplicates
+    Import {
+        file: PathBuf,
+        #[arg(long)]
+        global: bool,
+    },
+}
+
+pub async fn notes_cmd(workspace: PathBuf, cmd: NotesCmd) -> anyhow::Result<()> {
+    // without a config file the default note locations apply
+    let path = Config::default_config_path();
+    let notes_cfg = if path.exists() {
+        Config::load_from_yaml(path)?.tools.notes
+    } else {
+        NotesConfig::default()
+    };
+    run(&note_stores(&notes_cfg, &workspace), cmd).await
+}
+
+async fn run(stores: &NoteStores, cmd: NotesCmd) -> anyhow::Result<()> {
+    match cmd {
+        NotesCmd::List {
+            scope,
+            category,
+            tag,
+            search: query,
+            limit,
+        } => {
+            let tag = tag.map(|t| t.trim().to_lowercase());
+            let notes: Vec<(NoteScope, Note)> = load(stores, scope)
+                .await?
+                .into_iter()
+                .filter(|(_, n)| category.as_ref().is_none_or(|c| n.category == *c))
+                .filter(|(_, n)| tag.as_ref().is_none_or(|t| n.tags.contains(t)))
+                .collect();
+            let limit = limit.unwrap_or(usize::MAX);
+            let shown: Vec<&(NoteScope, Note)> = match &query {
+                Some(q) => search(&notes, q, limit),
+                None => notes.iter().take(limit).collect(),
+            };
+            if shown.is_empty() {
+                println!("No notes found");
+            }
+            for (scope, note) in shown {
+                println!("{}", format_note(*scope, note));
+            }
+        }
+        NotesCmd::Show { id, global } => {
+            let store = store_for(stores, global)?;
+            let note = store
+                .load()
+                .await
+                .map_err(|e| anyhow!(e))?
+                .into_iter()
+                .find(|n| n.id == id)
+                .ok_or_else(|| anyhow!("no note with id {} in {}", id, store.path().display()))?;
+            println!("{} {}", "id:".bold(), note.id);
+            println!("{} {}", "scope:".bold(), scope_of(global).as_str());
+            println!("{} {}", "category:".bold(), note.category);
+            if !note.tags.is_empty() {
+                println!("{} {}", "tags:".bold(), note.tags.join(", "));
+            }
+            println!("{} {}", "created:".bold(), note.created_at.to_rfc3339());
+            println!("{} {}", "updated:".bold(), note.updated_at.to_rfc3339());
+            println!("\n{}", note.content);
+        }
+        NotesCmd::Add {
+            content,
+            category,
+            tags,
+            global,
+        } => {
+            let note = store_for(stores, global)?
+                .add(&content, &category, &tags)
+                .await
+                .map_err(|e| anyhow!(e))?;
+            println!("Recorded {}", format_note(scope_of(global), &note));
+        }
+        NotesCmd::Edit {
+            id,
+            content,
+            category,
+            tags,
+            clear_tags,
+            global,
+        } => {
+            let store = store_for(stores, global)?;
+            let tags = if clear_tags {
+                Some(Vec::new())
+            } else {
+                (!tags.is_empty()).then_some(tags)
+            };
+            let mut update = NoteUpdate {
+                content,
+                category,
+                tags,
+            };
+            if update.content.is_none() && update.category.is_none() && update.tags.is_none() {
+                let note = store
+                    .load()
+                    .await
+                    .map_err(|e| anyhow!(e))?
+                    .into_iter()
+                    .find(|n| n.id == id)
+                    .ok_or_else(|| anyhow!("no note with id {}", id))?;
+                let edited = edit_in_editor(&editor(), &note.content)?;
+                if edited.trim() == note.content {
+                    println!("Note #{} unchanged", id);
+                    return Ok(());
+                }
+                update.content = Some(edited);
+            }
+            let note = store.update(id, update).await.map_err(|e| anyhow!(e))?;
+            println!("Updated {}", format_note(scope_of(global), &note));
+        }
+        NotesCmd::Delete { id, global } => {
+            let note = store_for(stores, global)?
+                .delete(id)
+                .await
+                .map_err(|e| anyhow!(e))?;
+            println!("Deleted {}", format_note(scope_of(global), &note));
+        }
+        NotesCmd::Export { scope, output } => {
+            let mut sections: Vec<(String, Vec<Note>)> = Vec::new();
+            for s in [NoteScope::Workspace, NoteScope::Global] {
+                if scope.is_some_and(|wanted| wanted != s) {
+                    continue;
+                }
+                let Ok(store) = stores.get(s) else {
+                    if scope.is_some() {
+                        bail!("global notes are disabled (tools.notes.global_path)");
+                    }
+                    continue;
+                };
+                let notes = store.load().await.map_err(|e| anyhow!(e))?;
+                sections.push((format!("{} notes", title_case(s.as_str())), notes));
+            }
+            let sections: Vec<(&str, &[Note])> = sections
+                .iter()
+                .map(|(title, notes)| (title.as_str(), notes.as_slice()))
+                .collect();
+            let markdown = to_markdown(&sections);
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, markdown)
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    println!("Exported notes to {}", path.display());
+                }
+                None => print!("{}", markdown),
+            }
+        }
+        NotesCmd::Import { file, global } => {
+            let notes = read_import(&file)?;
+            let store = store_for(stores, global)?;
+            let total = notes.len();
+            let added = store.import(notes).await.map_err(|e| anyhow!(e))?;
+            println!(
+                "Imported {} of {} notes into {} ({} already present)",
+                added,
+                total,
+                store.path().display(),
+                total - added
+            );
+        }
+    }
+    Ok(())
+}
+
+fn scope_of(global: bool) -> NoteScope {
+    if global {
+        NoteScope::Global
+    } else {
+        NoteScope::Workspace
+    }
+}
+
+fn store_for(stores: &NoteStores, global: bool) -> anyhow::Result<&NoteStore> {
+    stores.get(scope_of(global)).map_err(|e| anyhow!(e))
+}
+
+async fn load(
+    stores: &NoteStores,
+    scope: Option<NoteScope>,
+) -> anyhow::Result<Vec<(NoteScope, Note)>> {
+    if let Some(s) = scope {
+        stores.get(s).map_err(|e| anyhow!(e))?;
+    }
+    stores.load(scope).await.map_err(|e| anyhow!(e))
+}
+
+fn title_case(s: &str) -> String {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .map(|c| c.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Notes from a `notes export` Markdown file or a note store (`.agent_memory.json`).
+fn read_import(file: &Path) -> anyhow::Result<Vec<Note>> {
+    let text = std::fs::read_to_string(file)
+        .with_context(|| format!("failed to read {}", file.display()))?;
+    let notes = if text.trim_start().starts_with(['{', '[']) {
+        parse_notes(&text)
+    } else {
+        from_markdown(&text)
+    };
+    notes.map_err(|e| anyhow!("{}: {}", file.display(), e))
+}
+
+/// `$VISUAL`, `$EDITOR`, or the platform's default editor.
+fn editor() -> String {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| {
+            if cfg!(windows) {
+                "notepad".into()
+            } else {
+                "vi".into()
+            }
+        })
+}
+
+/// Write `content` to a new file with an unpredictable name in the temp directory. The file
+/// must not exist yet, so a planted file or symlink is never written through.
+fn create_temp_note(content: &str) -> std::io::Result<PathBuf> {
+    use std::io::Write;
+    loop {
+        let path = std::env::temp_dir().join(format!(
+            "miniagent-note-{}-{}.md",
+            std::process::id(),
+            uuid::Uuid::new_v4().simple()
+        ));
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(mut file) => {
+                if let Err(e) = file.write_all(content.as_bytes()) {
+                    let _ = std::fs::remove_file(&path);
+                    return Err(e);
+                }
+                return Ok(path);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Let the user edit `content` in `editor` and return the result.
+fn edit_in_editor(editor: &str, content: &str) -> anyhow::Result<String> {
+    let path = create_temp_note(content).context("failed to create a temporary file")?;
+    // the editor may come with arguments, e.g. `code --wait`
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().context("$EDITOR is empty")?;
+    let status = std::process::Command::new(program)
+        .args(parts)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("failed to start editor '{}'", editor));
+    let edited = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+    if !status?.success() {
+        bail!("editor '{}' exited with an error; note unchanged", editor);
+    }
+    Ok(edited?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn contents(notes: &[Note]) -> Vec<(&str, &str, &[String])> {
+        notes
+            .iter()
+            .map(|n| (n.content.as_str(), n.category.as_str(), n.tags.as_slice()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_export_then_import() {
+        let dir = TempDir::new("notes_cli");
+        let source = NoteStores {
+            workspace: NoteStore::new(dir.join("ws.json")),
+            global: Some(NoteStore::new(dir.join("global.json"))),
+        };
+        source
+            .workspace
+            .add(
+                "Build with `cargo build --offline`\n\n## not a heading",
+                "build",
+                &["rust".into()],
+            )
+            .await
+            .unwrap();
+        source
+            .global
+            .as_ref()
+            .unwrap()
+            .add("Prefers short commit subjects", "preference", &[])
+            .await
+            .unwrap();
+
+        let export = dir.join("notes.md");
+        run(
+            &source,
+            NotesCmd::Export {
+                scope: None,
+                output: Some(export.clone()),
+            },
+        )
+        .await
+        .unwrap();
+        let target = NoteStores {
+            workspace: NoteStore::new(dir.join("imported.json")),
+            global: None,
+        };
+        let import = || NotesCmd::Import {
+            file: export.clone(),
+            global: false,
+        };
+        run(&target, import()).await.unwrap();
+        // importing again skips what is already there
+        run(&target, import()).await.unwrap();
+        let mut expected = source.workspace.load().await.unwrap();
+        expected.extend(source.global.as_ref().unwrap().load().await.unwrap());
+        let imported = target.workspace.load().await.unwrap();
+        assert_eq!(contents(&imported), contents(&expected));
+    }
+
+    #[tokio::test]
+    async fn test_edit_note() {
+        let dir = TempDir::new("notes_cli");
+        let stores = NoteStores {
+            workspace: NoteStore::new(dir.join("ws.json")),
+            global: None,
+        };
+        let note = stores
+            .workspace
+            .add("Prefers short commit subjects", "preference", &[])
+            .await
+            .unwrap();
+        run(
+            &stores,
+            NotesCmd::Edit {
+                id: note.id,
+                content: Some("Prefers one-line commit subjects".into()),
+                category: None,
+                tags: vec!["git".into()],
+                clear_tags: false,
+                global: false,
+            },
+        )
+        .await
+        .unwrap();
+        let edited = &stores.workspace.load().await.unwrap()[0];
+        assert_eq!(edited.content, "Prefers one-line commit subjects");
+        assert_eq!(edited.category, "preference");
+        assert_eq!(edited.tags, ["git"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_edit_in_editor_uses_a_fresh_temp_file() {
+        let edited = edit_in_editor("sed -i s/draft/final/", "draft note").unwrap();
+        assert_eq!(edited, "final note");
+        assert!(edit_in_editor("false", "x").is_err());
+        let prefix = format!("miniagent-note-{}-", std::process::id());
+        let left = std::fs::read_dir(std::env::temp_dir())
+            .unwrap()
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+            .count();
+        assert_eq!(left, 0);
+
+        let a = create_temp_note("a").unwrap();
+        let b = create_temp_note("b").unwrap();
+        assert_ne!(a, b);
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(
+            std::fs::metadata(&a).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        std::fs::remove_file(a).unwrap();
+        std::fs::remove_file(b).unwrap();
+    }
+}
@@ -2,15 +2,16 @@ use super::build_agent;
 use crate::agent::Agent;
 use crate::config::Config;
 use colored::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-pub async fn repl(workspace: PathBuf) -> anyhow::Result<()> {
-    let (mut agent, _loader, cfg) = build_agent(workspace.clone()).await?;
+pub async fn repl(workspace: PathBuf, add_dirs: &[PathBuf]) -> anyhow::Result<()> {
+    let (mut agent, _loader, cfg) = build_agent(workspace.clone(), add_dirs, None).await?;
     print_banner();
     print_session(&agent, &workspace, &cfg.llm.model);
 
     use rustyline::{DefaultEditor, error::ReadlineError};
     let mut rl = DefaultEditor::new()?;
+    let mut recalled = false;
     loop {
         match rl.readline("You > ") {
             Ok(line) => {
@@ -21,6 +22,13 @@ pub async fn repl(workspace: PathBuf) -> anyhow::Result<()> {
                 if handle_builtin(&mut agent, input, &cfg).await? {
                     continue;
                 }
+                if !recalled {
+                    // the first message picks which notes are worth putting in the prompt
+                    recalled = true;
+                    if let Some(memory) = super::recall_memory(&cfg, &workspace, input).await {
+                        agent.extend_system_prompt(&memory);
+                    }
+                }
                 agent.add_user_message(input.to_string());
                 println!("\n{}\n", "Agent is thinking...".dimmed());
                 let _ = agent.run().await?;
@@ -46,6 +54,7 @@ pub async fn handle_builtin(agent: &mut Agent, input: &str, cfg: &Config) -> any
     match input.to_lowercase().as_str() {
         "/exit" | "/quit" | "/q" => {
             println!("{}", "Goodbye".yellow());
+            super::shutdown().await;
             std::process::exit(0);
         }
         "/help" => {
@@ -53,23 +62,19 @@ pub async fn handle_builtin(agent: &mut Agent, input: &str, cfg: &Config) -> any
             return Ok(true);
         }
         "/clear" => {
-            let sys = agent.messages.first().cloned();
-            agent.messages.clear();
-            if let Some(s) = sys {
-                agent.messages.push(s);
-            }
+            agent.clear_history();
             println!("{}", "History cleared".green());
             return Ok(true);
         }
         "/history" => {
-            println!("messages: {}", agent.messages.len());
+            println!("messages: {}", agent.history.len());
             return Ok(true);
         }
         "/stats" => {
             let mut user = 0usize;
             let mut assistant = 0usize;
             let mut tool = 0usize;
-            for m in &agent.messages {
+            for m in agent.history.iter() {
                 match m.role {
                     siumai::types::MessageRole::User => user += 1,
                     siumai::types::MessageRole::Assistant => assistant += 1,
@@ -79,12 +84,30 @@ pub async fn handle_builtin(agent: &mut Agent, input: &str, cfg: &Config) -> any
             }
             println!(
                 "messages: {} (user: {}, assistant: {}, tool: {})",
-                agent.messages.len(),
+                agent.history.len(),
                 user,
                 assistant,
                 tool
             );
             println!("tools: {}", agent.tool_names().len());
+            let cal = agent.token_calibration();
+            println!(
+                "tokens: ~{} estimated / {} limit (reserve {}), correction x{:.2}",
+                agent.estimated_tokens(),
+                agent.token_limit,
+                agent.completion_reserve,
+                cal.factor()
+            );
+            match cal.last_drift_percent() {
+                Some(drift) => println!(
+                    "last request: estimated {} vs actual {} tokens ({:+.1}% drift, {} sample(s))",
+                    cal.last_estimate(),
+                    cal.last_actual(),
+                    drift,
+                    cal.samples()
+                ),
+                None => println!("last request: no provider usage reported yet"),
+            }
             return Ok(true);
         }
         "/version" => {
@@ -101,17 +124,33 @@ pub async fn handle_builtin(agent: &mut Agent, input: &str, cfg: &Config) -> any
             if let Some(u) = &cfg.llm.base_url {
                 println!("base_url: {}", u);
             }
+            match cfg.model_info() {
+                Some(info) => {
+                    println!(
+                        "model info: {} (context {}, max output {}, tokenizer {:?})",
+                        info.id, info.context_window, info.max_output_tokens, info.tokenizer
+                    );
+                    if let Some(p) = info.pricing {
+                        println!(
+                            "pricing: ${}/Mtok input, ${}/Mtok output",
+                            p.input_per_mtok, p.output_per_mtok
+                        );
+                    }
+                }
+                None => println!("model info: (not in registry; using defaults)"),
+            }
             println!(
                 "token_limit: {} reserve: {}",
-                cfg.agent.token_limit, cfg.agent.completion_reserve
+                agent.token_limit, agent.completion_reserve
             );
             println!(
-                "retry: enabled={} max_retries={} initial_delay={}s max_delay={}s base={}",
+                "retry: enabled={} max_retries={} initial_delay={}s max_delay={}s base={} retryable_status={:?}",
                 cfg.llm.retry.enabled,
                 cfg.llm.retry.max_retries,
                 cfg.llm.retry.initial_delay,
                 cfg.llm.retry.max_delay,
-                cfg.llm.retry.exponential_base
+                cfg.llm.retry.exponential_base,
+                cfg.llm.retry.retryable_status_codes
             );
             return Ok(true);
         }
@@ -151,10 +190,10 @@ fn print_banner() {
     );
 }
 
-fn print_session(agent: &Agent, workspace: &PathBuf, model: &str) {
+fn print_session(agent: &Agent, workspace: &Path, model: &str) {
     println!("{} {}", "Model:".dimmed(), model);
     println!("{} {}", "Workspace:".dimmed(), workspace.display());
-    println!("{} {}", "Messages:".dimmed(), agent.messages.len());
+    println!("{} {}", "Messages:".dimmed(), agent.history.len());
 }
 
 fn print_help() {
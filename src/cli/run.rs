@@ -1,8 +1,12 @@
 use super::build_agent;
 use std::path::PathBuf;
 
-pub async fn run_once(workspace: PathBuf, prompt: String) -> anyhow::Result<()> {
-    let (mut agent, _loader, _cfg) = build_agent(workspace).await?;
+pub async fn run_once(
+    workspace: PathBuf,
+    add_dirs: &[PathBuf],
+    prompt: String,
+) -> anyhow::Result<()> {
+    let (mut agent, _loader, _cfg) = build_agent(workspace, add_dirs, Some(&prompt)).await?;
     agent.add_user_message(prompt);
     let output = agent.run().await?;
     if !output.is_empty() {
@@ -29,7 +29,7 @@ pub struct FetchArgs {
 }
 
 pub async fn skills_cmd(workspace: PathBuf, cmd: SkillsCmd) -> anyhow::Result<()> {
-    let (agent, loader, _cfg) = build_agent(workspace).await?;
+    let (agent, loader, _cfg) = build_agent(workspace, &[], None).await?;
     match cmd {
         SkillsCmd::List => {
             if let Some(l) = loader {
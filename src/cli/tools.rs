@@ -1,5 +1,9 @@
 use super::build_agent;
+use crate::config::{BashConfig, Config};
+use crate::tools::command_rules::{CommandRules, split_commands};
+use anyhow::bail;
 use clap::Subcommand;
+use colored::*;
 use std::path::PathBuf;
 
 #[derive(Subcommand, Debug)]
@@ -14,13 +18,24 @@ pub enum ToolsCmd {
         #[arg(short, long)]
         args: Option<String>,
     },
+    /// Check a shell command against the tools.bash allow/deny rules
+    CheckCommand { command: String },
 }
 
-pub async fn tools_cmd(workspace: PathBuf, cmd: ToolsCmd) -> anyhow::Result<()> {
-    let (agent, _loader, _cfg) = build_agent(workspace).await?;
+pub async fn tools_cmd(
+    workspace: PathBuf,
+    add_dirs: &[PathBuf],
+    cmd: ToolsCmd,
+) -> anyhow::Result<()> {
+    // checking a command needs no agent, so it is only built for the other subcommands
+    let load_agent = async || {
+        build_agent(workspace.clone(), add_dirs, None)
+            .await
+            .map(|(agent, _loader, _cfg)| agent)
+    };
     match cmd {
         ToolsCmd::List => {
-            let names = agent.tool_names();
+            let names = load_agent().await?.tool_names();
             if names.is_empty() {
                 println!("No tools loaded");
             } else {
@@ -30,7 +45,7 @@ pub async fn tools_cmd(workspace: PathBuf, cmd: ToolsCmd) -> anyhow::Result<()>
                 }
             }
         }
-        ToolsCmd::Describe { name } => match agent.tool_schema(&name) {
+        ToolsCmd::Describe { name } => match load_agent().await?.tool_schema(&name) {
             Some((n, desc, params)) => {
                 println!(
                     "name: {}\ndescription: {}\nparameters:\n{}",
@@ -52,7 +67,7 @@ pub async fn tools_cmd(workspace: PathBuf, cmd: ToolsCmd) -> anyhow::Result<()>
                 },
                 None => serde_json::json!({}),
             };
-            match agent.call_tool_direct(&name, parsed).await {
+            match load_agent().await?.call_tool_direct(&name, parsed).await {
                 Some(res) => {
                     println!("success: {}", res.success);
                     if res.success {
@@ -65,6 +80,29 @@ pub async fn tools_cmd(workspace: PathBuf, cmd: ToolsCmd) -> anyhow::Result<()>
                 None => println!("Tool '{}' not found", name),
             }
         }
+        ToolsCmd::CheckCommand { command } => check_command(&command)?,
     }
     Ok(())
 }
+
+fn check_command(command: &str) -> anyhow::Result<()> {
+    // without a config file the built-in default rules apply
+    let path = Config::default_config_path();
+    let bash = if path.exists() {
+        Config::load_from_yaml(path)?.tools.bash
+    } else {
+        BashConfig::default()
+    };
+    let rules = CommandRules::new(&bash.allow, &bash.deny)?;
+    println!("Commands:");
+    for c in split_commands(command) {
+        println!("  - {}", c);
+    }
+    match rules.check(command) {
+        Ok(()) => {
+            println!("{}", "allowed".green().bold());
+            Ok(())
+        }
+        Err(denial) => bail!("denied: {}", denial),
+    }
+}
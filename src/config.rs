@@ -1,4 +1,6 @@
+use crate::models::{ModelInfo, ModelRegistry};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -8,6 +10,9 @@ pub struct RetryConfig {
     pub initial_delay: f32,
     pub max_delay: f32,
     pub exponential_base: f32,
+    /// HTTP status codes worth retrying; any other status (e.g. 400 validation errors) is fatal.
+    #[serde(default = "default_retryable_status_codes")]
+    pub retryable_status_codes: Vec<u16>,
 }
 
 impl Default for RetryConfig {
@@ -18,10 +23,15 @@ impl Default for RetryConfig {
             initial_delay: 1.0,
             max_delay: 60.0,
             exponential_base: 2.0,
+            retryable_status_codes: default_retryable_status_codes(),
         }
     }
 }
 
+fn default_retryable_status_codes() -> Vec<u16> {
+    vec![408, 409, 429, 500, 502, 503, 504]
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmConfig {
     #[serde(default = "default_provider")]
@@ -50,10 +60,63 @@ pub struct AgentConfig {
     pub workspace_dir: String,
     #[serde(default = "default_system_prompt")]
     pub system_prompt_path: String,
-    #[serde(default = "default_token_limit")]
-    pub token_limit: usize,
-    #[serde(default = "default_completion_reserve")]
-    pub completion_reserve: usize,
+    /// Context budget; when omitted it comes from the model registry (or 80000 if unknown).
+    #[serde(default)]
+    pub token_limit: Option<usize>,
+    /// Tokens kept free for the completion; when omitted it comes from the model registry.
+    #[serde(default)]
+    pub completion_reserve: Option<usize>,
+    #[serde(default)]
+    pub summary: SummaryConfig,
+}
+
+/// History summarization settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummaryConfig {
+    /// Model used for summaries (same provider and credentials); defaults to `llm.model`.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Prompt template file, resolved like `system_prompt_path`. `{EXECUTION}` is replaced
+    /// with the transcript to summarize (appended if missing) and `{MAX_WORDS}` with `max_words`.
+    #[serde(default)]
+    pub prompt_path: Option<String>,
+    #[serde(default = "default_summary_max_words")]
+    pub max_words: usize,
+    /// Characters kept from each tool argument / tool output in the summary input.
+    #[serde(default = "default_summary_tool_output_chars")]
+    pub max_tool_output_chars: usize,
+}
+
+impl Default for SummaryConfig {
+    fn default() -> Self {
+        Self {
+            model: None,
+            prompt_path: None,
+            max_words: default_summary_max_words(),
+            max_tool_output_chars: default_summary_tool_output_chars(),
+        }
+    }
+}
+
+fn default_summary_max_words() -> usize {
+    1000
+}
+fn default_summary_tool_output_chars() -> usize {
+    1500
+}
+
+impl AgentConfig {
+    pub fn effective_token_limit(&self, model: Option<&ModelInfo>) -> usize {
+        self.token_limit
+            .or(model.map(|m| m.context_window))
+            .unwrap_or_else(default_token_limit)
+    }
+
+    pub fn effective_completion_reserve(&self, model: Option<&ModelInfo>) -> usize {
+        self.completion_reserve
+            .or(model.map(|m| m.completion_reserve()))
+            .unwrap_or_else(default_completion_reserve)
+    }
 }
 
 fn default_max_steps() -> usize {
@@ -76,10 +139,20 @@ fn default_completion_reserve() -> usize {
 pub struct ToolsConfig {
     #[serde(default = "default_true")]
     pub enable_file_tools: bool,
+    #[serde(default)]
+    pub files: FilesConfig,
     #[serde(default = "default_true")]
     pub enable_bash: bool,
+    #[serde(default)]
+    pub bash: BashConfig,
+    #[serde(default = "default_true")]
+    pub enable_fetch: bool,
+    #[serde(default)]
+    pub fetch: FetchConfig,
     #[serde(default = "default_true")]
     pub enable_note: bool,
+    #[serde(default)]
+    pub notes: NotesConfig,
 
     #[serde(default = "default_true")]
     pub enable_skills: bool,
@@ -90,6 +163,244 @@ pub struct ToolsConfig {
     pub enable_mcp: bool,
     #[serde(default = "default_mcp_path")]
     pub mcp_config_path: String,
+
+    /// Environment of processes started by tools (bash, background processes, MCP servers)
+    #[serde(default)]
+    pub env: EnvConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvConfig {
+    /// Remove credential variables (API keys, `*_TOKEN`, `*_PASSWORD`, ...) before spawning
+    #[serde(default = "default_true")]
+    pub scrub_secrets: bool,
+    /// Additional variables to remove (`*` wildcards allowed)
+    #[serde(default)]
+    pub strip: Vec<String>,
+    /// Variables kept even if they look like credentials (`*` wildcards allowed)
+    #[serde(default)]
+    pub passthrough: Vec<String>,
+    /// Variables to set for every spawned process
+    #[serde(default)]
+    pub set: HashMap<String, String>,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            scrub_secrets: true,
+            strip: Vec::new(),
+            passthrough: Vec::new(),
+            set: HashMap::new(),
+        }
+    }
+}
+
+/// Settings for the file tools (`read_file`, `write_file`, `edit_file`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilesConfig {
+    /// Directories besides the workspace the file tools may read and write
+    /// (relative to the workspace, or `~/...`)
+    #[serde(default)]
+    pub allowed_dirs: Vec<String>,
+    /// Directories the file tools may read but never write, also inside the workspace
+    #[serde(default)]
+    pub read_only_dirs: Vec<String>,
+    /// Refuse to change existing files the model hasn't read in this session, or that
+    /// changed on disk since it last read them
+    #[serde(default = "default_true")]
+    pub require_read_before_write: bool,
+    /// Largest file the file tools will write, in bytes
+    #[serde(default = "default_max_write_bytes")]
+    pub max_write_bytes: usize,
+}
+
+fn default_max_write_bytes() -> usize {
+    10 * 1024 * 1024
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        Self {
+            allowed_dirs: Vec::new(),
+            read_only_dirs: Vec::new(),
+            require_read_before_write: true,
+            max_write_bytes: default_max_write_bytes(),
+        }
+    }
+}
+
+/// Settings for the `fetch_url` tool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchConfig {
+    /// Only these domains (and their subdomains) may be fetched; empty allows any. Listing a
+    /// host also lets it be fetched when it is or resolves to a local or private address
+    #[serde(default)]
+    pub allow_domains: Vec<String>,
+    /// Domains (and their subdomains) that are never fetched; checked before `allow_domains`
+    #[serde(default)]
+    pub deny_domains: Vec<String>,
+    /// Largest response body downloaded, in bytes; the rest is cut off
+    #[serde(default = "default_fetch_max_bytes")]
+    pub max_bytes: usize,
+    /// Timeout for the whole request, in seconds
+    #[serde(default = "default_fetch_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_fetch_max_bytes() -> usize {
+    2 * 1024 * 1024
+}
+
+fn default_fetch_timeout_secs() -> u64 {
+    30
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            allow_domains: Vec::new(),
+            deny_domains: Vec::new(),
+            max_bytes: default_fetch_max_bytes(),
+            timeout_secs: default_fetch_timeout_secs(),
+        }
+    }
+}
+
+/// Settings for the note tools and the memory shown in the system prompt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotesConfig {
+    /// Notes shared by every workspace (`~/...` allowed); empty disables global notes
+    #[serde(default = "default_global_notes_path")]
+    pub global_path: String,
+    /// Add the most relevant notes to the system prompt at the start of a session
+    #[serde(default = "default_true")]
+    pub auto_recall: bool,
+    /// Token budget for the recalled notes
+    #[serde(default = "default_recall_tokens")]
+    pub recall_tokens: usize,
+}
+
+fn default_global_notes_path() -> String {
+    "~/.miniagent/memory.json".to_string()
+}
+
+fn default_recall_tokens() -> usize {
+    800
+}
+
+impl Default for NotesConfig {
+    fn default() -> Self {
+        Self {
+            global_path: default_global_notes_path(),
+            auto_recall: true,
+            recall_tokens: default_recall_tokens(),
+        }
+    }
+}
+
+/// Settings for the `bash` tool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BashConfig {
+    /// Keep one bash process per session so `cd`, `export`, etc. persist (Unix only).
+    #[serde(default)]
+    pub persistent_shell: bool,
+    /// Bytes of stdout (and, separately, stderr) returned to the model; the middle of longer
+    /// output is elided.
+    #[serde(default = "default_max_output_bytes")]
+    pub max_output_bytes: usize,
+    /// Seconds a command may run before its process group is killed (0: no limit). In the
+    /// persistent shell the shell itself is killed, since a hung command would block it.
+    #[serde(default = "default_bash_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default)]
+    pub sandbox: SandboxConfig,
+    /// If non-empty, every command must match one of these rules (glob, or regex with `re:`)
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Commands matching any of these rules are refused
+    #[serde(default = "default_deny_rules")]
+    pub deny: Vec<String>,
+}
+
+impl Default for BashConfig {
+    fn default() -> Self {
+        Self {
+            persistent_shell: false,
+            max_output_bytes: default_max_output_bytes(),
+            timeout_secs: default_bash_timeout_secs(),
+            sandbox: SandboxConfig::default(),
+            allow: Vec::new(),
+            deny: default_deny_rules(),
+        }
+    }
+}
+
+fn default_deny_rules() -> Vec<String> {
+    [
+        // rm -rf on /, /*, ~ or $HOME
+        r"re:^rm\s+(-\S+\s+)*(/|/\*|~/?|\$HOME/?)(\s|$)",
+        r"re:^git\s+push\b.*\s(-f|--force|--force-with-lease)\b",
+        // a `+` refspec force-pushes that ref
+        r#"re:^git\s+push\b.*\s['"]?\+\S"#,
+        // piping a download straight into a shell
+        r"re:\b(curl|wget)\b[^|]*\|\s*(sudo\s+)?(ba|z|da)?sh\b",
+        "mkfs*",
+        r"re:^dd\s.*\bof=/dev/",
+        // fork bomb
+        r"re::\(\)\s*\{\s*:\s*\|\s*:\s*&\s*\}",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+/// Opt-in Linux sandbox for bash commands (Landlock, seccomp and rlimits).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SandboxConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Extra writable paths besides the workspace (relative to the workspace, or `~/...`)
+    #[serde(default)]
+    pub writable_paths: Vec<String>,
+    /// Paths that stay read-only even beneath the workspace or `writable_paths`
+    #[serde(default)]
+    pub read_only_paths: Vec<String>,
+    /// Also allow writes to the system temp directory
+    #[serde(default = "default_true")]
+    pub allow_tmp: bool,
+    /// Set to false to refuse IPv4/IPv6 sockets
+    #[serde(default = "default_true")]
+    pub network: bool,
+    #[serde(default)]
+    pub cpu_time_secs: Option<u64>,
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    #[serde(default)]
+    pub max_processes: Option<u64>,
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            writable_paths: Vec::new(),
+            read_only_paths: Vec::new(),
+            allow_tmp: true,
+            network: true,
+            cpu_time_secs: None,
+            memory_mb: None,
+            max_processes: None,
+        }
+    }
+}
+
+fn default_max_output_bytes() -> usize {
+    30_000
+}
+
+fn default_bash_timeout_secs() -> u64 {
+    600
 }
 
 fn default_true() -> bool {
@@ -107,6 +418,9 @@ pub struct Config {
     pub llm: LlmConfig,
     pub agent: AgentConfig,
     pub tools: ToolsConfig,
+    /// Extra model registry entries; these override built-ins with the same id.
+    #[serde(default)]
+    pub models: Vec<ModelInfo>,
 }
 
 impl Config {
@@ -134,9 +448,15 @@ impl Config {
                 #[serde(default)]
                 system_prompt_path: Option<String>,
                 #[serde(default)]
+                token_limit: Option<usize>,
+                #[serde(default)]
                 completion_reserve: Option<usize>,
                 #[serde(default)]
                 tools: Option<ToolsConfig>,
+                #[serde(default)]
+                models: Vec<ModelInfo>,
+                #[serde(default)]
+                summary: SummaryConfig,
             }
             let flat: Flat = serde_yaml::from_value(raw)?;
             Config {
@@ -153,20 +473,26 @@ impl Config {
                     system_prompt_path: flat
                         .system_prompt_path
                         .unwrap_or_else(default_system_prompt),
-                    token_limit: default_token_limit(),
-                    completion_reserve: flat
-                        .completion_reserve
-                        .unwrap_or_else(default_completion_reserve),
+                    token_limit: flat.token_limit,
+                    completion_reserve: flat.completion_reserve,
+                    summary: flat.summary,
                 },
                 tools: flat.tools.unwrap_or(ToolsConfig {
                     enable_file_tools: true,
+                    files: FilesConfig::default(),
                     enable_bash: true,
+                    bash: BashConfig::default(),
+                    enable_fetch: true,
+                    fetch: FetchConfig::default(),
                     enable_note: true,
+                    notes: NotesConfig::default(),
                     enable_skills: true,
                     skills_dir: default_skills_dir(),
                     enable_mcp: true,
                     mcp_config_path: default_mcp_path(),
+                    env: EnvConfig::default(),
                 }),
+                models: flat.models,
             }
         } else {
             serde_yaml::from_value(raw)?
@@ -192,6 +518,14 @@ impl Config {
         Ok(cfg)
     }
 
+    /// Registry entry for the configured model (user `models` first, then built-ins).
+    pub fn model_info(&self) -> Option<ModelInfo> {
+        ModelRegistry::builtin()
+            .with_overrides(self.models.clone())
+            .lookup(&self.llm.model)
+            .cloned()
+    }
+
     pub fn get_package_dir() -> PathBuf {
         // current crate dir as package dir
         std::path::PathBuf::from(env!("CARGO_PKG_NAME"))
@@ -255,11 +589,11 @@ impl Config {
         // API key resolution
         // Priority: MINIAGENT_API_KEY > provider-specific > existing
         let provider_lc = cfg.llm.provider.to_lowercase();
-        if let Ok(k) = env::var("MINIAGENT_API_KEY") {
-            if !k.is_empty() {
-                cfg.llm.api_key = k;
-                return;
-            }
+        if let Ok(k) = env::var("MINIAGENT_API_KEY")
+            && !k.is_empty()
+        {
+            cfg.llm.api_key = k;
+            return;
         }
 
         // Provider-specific fallbacks
@@ -273,13 +607,11 @@ impl Config {
             "openai-compatible" => Some("OPENAI_API_KEY"),
             _ => None,
         };
-        if let Some(key) = provider_key {
-            if let Ok(k) = env::var(key) {
-                if !k.is_empty() {
-                    cfg.llm.api_key = k;
-                    return;
-                }
-            }
+        if let Some(key) = provider_key
+            && let Ok(k) = env::var(key)
+            && !k.is_empty()
+        {
+            cfg.llm.api_key = k;
         }
     }
 }
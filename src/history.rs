@@ -0,0 +1,121 @@
+use crate::token::TokenEstimator;
+use siumai::types::ChatMessage;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Conversation history with each message's token count cached next to it.
+///
+/// Counts are computed once when a message is added and only recomputed on `recount`
+/// (e.g. after switching tokenizer), so estimating a long history is O(1) per step.
+/// Messages are shared behind `Arc`, so copying entries (summarization, snapshots) does not
+/// deep-clone their content. The agent appends new messages to its cached request as they
+/// arrive and only calls `to_messages` when the history is rewritten.
+#[derive(Clone, Default)]
+pub struct History {
+    entries: Vec<Entry>,
+    total_tokens: usize,
+}
+
+#[derive(Clone)]
+struct Entry {
+    message: Arc<ChatMessage>,
+    tokens: usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, message: ChatMessage, estimator: &dyn TokenEstimator) {
+        let tokens = estimator.count_message(&message);
+        self.total_tokens += tokens;
+        self.entries.push(Entry {
+            message: Arc::new(message),
+            tokens,
+        });
+    }
+
+    /// Append the entry at `index` of another history, reusing its cached count.
+    pub fn push_from(&mut self, other: &History, index: usize) {
+        if let Some(e) = other.entries.get(index) {
+            self.total_tokens += e.tokens;
+            self.entries.push(e.clone());
+        }
+    }
+
+    /// Sum of cached per-message token counts.
+    pub fn total_tokens(&self) -> usize {
+        self.total_tokens
+    }
+
+    /// Recompute every cached count, e.g. after the estimator changed.
+    pub fn recount(&mut self, estimator: &dyn TokenEstimator) {
+        self.total_tokens = 0;
+        for e in &mut self.entries {
+            e.tokens = estimator.count_message(&e.message);
+            self.total_tokens += e.tokens;
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        for e in self.entries.drain(len.min(self.entries.len())..) {
+            self.total_tokens -= e.tokens;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&ChatMessage> {
+        self.entries.get(index).map(|e| e.message.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ChatMessage> {
+        self.entries.iter().map(|e| e.message.as_ref())
+    }
+
+    /// Shared handles to a range of messages (cheap; no content is copied).
+    pub fn slice(&self, range: Range<usize>) -> Vec<Arc<ChatMessage>> {
+        self.entries[range]
+            .iter()
+            .map(|e| e.message.clone())
+            .collect()
+    }
+
+    /// Owned copies of all messages, for rebuilding a `ChatRequest`.
+    pub fn to_messages(&self) -> Vec<ChatMessage> {
+        self.entries.iter().map(|e| (*e.message).clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::ApproxEstimator;
+
+    #[test]
+    fn test_cached_counts_follow_mutations() {
+        let est = ApproxEstimator;
+        let mut h = History::new();
+        h.push(ChatMessage::system("system prompt").build(), &est);
+        h.push(ChatMessage::user("hello there").build(), &est);
+        h.push(ChatMessage::assistant("general kenobi").build(), &est);
+        let all: Vec<_> = h.to_messages();
+        assert_eq!(h.total_tokens(), est.count_messages(&all));
+
+        let mut compacted = History::new();
+        compacted.push_from(&h, 0);
+        compacted.push_from(&h, 1);
+        assert_eq!(compacted.total_tokens(), est.count_messages(&all[..2]));
+
+        h.truncate(1);
+        assert_eq!(h.len(), 1);
+        assert_eq!(h.total_tokens(), est.count_messages(&all[..1]));
+    }
+}
@@ -1,10 +1,15 @@
 pub mod agent;
 pub mod cli;
 pub mod config;
+pub mod history;
 pub mod llm;
 pub mod logger;
+pub mod models;
 pub mod observer;
 pub mod token;
 pub mod tools;
 
+#[cfg(test)]
+mod test_support;
+
 pub use agent::Agent;
@@ -1,6 +1,7 @@
 use crate::config::{LlmConfig, RetryConfig};
 use anyhow::Result;
-use siumai::retry_api::{RetryBackend, RetryOptions, RetryPolicy};
+use siumai::LlmError;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct LlmClient {
@@ -10,7 +11,8 @@ pub struct LlmClient {
 impl LlmClient {
     pub async fn from_config(cfg: &LlmConfig) -> Result<Self> {
         // Normalize provider id: collapse aliases and generic openai-compatible
-        let provider_norm = match cfg.provider.to_lowercase().as_str() {
+        let provider_lc = cfg.provider.to_lowercase();
+        let provider_norm = match provider_lc.as_str() {
             // Aliases
             "google" => "gemini",
             "minimax" => "minimaxi",
@@ -19,7 +21,9 @@ impl LlmClient {
             other => other,
         };
 
-        // Single path: provider_id + optional base_url + retry
+        // Single path: provider_id + optional base_url.
+        // Retries are driven by the agent (see `classify_error` / `backoff_delay`) so that
+        // each failed attempt is visible to the observer and run log.
         let mut b = siumai::provider::Siumai::builder()
             .provider_id(provider_norm)
             .api_key(cfg.api_key.clone())
@@ -27,22 +31,16 @@ impl LlmClient {
         if let Some(url) = &cfg.base_url {
             b = b.base_url(url.clone());
         }
-        if let Some(options) = to_retry_options(&cfg.retry) {
-            b = b.with_retry(options);
-        }
         let client = match b.build().await {
             Ok(c) => c,
             Err(e) => {
                 // Fallback: prefer generic OpenAI-compatible path if a base_url is provided
                 if let Some(url) = &cfg.base_url {
-                    let mut fb = siumai::provider::Siumai::builder()
+                    let fb = siumai::provider::Siumai::builder()
                         .openai()
                         .api_key(cfg.api_key.clone())
                         .model(cfg.model.clone())
                         .base_url(url.clone());
-                    if let Some(options) = to_retry_options(&cfg.retry) {
-                        fb = fb.with_retry(options);
-                    }
                     tracing::warn!(
                         "Falling back to openai-compatible (openai + base_url) due to provider build error: {}",
                         e
@@ -65,21 +63,112 @@ impl LlmClient {
     }
 }
 
-fn to_retry_options(cfg: &RetryConfig) -> Option<RetryOptions> {
-    if !cfg.enabled {
-        return None;
+/// Whether a failed LLM call should be attempted again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    Retryable,
+    Fatal,
+}
+
+/// Classify an LLM error using the retry config.
+///
+/// Errors carrying an HTTP status are retryable only if the status is listed in
+/// `retryable_status_codes`; other errors (network, timeout, rate limit, ...) defer
+/// to siumai's own classification.
+pub fn classify_error(err: &LlmError, cfg: &RetryConfig) -> ErrorClass {
+    let retryable = match err.status_code() {
+        Some(code) => cfg.retryable_status_codes.contains(&code),
+        None => err.is_retryable(),
+    };
+    if retryable {
+        ErrorClass::Retryable
+    } else {
+        ErrorClass::Fatal
+    }
+}
+
+/// Short human-readable category of an LLM error (e.g. "network", "rate_limit").
+pub fn error_category(err: &LlmError) -> String {
+    use siumai::error::ErrorCategory::*;
+    let label = match err.category() {
+        Network => "network",
+        Authentication => "authentication",
+        RateLimit => "rate_limit",
+        Client => "client",
+        Server => "server",
+        Parsing => "parsing",
+        Validation => "validation",
+        Configuration => "configuration",
+        Unsupported => "unsupported",
+        Stream => "stream",
+        Provider => "provider",
+        Unknown => "unknown",
+    };
+    label.to_string()
+}
+
+/// Backoff delay before retrying after the given (1-based) failed attempt: a random
+/// point in the upper half of the exponential delay, so clients that failed together
+/// don't all retry at the same moment.
+pub fn backoff_delay(cfg: &RetryConfig, attempt: u32) -> Duration {
+    base_delay(cfg, attempt).mul_f32(0.5 + 0.5 * jitter())
+}
+
+fn base_delay(cfg: &RetryConfig, attempt: u32) -> Duration {
+    let exp = cfg.exponential_base.powi(attempt.saturating_sub(1) as i32);
+    let secs = (cfg.initial_delay * exp).min(cfg.max_delay).max(0.0);
+    Duration::from_secs_f32(secs)
+}
+
+/// A random number in `[0, 1)`, from the random high bits of a v4 UUID.
+fn jitter() -> f32 {
+    let bits = uuid::Uuid::new_v4().as_u128();
+    (bits >> 104) as f32 / (1u32 << 24) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_by_status_code() {
+        let cfg = RetryConfig::default();
+        assert_eq!(
+            classify_error(&LlmError::api_error(400, "bad request"), &cfg),
+            ErrorClass::Fatal
+        );
+        assert_eq!(
+            classify_error(&LlmError::api_error(503, "unavailable"), &cfg),
+            ErrorClass::Retryable
+        );
+        assert_eq!(
+            classify_error(&LlmError::TimeoutError("slow".into()), &cfg),
+            ErrorClass::Retryable
+        );
+        assert_eq!(
+            classify_error(&LlmError::InvalidInput("nope".into()), &cfg),
+            ErrorClass::Fatal
+        );
+    }
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let cfg = RetryConfig {
+            initial_delay: 1.0,
+            max_delay: 5.0,
+            exponential_base: 2.0,
+            ..RetryConfig::default()
+        };
+        assert_eq!(base_delay(&cfg, 1), Duration::from_secs(1));
+        assert_eq!(base_delay(&cfg, 2), Duration::from_secs(2));
+        assert_eq!(base_delay(&cfg, 3), Duration::from_secs(4));
+        assert_eq!(base_delay(&cfg, 4), Duration::from_secs(5));
+        let delays: Vec<Duration> = (0..20).map(|_| backoff_delay(&cfg, 3)).collect();
+        assert!(
+            delays
+                .iter()
+                .all(|d| (Duration::from_secs(2)..=Duration::from_secs(4)).contains(d))
+        );
+        assert!(delays.iter().any(|d| *d != delays[0]));
     }
-    let policy = RetryPolicy::new()
-        .with_max_attempts(cfg.max_retries)
-        .with_initial_delay(std::time::Duration::from_secs_f32(cfg.initial_delay))
-        .with_max_delay(std::time::Duration::from_secs_f32(cfg.max_delay))
-        .with_backoff_multiplier(cfg.exponential_base as f64)
-        .with_jitter(true);
-    Some(RetryOptions {
-        backend: RetryBackend::Policy,
-        provider: None,
-        policy: Some(policy),
-        retry_401: true,
-        idempotent: true,
-    })
 }
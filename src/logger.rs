@@ -8,6 +8,12 @@ pub struct AgentLogger {
     index: usize,
 }
 
+impl Default for AgentLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AgentLogger {
     pub fn new() -> Self {
         let mut dir = dirs::home_dir().unwrap_or_default();
@@ -49,6 +55,10 @@ impl AgentLogger {
         self.write("RESPONSE", payload);
     }
 
+    pub fn log_llm_error(&mut self, payload: &serde_json::Value) {
+        self.write("LLM_ERROR", payload);
+    }
+
     pub fn log_tool_result(&mut self, payload: &serde_json::Value) {
         self.write("TOOL_RESULT", payload);
     }
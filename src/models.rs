@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+
+/// Tokenizer family used to estimate prompt sizes for a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenizerFamily {
+    /// OpenAI cl100k_base (GPT-4 / GPT-3.5; also a close fit for Claude)
+    Cl100k,
+    /// OpenAI o200k_base (GPT-4o, GPT-4.1, GPT-5, o-series)
+    O200k,
+    /// Character-based approximation
+    Approximate,
+}
+
+/// Price in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Pricing {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+/// Static facts about a model. `id` matches the configured model name exactly or as a prefix
+/// (e.g. `claude-sonnet-4-5` matches `claude-sonnet-4-5-20250929`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    pub context_window: usize,
+    pub max_output_tokens: usize,
+    #[serde(default = "default_tokenizer")]
+    pub tokenizer: TokenizerFamily,
+    #[serde(default)]
+    pub pricing: Option<Pricing>,
+}
+
+fn default_tokenizer() -> TokenizerFamily {
+    TokenizerFamily::Approximate
+}
+
+impl ModelInfo {
+    /// Token budget to keep free for the completion: the model's max output,
+    /// capped at a quarter of the context window.
+    pub fn completion_reserve(&self) -> usize {
+        self.max_output_tokens.min(self.context_window / 4)
+    }
+}
+
+// (id, context window, max output tokens, tokenizer, input $/Mtok, output $/Mtok)
+type BuiltinEntry = (
+    &'static str,
+    usize,
+    usize,
+    TokenizerFamily,
+    Option<(f64, f64)>,
+);
+
+const BUILTIN: &[BuiltinEntry] = {
+    use TokenizerFamily::*;
+    &[
+        // Anthropic
+        ("claude-opus-4", 200_000, 32_000, Cl100k, Some((15.0, 75.0))),
+        (
+            "claude-sonnet-4-5",
+            200_000,
+            64_000,
+            Cl100k,
+            Some((3.0, 15.0)),
+        ),
+        (
+            "claude-sonnet-4",
+            200_000,
+            64_000,
+            Cl100k,
+            Some((3.0, 15.0)),
+        ),
+        (
+            "claude-haiku-4-5",
+            200_000,
+            64_000,
+            Cl100k,
+            Some((1.0, 5.0)),
+        ),
+        (
+            "claude-3-7-sonnet",
+            200_000,
+            64_000,
+            Cl100k,
+            Some((3.0, 15.0)),
+        ),
+        (
+            "claude-3-5-sonnet",
+            200_000,
+            8_192,
+            Cl100k,
+            Some((3.0, 15.0)),
+        ),
+        ("claude-3-5-haiku", 200_000, 8_192, Cl100k, Some((0.8, 4.0))),
+        // OpenAI
+        ("gpt-5", 400_000, 128_000, O200k, Some((1.25, 10.0))),
+        ("gpt-5-mini", 400_000, 128_000, O200k, Some((0.25, 2.0))),
+        ("gpt-5-nano", 400_000, 128_000, O200k, Some((0.05, 0.4))),
+        ("gpt-4.1", 1_047_576, 32_768, O200k, Some((2.0, 8.0))),
+        ("gpt-4.1-mini", 1_047_576, 32_768, O200k, Some((0.4, 1.6))),
+        ("gpt-4.1-nano", 1_047_576, 32_768, O200k, Some((0.1, 0.4))),
+        ("gpt-4o", 128_000, 16_384, O200k, Some((2.5, 10.0))),
+        ("gpt-4o-mini", 128_000, 16_384, O200k, Some((0.15, 0.6))),
+        ("gpt-4-turbo", 128_000, 4_096, Cl100k, Some((10.0, 30.0))),
+        ("o3", 200_000, 100_000, O200k, Some((2.0, 8.0))),
+        ("o4-mini", 200_000, 100_000, O200k, Some((1.1, 4.4))),
+        // Google
+        (
+            "gemini-2.5-pro",
+            1_048_576,
+            65_536,
+            Approximate,
+            Some((1.25, 10.0)),
+        ),
+        (
+            "gemini-2.5-flash",
+            1_048_576,
+            65_536,
+            Approximate,
+            Some((0.3, 2.5)),
+        ),
+        (
+            "gemini-2.0-flash",
+            1_048_576,
+            8_192,
+            Approximate,
+            Some((0.1, 0.4)),
+        ),
+        // MiniMax
+        (
+            "minimax-m2",
+            204_800,
+            131_072,
+            Approximate,
+            Some((0.3, 1.2)),
+        ),
+        // DeepSeek
+        ("deepseek-chat", 128_000, 8_192, Approximate, None),
+        ("deepseek-reasoner", 128_000, 65_536, Approximate, None),
+        ("deepseek-v3", 128_000, 8_192, Approximate, None),
+    ]
+};
+
+/// Lookup table from model ids to [`ModelInfo`]; user entries take precedence over built-ins.
+#[derive(Debug, Clone, Default)]
+pub struct ModelRegistry {
+    entries: Vec<ModelInfo>,
+}
+
+impl ModelRegistry {
+    pub fn builtin() -> Self {
+        let entries = BUILTIN
+            .iter()
+            .map(|(id, ctx, out, tok, price)| ModelInfo {
+                id: id.to_string(),
+                context_window: *ctx,
+                max_output_tokens: *out,
+                tokenizer: *tok,
+                pricing: price.map(|(i, o)| Pricing {
+                    input_per_mtok: i,
+                    output_per_mtok: o,
+                }),
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Add user-defined models; they shadow built-in entries with the same id.
+    pub fn with_overrides(mut self, models: Vec<ModelInfo>) -> Self {
+        let mut entries = models;
+        entries.append(&mut self.entries);
+        self.entries = entries;
+        self
+    }
+
+    /// Find the best entry for a model name: an exact id match wins, otherwise the longest
+    /// matching id prefix. Matching is case-insensitive and ignores a vendor prefix such as
+    /// `deepseek-ai/`.
+    pub fn lookup(&self, model: &str) -> Option<&ModelInfo> {
+        let name = model.to_lowercase();
+        let name = name.rsplit('/').next().unwrap_or(&name);
+        let mut best: Option<&ModelInfo> = None;
+        for e in &self.entries {
+            let id = e.id.to_lowercase();
+            if id == name {
+                return Some(e);
+            }
+            if name.starts_with(&id) && best.is_none_or(|b| id.len() > b.id.len()) {
+                best = Some(e);
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_prefers_longest_prefix() {
+        let reg = ModelRegistry::builtin();
+        let m = reg.lookup("claude-sonnet-4-5-20250929").unwrap();
+        assert_eq!(m.id, "claude-sonnet-4-5");
+        let m = reg.lookup("gpt-4o-mini-2024-07-18").unwrap();
+        assert_eq!(m.id, "gpt-4o-mini");
+        assert_eq!(m.tokenizer, TokenizerFamily::O200k);
+        assert_eq!(reg.lookup("MiniMax-M2").unwrap().id, "minimax-m2");
+        assert_eq!(
+            reg.lookup("deepseek-ai/DeepSeek-V3").unwrap().id,
+            "deepseek-v3"
+        );
+        assert!(reg.lookup("llama-3").is_none());
+    }
+
+    #[test]
+    fn test_user_entries_take_precedence() {
+        let reg = ModelRegistry::builtin().with_overrides(vec![ModelInfo {
+            id: "gpt-4o".into(),
+            context_window: 32_000,
+            max_output_tokens: 4_000,
+            tokenizer: TokenizerFamily::Approximate,
+            pricing: None,
+        }]);
+        let m = reg.lookup("gpt-4o").unwrap();
+        assert_eq!(m.context_window, 32_000);
+        assert_eq!(m.completion_reserve(), 4_000);
+    }
+}
@@ -1,21 +1,35 @@
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 pub trait AgentObserver: Send + Sync {
     fn on_log_file(&self, _path: &Path) {}
-    fn on_retry(&self, _attempt: u32, _next_delay_secs: f32, _error: &str) {}
+    fn on_retry(&self, _attempt: u32, _next_delay_secs: f32, _category: &str, _error: &str) {}
     fn on_summarize_start(&self, _before: usize, _threshold: usize) {}
     fn on_summarize_done(&self, _after: usize) {}
     fn on_thinking(&self, _text: &str) {}
     fn on_assistant_text(&self, _text: &str) {}
     fn on_tool_call(&self, _name: &str, _args_preview: &str) {}
+    /// Output produced by a tool while it runs (e.g. a bash command's stdout/stderr).
+    fn on_tool_output(&self, _name: &str, _chunk: &str) {}
     fn on_tool_result(&self, _name: &str, _success: bool, _preview: &str) {}
 }
 
-pub struct ConsoleObserver;
+pub struct ConsoleObserver {
+    /// Set once the running tool's output was printed as it streamed
+    streamed: AtomicBool,
+}
+
+impl Default for ConsoleObserver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl ConsoleObserver {
     pub fn new() -> Self {
-        Self
+        Self {
+            streamed: AtomicBool::new(false),
+        }
     }
 }
 
@@ -24,12 +38,13 @@ impl AgentObserver for ConsoleObserver {
         use colored::*;
         println!("{} {}", "Log file:".dimmed(), path.display());
     }
-    fn on_retry(&self, attempt: u32, next_delay_secs: f32, error: &str) {
+    fn on_retry(&self, attempt: u32, next_delay_secs: f32, category: &str, error: &str) {
         use colored::*;
         println!(
-            "{} LLM call failed (attempt {}): {}",
+            "{} LLM call failed (attempt {}, {}): {}",
             "!".yellow(),
             attempt,
+            category,
             error
         );
         println!(
@@ -65,7 +80,7 @@ impl AgentObserver for ConsoleObserver {
         println!(
             "\n{}\n{}",
             "Thinking:".magenta().bold(),
-            format!("{}", text).dimmed()
+            text.to_string().dimmed()
         );
     }
     fn on_assistant_text(&self, text: &str) {
@@ -79,9 +94,24 @@ impl AgentObserver for ConsoleObserver {
             println!("   {}", line.dimmed());
         }
     }
+    fn on_tool_output(&self, _name: &str, chunk: &str) {
+        use colored::*;
+        use std::io::Write;
+        self.streamed.store(true, Ordering::Relaxed);
+        print!("{}", chunk.dimmed());
+        let _ = std::io::stdout().flush();
+    }
     fn on_tool_result(&self, _name: &str, success: bool, preview: &str) {
         use colored::*;
-        if success {
+        // streamed output is already on screen; show only the status line
+        let streamed = self.streamed.swap(false, Ordering::Relaxed);
+        if success && streamed {
+            println!(
+                "{} {}",
+                "Result:".green(),
+                preview.lines().next().unwrap_or("")
+            );
+        } else if success {
             println!("{} {}", "Result:".green(), preview);
         } else {
             println!("{} {}", "Error:".red().bold(), preview.red());
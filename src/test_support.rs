@@ -0,0 +1,38 @@
+//! Helpers shared by the unit tests.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A fresh directory in the system temp dir, deleted with its contents when dropped (also
+/// when the test panics).
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `<temp>/miniagent_<label>_<uuid>`
+    pub fn new(label: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("miniagent_{}_{}", label, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
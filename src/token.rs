@@ -1,45 +1,147 @@
-use siumai::types::{ChatMessage, ContentPart, MessageContent};
+use crate::models::TokenizerFamily;
+use siumai::types::{ChatMessage, ContentPart, MessageContent, Tool};
 
-pub trait TokenEstimator: Send + Sync {
-    fn count_messages(&self, messages: &[ChatMessage]) -> usize;
-}
+/// Per-message framing overhead (role markers, separators).
+const MESSAGE_OVERHEAD: usize = 4;
+/// Per-tool framing overhead on top of the serialized definition.
+const TOOL_OVERHEAD: usize = 8;
+/// Rough cost of one image part; providers bill roughly 500-1600 tokens per image.
+const IMAGE_TOKENS: usize = 1_000;
 
-pub struct ApproxEstimator;
-
-impl ApproxEstimator {
-    fn count_text(s: &str) -> usize {
-        (s.chars().count() as f64 / 2.5) as usize
-    }
-}
+pub trait TokenEstimator: Send + Sync {
+    fn count_text(&self, s: &str) -> usize;
 
-impl TokenEstimator for ApproxEstimator {
-    fn count_messages(&self, messages: &[ChatMessage]) -> usize {
-        let mut total = 0usize;
-        for m in messages {
-            match &m.content {
-                MessageContent::Text(t) => {
-                    total += Self::count_text(t);
-                }
-                MessageContent::MultiModal(parts) => {
-                    for p in parts {
-                        match p {
-                            ContentPart::Text { text } => total += Self::count_text(text),
-                            ContentPart::ToolCall { arguments, .. } => {
-                                let s = serde_json::to_string(arguments).unwrap_or_default();
-                                total += Self::count_text(&s);
-                            }
-                            ContentPart::ToolResult { output, .. } => {
-                                total += Self::count_text(&output.to_string_lossy());
-                            }
-                            _ => {}
+    fn count_message(&self, m: &ChatMessage) -> usize {
+        let mut total = MESSAGE_OVERHEAD;
+        match &m.content {
+            MessageContent::Text(t) => total += self.count_text(t),
+            MessageContent::MultiModal(parts) => {
+                for p in parts {
+                    match p {
+                        ContentPart::Text { text } | ContentPart::Reasoning { text } => {
+                            total += self.count_text(text)
+                        }
+                        ContentPart::ToolCall {
+                            tool_name,
+                            arguments,
+                            ..
+                        } => {
+                            let s = serde_json::to_string(arguments).unwrap_or_default();
+                            total += self.count_text(tool_name) + self.count_text(&s);
+                        }
+                        ContentPart::ToolResult { output, .. } => {
+                            total += self.count_text(&output.to_string_lossy());
                         }
+                        ContentPart::Image { .. } => total += IMAGE_TOKENS,
+                        _ => {}
                     }
                 }
             }
-            total += 4; // overhead per message
         }
         total
     }
+
+    fn count_messages(&self, messages: &[ChatMessage]) -> usize {
+        messages.iter().map(|m| self.count_message(m)).sum()
+    }
+
+    /// Tokens taken by the tool definitions sent with every request.
+    fn count_tools(&self, tools: &[Tool]) -> usize {
+        tools
+            .iter()
+            .map(|t| {
+                let s = serde_json::to_string(t).unwrap_or_default();
+                self.count_text(&s) + TOOL_OVERHEAD
+            })
+            .sum()
+    }
+}
+
+/// Pick an estimator for a tokenizer family (falls back to `ApproxEstimator` without tiktoken).
+pub fn estimator_for(family: TokenizerFamily) -> Box<dyn TokenEstimator> {
+    match family {
+        #[cfg(feature = "tiktoken")]
+        TokenizerFamily::Cl100k => Box::new(TiktokenEstimator::cl100k()),
+        #[cfg(feature = "tiktoken")]
+        TokenizerFamily::O200k => Box::new(TiktokenEstimator::o200k()),
+        _ => Box::new(ApproxEstimator),
+    }
+}
+
+/// Weight of the newest sample in the running correction factor.
+const CALIBRATION_SMOOTHING: f64 = 0.3;
+
+/// Running correction between local estimates and the prompt tokens the provider reports.
+#[derive(Debug, Clone)]
+pub struct TokenCalibration {
+    factor: f64,
+    samples: usize,
+    last_estimate: usize,
+    last_actual: usize,
+}
+
+impl Default for TokenCalibration {
+    fn default() -> Self {
+        Self {
+            factor: 1.0,
+            samples: 0,
+            last_estimate: 0,
+            last_actual: 0,
+        }
+    }
+}
+
+impl TokenCalibration {
+    /// Record one request: the raw local estimate and the provider's prompt token count.
+    pub fn observe(&mut self, estimate: usize, actual: usize) {
+        if estimate == 0 || actual == 0 {
+            return;
+        }
+        let ratio = actual as f64 / estimate as f64;
+        self.factor = if self.samples == 0 {
+            ratio
+        } else {
+            self.factor + CALIBRATION_SMOOTHING * (ratio - self.factor)
+        }
+        .clamp(0.25, 4.0);
+        self.samples += 1;
+        self.last_estimate = estimate;
+        self.last_actual = actual;
+    }
+
+    /// Apply the correction factor to a raw estimate.
+    pub fn apply(&self, estimate: usize) -> usize {
+        (estimate as f64 * self.factor).round() as usize
+    }
+
+    pub fn factor(&self) -> f64 {
+        self.factor
+    }
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+    pub fn last_estimate(&self) -> usize {
+        self.last_estimate
+    }
+    pub fn last_actual(&self) -> usize {
+        self.last_actual
+    }
+
+    /// Relative error of the last raw estimate versus actual usage, in percent.
+    pub fn last_drift_percent(&self) -> Option<f64> {
+        (self.samples > 0).then(|| {
+            (self.last_actual as f64 - self.last_estimate as f64) / self.last_estimate as f64
+                * 100.0
+        })
+    }
+}
+
+pub struct ApproxEstimator;
+
+impl TokenEstimator for ApproxEstimator {
+    fn count_text(&self, s: &str) -> usize {
+        (s.chars().count() as f64 / 2.5) as usize
+    }
 }
 
 #[cfg(feature = "tiktoken")]
@@ -54,38 +156,49 @@ impl TiktokenEstimator {
             bpe: tiktoken_rs::cl100k_base().expect("cl100k_base"),
         }
     }
-    fn count_str(&self, s: &str) -> usize {
-        self.bpe.encode_ordinary(s).len()
+    pub fn o200k() -> Self {
+        Self {
+            bpe: tiktoken_rs::o200k_base().expect("o200k_base"),
+        }
     }
 }
 
 #[cfg(feature = "tiktoken")]
 impl TokenEstimator for TiktokenEstimator {
-    fn count_messages(&self, messages: &[ChatMessage]) -> usize {
-        let mut total = 0usize;
-        for m in messages {
-            match &m.content {
-                MessageContent::Text(t) => {
-                    total += self.count_str(t);
-                }
-                MessageContent::MultiModal(parts) => {
-                    for p in parts {
-                        match p {
-                            ContentPart::Text { text } => total += self.count_str(text),
-                            ContentPart::ToolCall { arguments, .. } => {
-                                let s = serde_json::to_string(arguments).unwrap_or_default();
-                                total += self.count_str(&s);
-                            }
-                            ContentPart::ToolResult { output, .. } => {
-                                total += self.count_str(&output.to_string_lossy());
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-            }
-            total += 4;
-        }
-        total
+    fn count_text(&self, s: &str) -> usize {
+        self.bpe.encode_ordinary(s).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calibration_tracks_provider_usage() {
+        let mut cal = TokenCalibration::default();
+        assert_eq!(cal.apply(1000), 1000);
+        cal.observe(1000, 1500);
+        assert_eq!(cal.apply(1000), 1500);
+        assert_eq!(cal.last_drift_percent(), Some(50.0));
+        cal.observe(1000, 1000);
+        // moves toward the new ratio without jumping to it
+        assert!(cal.factor() > 1.0 && cal.factor() < 1.5);
+        assert_eq!(cal.samples(), 2);
+    }
+
+    #[test]
+    fn test_tools_and_images_are_counted() {
+        let est = ApproxEstimator;
+        let tool = Tool::function(
+            "read_file".to_string(),
+            "Read a file".to_string(),
+            serde_json::json!({"type": "object", "properties": {"path": {"type": "string"}}}),
+        );
+        assert!(est.count_tools(&[tool]) > TOOL_OVERHEAD);
+        let msg = ChatMessage::user("look")
+            .with_image("https://example.com/a.png".to_string(), None)
+            .build();
+        assert!(est.count_messages(&[msg]) >= IMAGE_TOKENS);
     }
 }
@@ -0,0 +1,185 @@
+//! Crash-safe file replacement for the file tools: write a temp file in the same directory,
+//! fsync it, copy the old file's permissions (and owner on Unix), then rename over the target.
+//! Read-only targets are refused, and hard-linked ones are written in place so every link
+//! sees the new content.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::AsyncWriteExt;
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let unique = format!(
+        ".{}.{}-{}.tmp",
+        name,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    path.with_file_name(unique)
+}
+
+/// Error unless `len` bytes are within `max` (`tools.files.max_write_bytes`).
+pub fn check_write_size(path: &Path, len: usize, max: usize) -> Result<(), String> {
+    if len > max {
+        return Err(format!(
+            "refusing to write {} bytes to {}: larger than tools.files.max_write_bytes ({})",
+            len,
+            path.display(),
+            max
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `path` itself may be written: it has a write bit set and (on Unix) the current
+/// user has write access. Renaming over it only needs write access to the directory, so
+/// this has to be checked separately.
+fn writable(path: &Path, meta: &std::fs::Metadata) -> bool {
+    if meta.permissions().readonly() {
+        return false;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let Ok(c_path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+        unsafe { libc::access(c_path.as_ptr(), libc::W_OK) == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        true
+    }
+}
+
+fn hard_linked(meta: &std::fs::Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        meta.nlink() > 1
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = meta;
+        false
+    }
+}
+
+/// Replace `path` with `bytes` atomically: readers see either the old or the new content,
+/// never a truncated file. Parent directories are created as needed.
+///
+/// Fails with `PermissionDenied` if `path` exists but is not writable. A file with several
+/// hard links is overwritten in place instead, which keeps the links but is not atomic.
+pub async fn atomic_write(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let existing = tokio::fs::metadata(path).await.ok();
+    if let Some(meta) = &existing {
+        if meta.is_file() && !writable(path, meta) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{} is read-only", path.display()),
+            ));
+        }
+        if meta.is_file() && hard_linked(meta) {
+            let mut file = tokio::fs::OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .open(path)
+                .await?;
+            file.write_all(bytes).await?;
+            return file.sync_all().await;
+        }
+    }
+    let tmp = temp_path(path);
+    let result = async {
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)
+            .await?;
+        file.write_all(bytes).await?;
+        file.sync_all().await?;
+        drop(file);
+        if let Some(meta) = &existing {
+            tokio::fs::set_permissions(&tmp, meta.permissions()).await?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                // only possible for root or within the user's groups; best effort
+                let _ = std::os::unix::fs::chown(&tmp, Some(meta.uid()), Some(meta.gid()));
+            }
+        }
+        tokio::fs::rename(&tmp, path).await
+    }
+    .await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&tmp).await;
+        return result;
+    }
+    #[cfg(unix)]
+    if let Some(parent) = path.parent()
+        && let Ok(dir) = tokio::fs::File::open(parent).await
+    {
+        // persist the rename itself
+        let _ = dir.sync_all().await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[tokio::test]
+    async fn test_atomic_write() {
+        let dir = TempDir::new("atomic");
+        let file = dir.join("sub/run.sh");
+        atomic_write(&file, b"#!/bin/sh\necho one\n").await.unwrap();
+        assert_eq!(std::fs::read(&file).unwrap(), b"#!/bin/sh\necho one\n");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o750)).unwrap();
+            atomic_write(&file, b"#!/bin/sh\necho two\n").await.unwrap();
+            let mode = std::fs::metadata(&file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o750);
+        }
+        assert_eq!(std::fs::read(&file).unwrap(), b"#!/bin/sh\necho two\n");
+
+        // a failed write leaves the old content and no temp file behind
+        assert!(atomic_write(&dir.join("sub"), b"x").await.is_err());
+        let names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec![std::ffi::OsString::from("sub")]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_read_only_and_hard_linked_targets() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = TempDir::new("atomic");
+
+        let linked = dir.join("a.txt");
+        std::fs::write(&linked, "old").unwrap();
+        std::fs::hard_link(&linked, dir.join("b.txt")).unwrap();
+        atomic_write(&linked, b"new").await.unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("b.txt")).unwrap(), "new");
+
+        let locked = dir.join("locked.txt");
+        std::fs::write(&locked, "keep").unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o444)).unwrap();
+        let err = atomic_write(&locked, b"x").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(std::fs::read_to_string(&locked).unwrap(), "keep");
+    }
+}
@@ -0,0 +1,440 @@
+//! Background processes started by `bash` with `run_in_background`, plus the tools to poll,
+//! list and kill them. Processes live in a global registry so `cleanup_background` can stop
+//! them when the agent exits.
+
+use crate::tools::base::{Tool, ToolResult};
+use crate::tools::bash::signal_label;
+use crate::tools::output::strip_ansi;
+use async_trait::async_trait;
+use once_cell::sync::OnceCell;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+use tokio::sync::Notify;
+
+/// Output kept per process; older bytes are dropped once exceeded.
+const MAX_BUFFER_BYTES: usize = 1024 * 1024;
+/// Output returned by a single `bash_output` call.
+const MAX_POLL_BYTES: usize = 32 * 1024;
+/// Time between SIGTERM and SIGKILL when killing a process group.
+const KILL_GRACE: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessStatus {
+    Running,
+    Exited(i32),
+    /// Ended by a signal it didn't handle (unix), e.g. a crash or the OOM killer
+    Signaled(i32),
+    Killed,
+}
+
+impl std::fmt::Display for ProcessStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessStatus::Running => write!(f, "running"),
+            ProcessStatus::Exited(code) => write!(f, "exited ({})", code),
+            ProcessStatus::Signaled(sig) => {
+                write!(f, "terminated by signal {}", signal_label(*sig))
+            }
+            ProcessStatus::Killed => write!(f, "killed"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct OutputBuffer {
+    data: Vec<u8>,
+    /// Offset in `data` up to which output was already returned by a poll
+    read_pos: usize,
+    /// Unread bytes discarded because the buffer was full
+    dropped: usize,
+}
+
+impl OutputBuffer {
+    fn append(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+        if self.data.len() > MAX_BUFFER_BYTES {
+            let excess = self.data.len() - MAX_BUFFER_BYTES;
+            self.data.drain(..excess);
+            self.dropped += excess.saturating_sub(self.read_pos);
+            self.read_pos = self.read_pos.saturating_sub(excess);
+        }
+    }
+
+    /// Take up to `limit` unread bytes; returns (bytes, dropped since last poll, bytes still pending).
+    fn take_unread(&mut self, limit: usize) -> (Vec<u8>, usize, usize) {
+        let end = self.data.len().min(self.read_pos + limit);
+        let out = self.data[self.read_pos..end].to_vec();
+        self.read_pos = end;
+        let dropped = std::mem::take(&mut self.dropped);
+        (out, dropped, self.data.len() - end)
+    }
+}
+
+pub struct BackgroundProcess {
+    pub id: String,
+    pub command: String,
+    pub pid: Option<u32>,
+    started: Instant,
+    output: Mutex<OutputBuffer>,
+    status: Mutex<ProcessStatus>,
+    kill: Notify,
+    done: Notify,
+}
+
+impl BackgroundProcess {
+    pub fn status(&self) -> ProcessStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Stop the process (and its process group on Unix) and wait until it has exited.
+    pub async fn kill(&self) {
+        let done = self.done.notified();
+        if self.status() != ProcessStatus::Running {
+            return;
+        }
+        self.kill.notify_one();
+        done.await;
+    }
+}
+
+static REGISTRY: OnceCell<Mutex<BTreeMap<String, Arc<BackgroundProcess>>>> = OnceCell::new();
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+fn registry() -> &'static Mutex<BTreeMap<String, Arc<BackgroundProcess>>> {
+    REGISTRY.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+pub fn get_process(id: &str) -> Option<Arc<BackgroundProcess>> {
+    registry().lock().unwrap().get(id).cloned()
+}
+
+pub fn list_processes() -> Vec<Arc<BackgroundProcess>> {
+    registry().lock().unwrap().values().cloned().collect()
+}
+
+/// Spawn `command` (configured by the caller with program, args and cwd) in the background
+/// and register it. stdout and stderr are merged into one buffer in arrival order.
+pub fn spawn_background(
+    mut command: Command,
+    display: &str,
+) -> std::io::Result<Arc<BackgroundProcess>> {
+    command
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true);
+    // own process group, so killing it also stops children (dev servers, watchers, ...)
+    #[cfg(unix)]
+    command.process_group(0);
+    let mut child = command.spawn()?;
+
+    let id = format!("bg{}", NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let proc = Arc::new(BackgroundProcess {
+        id: id.clone(),
+        command: display.to_string(),
+        pid: child.id(),
+        started: Instant::now(),
+        output: Mutex::new(OutputBuffer::default()),
+        status: Mutex::new(ProcessStatus::Running),
+        kill: Notify::new(),
+        done: Notify::new(),
+    });
+
+    let readers = [
+        child
+            .stdout
+            .take()
+            .map(|s| tokio::spawn(pump(s, proc.clone()))),
+        child
+            .stderr
+            .take()
+            .map(|s| tokio::spawn(pump(s, proc.clone()))),
+    ];
+
+    let p = proc.clone();
+    tokio::spawn(async move {
+        let status = tokio::select! {
+            res = child.wait() => match res {
+                Ok(s) => exit_status(s),
+                Err(_) => ProcessStatus::Exited(-1),
+            },
+            _ = p.kill.notified() => {
+                terminate(&mut child).await;
+                ProcessStatus::Killed
+            }
+        };
+        // let the readers drain what the process wrote before it exited
+        for r in readers.into_iter().flatten() {
+            let _ = tokio::time::timeout(Duration::from_millis(500), r).await;
+        }
+        *p.status.lock().unwrap() = status;
+        p.done.notify_waiters();
+    });
+
+    registry().lock().unwrap().insert(id, proc.clone());
+    Ok(proc)
+}
+
+async fn pump<R: AsyncRead + Unpin>(mut reader: R, proc: Arc<BackgroundProcess>) {
+    let mut buf = [0u8; 8192];
+    while let Ok(n) = reader.read(&mut buf).await {
+        if n == 0 {
+            break;
+        }
+        proc.output.lock().unwrap().append(&buf[..n]);
+    }
+}
+
+fn exit_status(status: std::process::ExitStatus) -> ProcessStatus {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(sig) = status.signal() {
+            return ProcessStatus::Signaled(sig);
+        }
+    }
+    ProcessStatus::Exited(status.code().unwrap_or(-1))
+}
+
+/// Stop `child` and its process group: SIGTERM, then SIGKILL after a grace period.
+#[cfg(unix)]
+pub async fn terminate(child: &mut tokio::process::Child) {
+    if let Some(pid) = child.id() {
+        // negative pid signals the whole process group
+        let pgid = -(pid as i32);
+        unsafe { libc::kill(pgid, libc::SIGTERM) };
+        let _ = tokio::time::timeout(KILL_GRACE, child.wait()).await;
+        // also reaps children that ignored SIGTERM or outlived the leader
+        unsafe { libc::kill(pgid, libc::SIGKILL) };
+    }
+    let _ = child.kill().await;
+}
+
+/// Stop `child` and every process it started. `Child::kill` alone would only end the shell.
+#[cfg(not(unix))]
+pub async fn terminate(child: &mut tokio::process::Child) {
+    if let Some(pid) = child.id() {
+        let mut taskkill = tokio::process::Command::new("taskkill");
+        taskkill
+            .args(["/T", "/F", "/PID", pid.to_string().as_str()])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null());
+        let _ = tokio::time::timeout(KILL_GRACE, taskkill.status()).await;
+    }
+    let _ = tokio::time::timeout(KILL_GRACE, child.kill()).await;
+}
+
+/// Kill every background process that is still running.
+pub async fn cleanup_background() {
+    for p in list_processes() {
+        p.kill().await;
+    }
+}
+
+fn missing_id() -> ToolResult {
+    ToolResult {
+        success: false,
+        content: String::new(),
+        error: Some("missing 'id'".into()),
+    }
+}
+
+fn unknown_id(id: &str) -> ToolResult {
+    ToolResult {
+        success: false,
+        content: String::new(),
+        error: Some(format!("no background process '{}'", id)),
+    }
+}
+
+pub struct BashOutputTool;
+
+#[async_trait]
+impl Tool for BashOutputTool {
+    fn name(&self) -> &str {
+        "bash_output"
+    }
+    fn description(&self) -> &str {
+        "Read new output (stdout and stderr) from a background process started with bash run_in_background, along with its status"
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": {"type": "string", "description": "Background process id (e.g. bg1)"}
+            },
+            "required": ["id"],
+        })
+    }
+    async fn execute(&self, args: Value) -> ToolResult {
+        let Some(id) = args.get("id").and_then(|v| v.as_str()) else {
+            return missing_id();
+        };
+        let Some(proc) = get_process(id) else {
+            return unknown_id(id);
+        };
+        // read status first so output written before exit is included
+        let status = proc.status();
+        let (bytes, dropped, pending) = proc.output.lock().unwrap().take_unread(MAX_POLL_BYTES);
+        let mut content = format!("[{} {}]\n", proc.id, status);
+        if dropped > 0 {
+            content.push_str(&format!("[{} bytes of older output dropped]\n", dropped));
+        }
+        if bytes.is_empty() {
+            content.push_str("(no new output)");
+        } else {
+            content.push_str(&strip_ansi(&String::from_utf8_lossy(&bytes)));
+        }
+        if pending > 0 {
+            content.push_str(&format!(
+                "\n[{} more bytes pending; call bash_output again]",
+                pending
+            ));
+        }
+        ToolResult {
+            success: true,
+            content,
+            error: None,
+        }
+    }
+}
+
+pub struct BashListTool;
+
+#[async_trait]
+impl Tool for BashListTool {
+    fn name(&self) -> &str {
+        "bash_list"
+    }
+    fn description(&self) -> &str {
+        "List background processes with their status, pid, run time and command"
+    }
+    fn parameters(&self) -> Value {
+        json!({"type": "object", "properties": {}})
+    }
+    async fn execute(&self, _args: Value) -> ToolResult {
+        let procs = list_processes();
+        let content = if procs.is_empty() {
+            "No background processes".to_string()
+        } else {
+            procs
+                .iter()
+                .map(|p| {
+                    format!(
+                        "{}  {}  pid={}  {}s  {}",
+                        p.id,
+                        p.status(),
+                        p.pid.map(|x| x.to_string()).unwrap_or_else(|| "-".into()),
+                        p.elapsed().as_secs(),
+                        p.command
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        ToolResult {
+            success: true,
+            content,
+            error: None,
+        }
+    }
+}
+
+pub struct BashKillTool;
+
+#[async_trait]
+impl Tool for BashKillTool {
+    fn name(&self) -> &str {
+        "bash_kill"
+    }
+    fn description(&self) -> &str {
+        "Stop a background process (and any children it started)"
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": {"type": "string", "description": "Background process id (e.g. bg1)"}
+            },
+            "required": ["id"],
+        })
+    }
+    async fn execute(&self, args: Value) -> ToolResult {
+        let Some(id) = args.get("id").and_then(|v| v.as_str()) else {
+            return missing_id();
+        };
+        let Some(proc) = get_process(id) else {
+            return unknown_id(id);
+        };
+        let status = proc.status();
+        if status != ProcessStatus::Running {
+            return ToolResult {
+                success: true,
+                content: format!("{} already {}", id, status),
+                error: None,
+            };
+        }
+        proc.kill().await;
+        ToolResult {
+            success: true,
+            content: format!("{} {}", id, proc.status()),
+            error: None,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_poll_and_kill() {
+        let mut cmd = Command::new("bash");
+        cmd.arg("-c").arg("echo started; sleep 30");
+        let proc = spawn_background(cmd, "echo started; sleep 30").unwrap();
+
+        let mut out = String::new();
+        for _ in 0..50 {
+            out = BashOutputTool.execute(json!({"id": proc.id})).await.content;
+            if out.contains("started") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(out.contains("running") && out.contains("started"), "{out}");
+        let out = BashOutputTool.execute(json!({"id": proc.id})).await.content;
+        assert!(out.contains("(no new output)"));
+
+        let res = BashKillTool.execute(json!({"id": proc.id})).await;
+        assert!(res.success);
+        assert_eq!(proc.status(), ProcessStatus::Killed);
+        assert!(!BashOutputTool.execute(json!({"id": "nope"})).await.success);
+    }
+
+    #[tokio::test]
+    async fn test_signal_is_reported() {
+        let mut cmd = Command::new("bash");
+        cmd.arg("-c").arg("kill -SEGV $$");
+        let proc = spawn_background(cmd, "kill -SEGV $$").unwrap();
+        for _ in 0..100 {
+            if proc.status() != ProcessStatus::Running {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(proc.status(), ProcessStatus::Signaled(libc::SIGSEGV));
+        assert_eq!(
+            proc.status().to_string(),
+            "terminated by signal 11 (SIGSEGV)"
+        );
+    }
+}
@@ -1,5 +1,9 @@
 use async_trait::async_trait;
 use serde_json::Value;
+use std::sync::Arc;
+
+/// Receives output chunks while a tool is still running.
+pub type OutputSink = Arc<dyn Fn(&str) + Send + Sync>;
 
 #[derive(Debug, Clone)]
 pub struct ToolResult {
@@ -15,6 +19,11 @@ pub trait Tool: Send + Sync {
     fn parameters(&self) -> Value;
     async fn execute(&self, args: Value) -> ToolResult;
 
+    /// Like `execute`, but tools that produce output over time may report it through `sink`.
+    async fn execute_streaming(&self, args: Value, _sink: OutputSink) -> ToolResult {
+        self.execute(args).await
+    }
+
     fn to_siumai_tool(&self) -> siumai::types::Tool {
         siumai::types::Tool::function(
             self.name().to_string(),
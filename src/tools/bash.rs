@@ -1,11 +1,226 @@
-use crate::tools::base::{Tool, ToolResult};
+use crate::config::BashConfig;
+use crate::tools::background::{spawn_background, terminate};
+use crate::tools::base::{OutputSink, Tool, ToolResult};
+use crate::tools::command_rules::CommandRules;
+use crate::tools::env::EnvPolicy;
+use crate::tools::output::{CHUNK_BYTES, CappedOutput, partial_line_len, strip_ansi};
+use crate::tools::sandbox::Sandbox;
+use crate::tools::shell::{SharedSession, ShellSession, new_shared_session};
 use async_trait::async_trait;
 use serde_json::{Value, json};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 pub struct BashTool {
     pub workspace: PathBuf,
+    pub config: BashConfig,
+    /// Set when `config.sandbox.enabled`; applied to every process the tool starts
+    pub sandbox: Option<Sandbox>,
+    /// Compiled `config.allow` / `config.deny` rules
+    rules: CommandRules,
+    /// Environment applied to every process the tool starts
+    pub env: EnvPolicy,
+    /// Long-lived shell used when `config.persistent_shell` is set (started lazily)
+    session: SharedSession,
+}
+
+impl BashTool {
+    /// Fails if an allow/deny rule is not a valid pattern.
+    pub fn new(workspace: PathBuf, config: BashConfig) -> anyhow::Result<Self> {
+        let sandbox = Sandbox::from_config(&config.sandbox, &workspace);
+        let rules = CommandRules::new(&config.allow, &config.deny)
+            .map_err(|e| anyhow::anyhow!("invalid tools.bash allow/deny rule: {}", e))?;
+        Ok(Self {
+            workspace,
+            config,
+            sandbox,
+            rules,
+            env: EnvPolicy::default(),
+            session: new_shared_session(),
+        })
+    }
+
+    async fn execute_background(&self, cmd: &str) -> ToolResult {
+        // start where the persistent shell currently is, if there is one
+        let cwd = match self.session.try_lock() {
+            Ok(guard) => guard
+                .as_ref()
+                .map(|s| s.cwd().to_path_buf())
+                .unwrap_or_else(|| self.workspace.clone()),
+            Err(_) => self.workspace.clone(),
+        };
+        let command = match self.command(cmd, &cwd) {
+            Ok(c) => c,
+            Err(e) => return sandbox_error(e),
+        };
+        match spawn_background(command, cmd) {
+            Ok(proc) => ToolResult {
+                success: true,
+                content: format!(
+                    "Started background process {} (pid {}). Use bash_output to read its output and bash_kill to stop it.",
+                    proc.id,
+                    proc.pid
+                        .map(|p| p.to_string())
+                        .unwrap_or_else(|| "-".into())
+                ),
+                error: None,
+            },
+            Err(e) => ToolResult {
+                success: false,
+                content: String::new(),
+                error: Some(format!("failed to start background process: {}", e)),
+            },
+        }
+    }
+
+    pub fn with_env_policy(mut self, env: EnvPolicy) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// One-shot shell invocation of `cmd` in `cwd`, with the env policy applied and
+    /// sandboxed if configured.
+    fn command(&self, cmd: &str, cwd: &Path) -> std::io::Result<tokio::process::Command> {
+        let mut command = shell_command(cmd, cwd);
+        self.env.apply(&mut command);
+        if let Some(sb) = &self.sandbox {
+            sb.apply(&mut command)?;
+        }
+        Ok(command)
+    }
+
+    fn finish(
+        &self,
+        status: &str,
+        success: bool,
+        cwd: Option<&Path>,
+        stdout: &CappedOutput,
+        stderr: &CappedOutput,
+    ) -> ToolResult {
+        let mut error = status.to_string();
+        if !success
+            && let Some(sb) = &self.sandbox
+            && let Some(why) = sb.explain_failure(status, &stderr.render())
+        {
+            error.push_str(&format!(" (sandbox: {})", why));
+        }
+        ToolResult {
+            success,
+            content: render(status, cwd, stdout, stderr),
+            error: (!success).then_some(error),
+        }
+    }
+
+    /// `fut`'s output, or `None` if `config.timeout_secs` passed first.
+    async fn within_timeout<T>(&self, fut: impl Future<Output = T>) -> Option<T> {
+        match self.config.timeout_secs {
+            0 => Some(fut.await),
+            secs => tokio::time::timeout(Duration::from_secs(secs), fut)
+                .await
+                .ok(),
+        }
+    }
+
+    fn timed_out(&self, killed: &str, stdout: &CappedOutput, stderr: &CappedOutput) -> ToolResult {
+        let status = format!("timed out after {}s", self.config.timeout_secs);
+        ToolResult {
+            success: false,
+            content: render(&status, None, stdout, stderr),
+            error: Some(format!(
+                "{}; {} (limit: tools.bash.timeout_secs)",
+                status, killed
+            )),
+        }
+    }
+
+    fn persistent(&self) -> bool {
+        self.config.persistent_shell && cfg!(not(target_os = "windows"))
+    }
+
+    async fn execute_persistent(
+        &self,
+        cmd: Option<&str>,
+        restart: bool,
+        sink: &OutputSink,
+    ) -> ToolResult {
+        let mut guard = self.session.lock().await;
+        if restart && let Some(mut old) = guard.take() {
+            // kill the whole process group, so jobs the old shell started with `&` go too
+            old.kill().await;
+        }
+        let Some(cmd) = cmd else {
+            return if restart {
+                ToolResult {
+                    success: true,
+                    content: format!("Shell restarted in {}", self.workspace.display()),
+                    error: None,
+                }
+            } else {
+                ToolResult {
+                    success: false,
+                    content: String::new(),
+                    error: Some("missing 'command'".into()),
+                }
+            };
+        };
+        if guard.is_none() {
+            match ShellSession::spawn(&self.workspace, &self.env, self.sandbox.as_ref()) {
+                Ok(s) => *guard = Some(s),
+                Err(e) => {
+                    return ToolResult {
+                        success: false,
+                        content: String::new(),
+                        error: Some(format!("failed to start shell: {}", e)),
+                    };
+                }
+            }
+        }
+        let session = guard.as_mut().expect("session started above");
+        let mut stdout = CappedOutput::new(self.config.max_output_bytes);
+        let mut stderr = CappedOutput::new(self.config.max_output_bytes);
+        let res = self
+            .within_timeout(session.run_streaming(
+                cmd,
+                &mut |chunk| forward(chunk, sink, &mut stdout),
+                &mut |chunk| forward(chunk, sink, &mut stderr),
+            ))
+            .await;
+        let Some(res) = res else {
+            // the shell may be stuck mid-command, so it can't be reused
+            session.kill().await;
+            *guard = None;
+            return self.timed_out(
+                "the shell was killed and a fresh one will be started on the next call",
+                &stdout,
+                &stderr,
+            );
+        };
+        match res {
+            Ok((exit_code, cwd)) => {
+                let status = describe_exit_code(exit_code);
+                self.finish(&status, exit_code == 0, Some(&cwd), &stdout, &stderr)
+            }
+            Err(e) => {
+                let status = session
+                    .try_exit_code()
+                    .map(|c| format!(" (exit {})", c))
+                    .unwrap_or_default();
+                session.kill().await;
+                *guard = None;
+                ToolResult {
+                    success: false,
+                    content: render(&format!("shell exited{}", status), None, &stdout, &stderr),
+                    error: Some(format!(
+                        "shell session ended{}: {}; a fresh shell will be started on the next call",
+                        status, e
+                    )),
+                }
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -14,18 +229,72 @@ impl Tool for BashTool {
         "bash"
     }
     fn description(&self) -> &str {
-        "Execute a shell command in the workspace (Windows: PowerShell if available, otherwise cmd.exe; Unix: bash -lc)"
+        if self.persistent() {
+            "Execute a shell command in a persistent bash session rooted at the workspace. \
+             Working directory, exported variables and shell functions persist between calls; \
+             the current directory is reported after each command. Pass restart=true to start a fresh shell."
+        } else {
+            "Execute a shell command in the workspace (Windows: PowerShell if available, otherwise cmd.exe; Unix: bash -lc)"
+        }
     }
     fn parameters(&self) -> Value {
+        if self.persistent() {
+            return json!({
+                "type": "object",
+                "properties": {
+                    "command": {"type": "string", "description": "Command to run"},
+                    "restart": {"type": "boolean", "description": "Kill the current shell and start a fresh one (before running command, if given)"},
+                    "run_in_background": {"type": "boolean", "description": "Start the command as a background process and return its id immediately"}
+                },
+            });
+        }
         json!({
             "type": "object",
             "properties": {
-                "command": {"type": "string", "description": "Command to run"}
+                "command": {"type": "string", "description": "Command to run"},
+                "run_in_background": {"type": "boolean", "description": "Start the command as a background process and return its id immediately"}
             },
             "required": ["command"],
         })
     }
     async fn execute(&self, args: Value) -> ToolResult {
+        self.execute_streaming(args, Arc::new(|_| {})).await
+    }
+    async fn execute_streaming(&self, args: Value, sink: OutputSink) -> ToolResult {
+        if let Some(cmd) = args.get("command").and_then(|v| v.as_str())
+            && let Err(denial) = self.rules.check(cmd)
+        {
+            return ToolResult {
+                success: false,
+                content: String::new(),
+                error: Some(format!(
+                    "{}; this command is not allowed by the tools.bash rules",
+                    denial
+                )),
+            };
+        }
+        if args
+            .get("run_in_background")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+        {
+            return match args.get("command").and_then(|v| v.as_str()) {
+                Some(cmd) => self.execute_background(cmd).await,
+                None => ToolResult {
+                    success: false,
+                    content: String::new(),
+                    error: Some("missing 'command'".into()),
+                },
+            };
+        }
+        if self.persistent() {
+            let restart = args
+                .get("restart")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let cmd = args.get("command").and_then(|v| v.as_str());
+            return self.execute_persistent(cmd, restart, &sink).await;
+        }
         let Some(cmd) = args.get("command").and_then(|v| v.as_str()) else {
             return ToolResult {
                 success: false,
@@ -34,42 +303,21 @@ impl Tool for BashTool {
             };
         };
 
-        #[cfg(target_os = "windows")]
-        let mut command = {
-            // Prefer PowerShell (pwsh), then Windows PowerShell, then cmd.exe
-            if which::which("pwsh").is_ok() {
-                let mut c = tokio::process::Command::new("pwsh");
-                c.arg("-NoLogo")
-                    .arg("-Command")
-                    .arg(cmd)
-                    .current_dir(&self.workspace);
-                c
-            } else if which::which("powershell").is_ok() {
-                let mut c = tokio::process::Command::new("powershell");
-                c.arg("-NoLogo")
-                    .arg("-Command")
-                    .arg(cmd)
-                    .current_dir(&self.workspace);
-                c
-            } else {
-                let mut c = tokio::process::Command::new("cmd");
-                c.arg("/C").arg(cmd).current_dir(&self.workspace);
-                c
-            }
+        let mut command = match self.command(cmd, &self.workspace) {
+            Ok(c) => c,
+            Err(e) => return sandbox_error(e),
         };
-
-        #[cfg(not(target_os = "windows"))]
-        let mut command = tokio::process::Command::new("bash");
-        #[cfg(not(target_os = "windows"))]
-        let command = command.arg("-lc").arg(cmd).current_dir(&self.workspace);
-
-        let output = match command
+        // own process group, so a timeout also kills what the command started
+        #[cfg(unix)]
+        command.process_group(0);
+        let mut child = match command
+            .stdin(Stdio::null())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .output()
-            .await
+            .kill_on_drop(true)
+            .spawn()
         {
-            Ok(o) => o,
+            Ok(c) => c,
             Err(e) => {
                 return ToolResult {
                     success: false,
@@ -78,21 +326,312 @@ impl Tool for BashTool {
                 };
             }
         };
-        let mut content = String::new();
-        if !output.stdout.is_empty() {
-            content.push_str(&String::from_utf8_lossy(&output.stdout));
+        let mut stdout = CappedOutput::new(self.config.max_output_bytes);
+        let mut stderr = CappedOutput::new(self.config.max_output_bytes);
+        let (out_pipe, err_pipe) = (child.stdout.take(), child.stderr.take());
+        let finished = self
+            .within_timeout(async {
+                tokio::join!(
+                    read_stream(out_pipe, &sink, &mut stdout),
+                    read_stream(err_pipe, &sink, &mut stderr)
+                );
+                child.wait().await
+            })
+            .await;
+        let Some(finished) = finished else {
+            terminate(&mut child).await;
+            return self.timed_out("the command was killed", &stdout, &stderr);
+        };
+        match finished {
+            Ok(status) => self.finish(
+                &describe_exit(&status),
+                status.success(),
+                None,
+                &stdout,
+                &stderr,
+            ),
+            Err(e) => ToolResult {
+                success: false,
+                content: render("exit status unknown", None, &stdout, &stderr),
+                error: Some(e.to_string()),
+            },
         }
-        if !output.stderr.is_empty() {
-            content.push_str(&String::from_utf8_lossy(&output.stderr));
+    }
+}
+
+/// Strip escape sequences from a chunk of output, forward it to the sink and keep it (bounded).
+fn forward(chunk: &[u8], sink: &OutputSink, out: &mut CappedOutput) {
+    let text = strip_ansi(&String::from_utf8_lossy(chunk));
+    sink(&text);
+    out.push(text.as_bytes());
+}
+
+/// Pass output on line by line as it arrives, in `CHUNK_BYTES` pieces for long lines, so
+/// memory stays bounded even for output without newlines.
+async fn read_stream<R: AsyncRead + Unpin>(
+    reader: Option<R>,
+    sink: &OutputSink,
+    out: &mut CappedOutput,
+) {
+    let Some(mut reader) = reader else {
+        return;
+    };
+    let mut chunk = vec![0u8; CHUNK_BYTES];
+    let mut pending = Vec::new();
+    loop {
+        match reader.read(&mut chunk).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => pending.extend_from_slice(&chunk[..n]),
         }
-        ToolResult {
-            success: output.status.success(),
-            content,
-            error: if output.status.success() {
-                None
-            } else {
-                Some(format!("exit: {}", output.status))
-            },
+        let ready = match pending.iter().rposition(|&b| b == b'\n') {
+            Some(i) => i + 1,
+            None => partial_line_len(&pending),
+        };
+        if ready > 0 {
+            forward(&pending[..ready], sink, out);
+            pending.drain(..ready);
+        }
+    }
+    if !pending.is_empty() {
+        forward(&pending, sink, out);
+    }
+}
+
+/// "exit code: N", or the terminating signal when the process was killed by one.
+fn describe_exit(status: &std::process::ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(sig) = status.signal() {
+            return format!("terminated by signal {}", signal_label(sig));
+        }
+    }
+    match status.code() {
+        Some(code) => format!("exit code: {}", code),
+        None => "exit code: unknown".to_string(),
+    }
+}
+
+/// Like `describe_exit` for a code reported by bash, which uses 128+N for signal N.
+fn describe_exit_code(code: i32) -> String {
+    if (129..=128 + 64).contains(&code) {
+        format!("exit code: {} (signal {})", code, signal_label(code - 128))
+    } else {
+        format!("exit code: {}", code)
+    }
+}
+
+/// `N (SIGNAME)` for common signals, just `N` otherwise.
+pub fn signal_label(sig: i32) -> String {
+    let name = match sig {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        6 => "SIGABRT",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        15 => "SIGTERM",
+        24 => "SIGXCPU",
+        25 => "SIGXFSZ",
+        _ => return sig.to_string(),
+    };
+    format!("{} ({})", sig, name)
+}
+
+/// Status line(s) followed by the non-empty streams, each in its own tagged section.
+fn render(
+    status: &str,
+    cwd: Option<&Path>,
+    stdout: &CappedOutput,
+    stderr: &CappedOutput,
+) -> String {
+    let mut content = status.to_string();
+    if let Some(cwd) = cwd {
+        content.push_str(&format!("\ncwd: {}", cwd.display()));
+    }
+    for (tag, out) in [("stdout", stdout), ("stderr", stderr)] {
+        if !out.is_empty() {
+            content.push_str(&format!(
+                "\n<{tag}>\n{}\n</{tag}>",
+                out.render().trim_end_matches('\n')
+            ));
         }
     }
+    if stdout.is_empty() && stderr.is_empty() {
+        content.push_str("\n(no output)");
+    }
+    content
+}
+
+fn sandbox_error(e: std::io::Error) -> ToolResult {
+    ToolResult {
+        success: false,
+        content: String::new(),
+        error: Some(format!("sandbox setup failed, command not run: {}", e)),
+    }
+}
+
+/// One-shot shell invocation for `cmd` in `cwd` (Windows: pwsh, powershell or cmd; Unix: bash -lc).
+fn shell_command(cmd: &str, cwd: &Path) -> tokio::process::Command {
+    #[cfg(target_os = "windows")]
+    let command = {
+        // Prefer PowerShell (pwsh), then Windows PowerShell, then cmd.exe
+        if which::which("pwsh").is_ok() {
+            let mut c = tokio::process::Command::new("pwsh");
+            c.arg("-NoLogo").arg("-Command").arg(cmd).current_dir(cwd);
+            c
+        } else if which::which("powershell").is_ok() {
+            let mut c = tokio::process::Command::new("powershell");
+            c.arg("-NoLogo").arg("-Command").arg(cmd).current_dir(cwd);
+            c
+        } else {
+            let mut c = tokio::process::Command::new("cmd");
+            c.arg("/C").arg(cmd).current_dir(cwd);
+            c
+        }
+    };
+
+    #[cfg(not(target_os = "windows"))]
+    let command = {
+        let mut c = tokio::process::Command::new("bash");
+        c.arg("-lc").arg(cmd).current_dir(cwd);
+        c
+    };
+    command
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+
+    #[tokio::test]
+    async fn test_structured_output() {
+        let tool = BashTool::new(
+            std::env::temp_dir(),
+            BashConfig {
+                max_output_bytes: 20,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let streamed = Arc::new(StdMutex::new(String::new()));
+        let s = streamed.clone();
+        let sink: OutputSink = Arc::new(move |c| s.lock().unwrap().push_str(c));
+        let res = tool
+            .execute_streaming(
+                json!({"command": "printf '\\033[32mok\\033[0m\\n'; echo bad >&2; exit 2"}),
+                sink,
+            )
+            .await;
+        assert!(!res.success);
+        assert_eq!(res.error.as_deref(), Some("exit code: 2"));
+        // login shells may print profile noise on stderr, so only check the ends
+        assert!(
+            res.content
+                .starts_with("exit code: 2\n<stdout>\nok\n</stdout>\n<stderr>\n"),
+            "{}",
+            res.content
+        );
+        assert!(res.content.ends_with("bad\n</stderr>"));
+        assert!(streamed.lock().unwrap().contains("ok\n"));
+
+        // a long line without newlines is still capped
+        let res = tool
+            .execute(json!({"command": "head -c 1000000 /dev/zero | tr '\\0' x"}))
+            .await;
+        assert!(res.content.contains("[... 999980 bytes elided ...]"));
+
+        let res = tool.execute(json!({"command": "seq 1 100"})).await;
+        assert!(res.success);
+        assert!(res.content.contains("bytes elided"));
+        assert!(res.content.contains("\n100\n</stdout>"));
+
+        let res = tool.execute(json!({"command": "kill -9 $$"})).await;
+        assert_eq!(
+            res.error.as_deref(),
+            Some("terminated by signal 9 (SIGKILL)")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_command() {
+        let config = BashConfig {
+            timeout_secs: 4,
+            ..Default::default()
+        };
+        let tool = BashTool::new(std::env::temp_dir(), config.clone()).unwrap();
+        let res = tool
+            .execute(json!({"command": "echo started; sleep 30"}))
+            .await;
+        assert!(res.error.unwrap().starts_with("timed out after 4s"));
+        assert!(res.content.contains("started"), "{}", res.content);
+
+        // a hung command in the persistent shell costs the session, not the tool
+        let tool = BashTool::new(
+            std::env::temp_dir(),
+            BashConfig {
+                persistent_shell: true,
+                ..config
+            },
+        )
+        .unwrap();
+        tool.execute(json!({"command": "export KEPT=1"})).await;
+        let started = std::time::Instant::now();
+        let res = tool.execute(json!({"command": "sleep 30 | cat"})).await;
+        assert!(res.error.unwrap().contains("the shell was killed"));
+        assert!(started.elapsed() < Duration::from_secs(15));
+        let res = tool
+            .execute(json!({"command": "echo \"kept=${KEPT:-no}\""}))
+            .await;
+        assert!(res.content.contains("kept=no"), "{}", res.content);
+    }
+
+    /// Whether `pid` is still running (a zombie waiting to be reaped counts as gone).
+    #[cfg(target_os = "linux")]
+    fn running(pid: &str) -> bool {
+        std::fs::read_to_string(format!("/proc/{pid}/stat"))
+            .is_ok_and(|stat| !stat.rsplit(')').next().unwrap_or("").starts_with(" Z"))
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_restart_and_shutdown_kill_background_jobs() {
+        let tool = BashTool::new(
+            std::env::temp_dir(),
+            BashConfig {
+                persistent_shell: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let start_job = async || {
+            let res = tool
+                .execute(json!({"command": "sleep 100 & echo \"pid=$!\""}))
+                .await;
+            let pid = res.content.split("pid=").nth(1).unwrap();
+            let pid: String = pid.chars().take_while(char::is_ascii_digit).collect();
+            assert!(running(&pid));
+            pid
+        };
+        let gone = async |pid: &str| {
+            for _ in 0..50 {
+                if !running(pid) {
+                    return true;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            false
+        };
+
+        let pid = start_job().await;
+        let res = tool.execute(json!({"restart": true})).await;
+        assert!(res.success, "{:?}", res.error);
+        assert!(gone(&pid).await, "sleep {pid} survived the restart");
+
+        let pid = start_job().await;
+        crate::tools::shell::cleanup_shells().await;
+        assert!(gone(&pid).await, "sleep {pid} survived the shutdown");
+    }
 }
@@ -0,0 +1,204 @@
+//! Search/replace edits for `edit_file`: unique-match checks, "did you mean" hints for
+//! near misses, and unified diffs of the result.
+
+use serde::Deserialize;
+use similar::TextDiff;
+
+/// Diffs longer than this are cut in the middle.
+const MAX_DIFF_BYTES: usize = 20_000;
+/// Lines compared for fuzzy hints; bigger files only get the whitespace-insensitive check.
+const MAX_FUZZY_LINES: usize = 20_000;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Edit {
+    pub old_str: String,
+    pub new_str: String,
+    #[serde(default)]
+    pub replace_all: bool,
+}
+
+/// Apply `edits` in order to `content`. Fails without a partial result if any edit doesn't
+/// match exactly once (or at least once with `replace_all`). Returns the new text and the
+/// number of replacements.
+pub fn apply_edits(content: &str, edits: &[Edit]) -> Result<(String, usize), String> {
+    let mut text = content.to_string();
+    let mut total = 0;
+    for (i, edit) in edits.iter().enumerate() {
+        let label = if edits.len() > 1 {
+            format!("edit {}: ", i + 1)
+        } else {
+            String::new()
+        };
+        // file text has `\n` line breaks, so match the model's text the same way
+        let old = edit.old_str.replace("\r\n", "\n");
+        let new = edit.new_str.replace("\r\n", "\n");
+        if old.is_empty() {
+            return Err(format!("{}old_str is empty", label));
+        }
+        if old == new {
+            return Err(format!("{}old_str and new_str are identical", label));
+        }
+        let starts: Vec<usize> = text.match_indices(&old).map(|(i, _)| i).collect();
+        match starts.len() {
+            0 => {
+                return Err(format!("{}old_str not found{}", label, hint(&text, &old)));
+            }
+            1 => {}
+            n if !edit.replace_all => {
+                let lines: Vec<String> = starts
+                    .iter()
+                    .take(10)
+                    .map(|&s| line_of(&text, s).to_string())
+                    .collect();
+                return Err(format!(
+                    "{}old_str matches {} times (lines {}{}); include more surrounding lines to make it unique, or set replace_all",
+                    label,
+                    n,
+                    lines.join(", "),
+                    if n > 10 { ", ..." } else { "" }
+                ));
+            }
+            _ => {}
+        }
+        total += starts.len();
+        text = text.replace(&old, &new);
+    }
+    Ok((text, total))
+}
+
+/// 1-based line number of byte offset `at`.
+fn line_of(text: &str, at: usize) -> usize {
+    text[..at].matches('\n').count() + 1
+}
+
+/// Point at lines that match `old` apart from whitespace, or else at the lines most similar
+/// to its first line.
+fn hint(text: &str, old: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let wanted: Vec<String> = old
+        .trim_matches('\n')
+        .lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+    if wanted.is_empty() || lines.len() < wanted.len() {
+        return String::new();
+    }
+    let normalized: Vec<String> = lines
+        .iter()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect();
+    if let Some(start) = normalized.windows(wanted.len()).position(|w| w == wanted) {
+        return format!(
+            "; it matches lines {}-{} if whitespace is ignored. The file has:\n{}",
+            start + 1,
+            start + wanted.len(),
+            excerpt(&lines, start, wanted.len())
+        );
+    }
+    if lines.len() > MAX_FUZZY_LINES {
+        return String::new();
+    }
+    let Some(first) = wanted.iter().find(|l| !l.is_empty()) else {
+        return String::new();
+    };
+    let mut scored: Vec<(f32, usize)> = normalized
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !l.is_empty())
+        .map(|(i, l)| (TextDiff::from_chars(first.as_str(), l.as_str()).ratio(), i))
+        .filter(|(score, _)| *score >= 0.6)
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    if scored.is_empty() {
+        return String::new();
+    }
+    let mut out = String::from("; did you mean:");
+    for (_, start) in scored.into_iter().take(3) {
+        let len = wanted.len().min(lines.len() - start);
+        out.push('\n');
+        out.push_str(&excerpt(&lines, start, len));
+    }
+    out
+}
+
+fn excerpt(lines: &[&str], start: usize, len: usize) -> String {
+    lines[start..start + len]
+        .iter()
+        .enumerate()
+        .map(|(i, l)| format!("{:>6}\t{}", start + i + 1, l))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Unified diff between two versions of `path`, capped at `MAX_DIFF_BYTES`.
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    let diff = TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string();
+    if diff.len() <= MAX_DIFF_BYTES {
+        return diff;
+    }
+    let mut capped = crate::tools::output::CappedOutput::new(MAX_DIFF_BYTES);
+    capped.push(diff.as_bytes());
+    capped.render()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(old: &str, new: &str, replace_all: bool) -> Edit {
+        Edit {
+            old_str: old.into(),
+            new_str: new.into(),
+            replace_all,
+        }
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let src = "fn a() {\n    let x = 1;\n}\nfn b() {\n    let x = 1;\n}\n";
+
+        let err = apply_edits(src, &[edit("let x = 1;", "let x = 2;", false)]).unwrap_err();
+        assert!(err.contains("matches 2 times (lines 2, 5)"), "{err}");
+        let (out, n) = apply_edits(src, &[edit("let x = 1;", "let x = 2;", true)]).unwrap();
+        assert_eq!((out.matches("x = 2").count(), n), (2, 2));
+
+        let (out, n) = apply_edits(
+            src,
+            &[
+                edit(
+                    "fn a() {\n    let x = 1;",
+                    "fn a() {\n    let x = 3;",
+                    false,
+                ),
+                edit("fn b", "fn c", false),
+            ],
+        )
+        .unwrap();
+        assert_eq!(n, 2);
+        assert!(out.contains("x = 3") && out.contains("fn c"));
+
+        // a failing edit in a batch fails the whole batch
+        let err = apply_edits(
+            src,
+            &[edit("fn a", "fn z", false), edit("nope", "x", false)],
+        )
+        .unwrap_err();
+        assert!(err.starts_with("edit 2: old_str not found"), "{err}");
+
+        let err = apply_edits(src, &[edit("fn b() {\n  let x = 1;", "", false)]).unwrap_err();
+        assert!(
+            err.contains("matches lines 4-5 if whitespace is ignored"),
+            "{err}"
+        );
+        let err = apply_edits(src, &[edit("fn bb() {", "", false)]).unwrap_err();
+        assert!(err.contains("did you mean:\n     4\tfn b() {"), "{err}");
+
+        let diff = unified_diff("x.rs", src, &out);
+        assert!(diff.starts_with("--- a/x.rs\n+++ b/x.rs\n@@"));
+        assert!(diff.contains("-    let x = 1;\n+    let x = 3;\n"));
+    }
+}
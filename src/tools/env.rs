@@ -0,0 +1,144 @@
+//! Environment policy for processes spawned by tools (bash commands, background processes,
+//! MCP servers): credential variables are removed, then configured variables are injected.
+
+use crate::config::EnvConfig;
+use std::collections::BTreeMap;
+
+/// Variables scrubbed by default: the keys `Config::apply_env_overrides` reads, plus
+/// common credential naming patterns (`*` matches any text, case-insensitive).
+const SECRET_PATTERNS: &[&str] = &[
+    "MINIAGENT_API_KEY",
+    "ANTHROPIC_API_KEY",
+    "OPENAI_API_KEY",
+    "GEMINI_API_KEY",
+    "MINIMAX_API_KEY",
+    "MINIMAXI_API_KEY",
+    "*_API_KEY",
+    "*_APIKEY",
+    "*_SECRET",
+    "*_SECRET_KEY",
+    "*_ACCESS_KEY",
+    "*_SECRET_ACCESS_KEY",
+    "*_TOKEN",
+    "*_PASSWORD",
+];
+
+#[derive(Debug, Clone)]
+pub struct EnvPolicy {
+    strip: Vec<String>,
+    passthrough: Vec<String>,
+    set: BTreeMap<String, String>,
+}
+
+impl Default for EnvPolicy {
+    fn default() -> Self {
+        Self::from_config(&EnvConfig::default())
+    }
+}
+
+impl EnvPolicy {
+    pub fn from_config(config: &EnvConfig) -> Self {
+        let mut strip: Vec<String> = Vec::new();
+        if config.scrub_secrets {
+            strip.extend(SECRET_PATTERNS.iter().map(|s| s.to_string()));
+        }
+        strip.extend(config.strip.iter().cloned());
+        Self {
+            strip,
+            passthrough: config.passthrough.clone(),
+            set: config
+                .set
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+
+    /// Names from `vars` that the policy removes (explicitly injected ones excepted).
+    pub fn removed<'a>(&self, vars: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+        vars.into_iter()
+            .filter(|name| {
+                self.strip.iter().any(|p| glob_match(p, name))
+                    && !self.passthrough.iter().any(|p| glob_match(p, name))
+                    && !self.set.contains_key(*name)
+            })
+            .collect()
+    }
+
+    /// Apply the policy to a command about to inherit this process's environment.
+    pub fn apply(&self, command: &mut tokio::process::Command) {
+        let names: Vec<String> = std::env::vars_os()
+            .filter_map(|(k, _)| k.into_string().ok())
+            .collect();
+        for name in self.removed(names.iter().map(String::as_str)) {
+            command.env_remove(name);
+        }
+        command.envs(&self.set);
+    }
+}
+
+/// Case-insensitive match where `*` stands for any (possibly empty) text.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_ascii_uppercase();
+    let name = name.to_ascii_uppercase();
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == name;
+    }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !name.starts_with(first) || name.len() < first.len() + last.len() {
+        return false;
+    }
+    let mut rest = &name[first.len()..name.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    name.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_scrubs_secrets() {
+        let cfg = EnvConfig {
+            passthrough: vec!["GH_TOKEN".into()],
+            strip: vec!["INTERNAL_*".into()],
+            set: [("DATABASE_PASSWORD".to_string(), "dev".to_string())].into(),
+            ..Default::default()
+        };
+        let policy = EnvPolicy::from_config(&cfg);
+        let vars = [
+            "PATH",
+            "HOME",
+            "OPENAI_API_KEY",
+            "MINIAGENT_API_KEY",
+            "MINIAGENT_MODEL",
+            "aws_secret_access_key",
+            "GITHUB_TOKEN",
+            "GH_TOKEN",
+            "INTERNAL_URL",
+            "DATABASE_PASSWORD",
+        ];
+        assert_eq!(
+            policy.removed(vars),
+            vec![
+                "OPENAI_API_KEY",
+                "MINIAGENT_API_KEY",
+                "aws_secret_access_key",
+                "GITHUB_TOKEN",
+                "INTERNAL_URL"
+            ]
+        );
+
+        let off = EnvPolicy::from_config(&EnvConfig {
+            scrub_secrets: false,
+            ..Default::default()
+        });
+        assert!(off.removed(vars).is_empty());
+    }
+}
@@ -0,0 +1,553 @@
+//! `fetch_url`: HTTP GET with size and time limits. HTML is converted to Markdown, JSON is
+//! pretty-printed, and hosts are checked against `tools.fetch` allow/deny lists (redirects too).
+//! Loopback, link-local and private addresses are refused unless the host is allow-listed,
+//! whether given literally or reached through DNS.
+
+use crate::config::FetchConfig;
+use crate::tools::base::{Tool, ToolResult};
+use crate::tools::html::html_to_markdown;
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+/// Characters of converted content returned per call; `start` pages through the rest.
+const DEFAULT_MAX_CHARS: usize = 40_000;
+const MAX_REDIRECTS: usize = 10;
+
+/// Host filter built from `tools.fetch.allow_domains` / `deny_domains`.
+#[derive(Debug, Clone)]
+struct DomainRules {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+fn normalize_domain(pattern: &str) -> String {
+    let p = pattern.trim().trim_end_matches('.').to_ascii_lowercase();
+    p.strip_prefix("*.").map(str::to_string).unwrap_or(p)
+}
+
+/// `host` is `domain` or one of its subdomains.
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|rest| rest.ends_with('.'))
+}
+
+/// Whether `ip` is on the public internet, not loopback, link-local (which includes cloud
+/// metadata endpoints like 169.254.169.254), private, or otherwise special-purpose.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || a == 0
+                // carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public(IpAddr::V4(v4)),
+            None => {
+                !(v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_multicast()
+                    || v6.is_unique_local()
+                    || v6.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+impl DomainRules {
+    /// `host` is covered by `tools.fetch.allow_domains`.
+    fn allow_listed(&self, host: &str) -> bool {
+        self.allow.iter().any(|d| domain_matches(host, d))
+    }
+
+    fn private_address(host: &str, ip: IpAddr) -> String {
+        format!(
+            "{} is a private or local address ({}); add {} to tools.fetch.allow_domains to fetch it",
+            host, ip, host
+        )
+    }
+
+    fn new(config: &FetchConfig) -> Self {
+        Self {
+            allow: config
+                .allow_domains
+                .iter()
+                .map(|d| normalize_domain(d))
+                .collect(),
+            deny: config
+                .deny_domains
+                .iter()
+                .map(|d| normalize_domain(d))
+                .collect(),
+        }
+    }
+
+    fn check(&self, url: &Url) -> Result<(), String> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!(
+                "unsupported URL scheme '{}': only http and https can be fetched",
+                url.scheme()
+            ));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| format!("URL has no host: {}", url))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_ascii_lowercase();
+        if self.deny.iter().any(|d| domain_matches(&host, d)) {
+            return Err(format!("{} is blocked by tools.fetch.deny_domains", host));
+        }
+        if !self.allow.is_empty() && !self.allow_listed(&host) {
+            return Err(format!("{} is not in tools.fetch.allow_domains", host));
+        }
+        // names are checked once resolved, by `CheckedResolver`
+        if let Ok(ip) = host.parse::<IpAddr>()
+            && !is_public(ip)
+            && !self.allow_listed(&host)
+        {
+            return Err(Self::private_address(&host, ip));
+        }
+        Ok(())
+    }
+}
+
+/// DNS resolver that drops non-public addresses for hosts that aren't allow-listed, so a
+/// public name pointing at 127.0.0.1 or a metadata endpoint can't be used to reach them.
+struct CheckedResolver {
+    rules: DomainRules,
+}
+
+impl reqwest::dns::Resolve for CheckedResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().trim_end_matches('.').to_ascii_lowercase();
+        let trusted = self.rules.allow_listed(&host);
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if trusted {
+                return Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs);
+            }
+            let public: Vec<SocketAddr> = addrs
+                .iter()
+                .copied()
+                .filter(|a| is_public(a.ip()))
+                .collect();
+            match (public.is_empty(), addrs.first()) {
+                (true, Some(first)) => Err(DomainRules::private_address(&host, first.ip()).into()),
+                _ => Ok(Box::new(public.into_iter()) as reqwest::dns::Addrs),
+            }
+        })
+    }
+}
+
+pub struct FetchTool {
+    pub config: FetchConfig,
+    rules: DomainRules,
+    client: reqwest::Client,
+}
+
+impl FetchTool {
+    /// Fails if the HTTP client can't be built (e.g. no TLS backend).
+    pub fn new(config: FetchConfig) -> anyhow::Result<Self> {
+        let rules = DomainRules::new(&config);
+        let redirect_rules = rules.clone();
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs.max(1)))
+            .user_agent(concat!("miniagent/", env!("CARGO_PKG_VERSION")))
+            .dns_resolver(Arc::new(CheckedResolver {
+                rules: rules.clone(),
+            }))
+            .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error(format!("more than {} redirects", MAX_REDIRECTS))
+                } else if let Err(e) = redirect_rules.check(attempt.url()) {
+                    let msg = format!("redirect to {} refused: {}", attempt.url(), e);
+                    attempt.error(msg)
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .build()?;
+        Ok(Self {
+            config,
+            rules,
+            client,
+        })
+    }
+
+    /// GET `url` and return the final URL, content type and at most `max_bytes` of the body
+    /// (plus whether it was cut off).
+    async fn download(&self, url: Url) -> Result<(Url, String, Vec<u8>, bool), String> {
+        let mut response = self.client.get(url).send().await.map_err(request_error)?;
+        let final_url = response.url().clone();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response.chunk().await.map_err(request_error)? {
+            let room = self.config.max_bytes.saturating_sub(body.len());
+            if chunk.len() > room {
+                body.extend_from_slice(&chunk[..room]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+        if !status.is_success() {
+            let snippet: String = String::from_utf8_lossy(&body).chars().take(500).collect();
+            return Err(format!(
+                "HTTP {} from {}{}",
+                status,
+                final_url,
+                if snippet.trim().is_empty() {
+                    String::new()
+                } else {
+                    format!(": {}", snippet.trim())
+                }
+            ));
+        }
+        Ok((final_url, content_type, body, truncated))
+    }
+}
+
+fn request_error(e: reqwest::Error) -> String {
+    if e.is_timeout() {
+        return "request timed out (tools.fetch.timeout_secs)".into();
+    }
+    // the redirect policy's message is in the source chain
+    let mut msg = e.to_string();
+    let mut source = std::error::Error::source(&e);
+    while let Some(s) = source {
+        msg.push_str(&format!(": {}", s));
+        source = s.source();
+    }
+    format!("request failed: {}", msg)
+}
+
+/// Decode `body` using the charset from the content type, falling back to UTF-8.
+fn decode_body(content_type: &str, body: &[u8]) -> String {
+    let encoding = content_type
+        .split(';')
+        .filter_map(|p| p.trim().strip_prefix("charset="))
+        .find_map(|c| encoding_rs::Encoding::for_label(c.trim_matches('"').as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    encoding.decode(body).0.into_owned()
+}
+
+/// Convert a response to text for the model, returning an optional page title with it.
+fn render(url: &Url, content_type: &str, body: &[u8]) -> Result<(Option<String>, String), String> {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    let text = decode_body(content_type, body);
+    let looks_html = || {
+        let head: String = text.trim_start().chars().take(256).collect();
+        let head = head.to_ascii_lowercase();
+        head.starts_with("<!doctype html") || head.starts_with("<html")
+    };
+    if mime == "text/html" || mime == "application/xhtml+xml" || (mime.is_empty() && looks_html()) {
+        return Ok(html_to_markdown(&text, Some(url)));
+    }
+    if mime == "application/json" || mime.ends_with("+json") || mime.is_empty() {
+        if let Ok(value) = serde_json::from_str::<Value>(&text) {
+            let pretty = serde_json::to_string_pretty(&value).unwrap_or(text);
+            return Ok((None, pretty));
+        }
+        if !mime.is_empty() {
+            // cut off or malformed: show it as text
+            return Ok((None, text));
+        }
+    }
+    let textual = mime.starts_with("text/")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/xml"
+                | "application/javascript"
+                | "application/x-yaml"
+                | "application/yaml"
+        );
+    if textual || (mime.is_empty() && !body.contains(&0)) {
+        return Ok((None, text));
+    }
+    Err(format!(
+        "{} is {} ({} bytes), which can't be shown as text",
+        url,
+        if mime.is_empty() { "binary" } else { &mime },
+        body.len()
+    ))
+}
+
+#[async_trait]
+impl Tool for FetchTool {
+    fn name(&self) -> &str {
+        "fetch_url"
+    }
+    fn description(&self) -> &str {
+        "Fetch a web page or API response with HTTP GET. HTML is converted to Markdown, JSON is pretty-printed, plain text is returned as is. Long content is paged: pass start to continue where the previous call stopped"
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": {"type": "string", "description": "http(s) URL to fetch"},
+                "start": {"type": "integer", "description": "Character offset into the converted content (default 0)"},
+                "max_chars": {"type": "integer", "description": "Characters to return (default 40000)"}
+            },
+            "required": ["url"]
+        })
+    }
+    async fn execute(&self, args: Value) -> ToolResult {
+        let fail = |e: String| ToolResult {
+            success: false,
+            content: String::new(),
+            error: Some(e),
+        };
+        let raw = args
+            .get("url")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .trim();
+        let url = match Url::parse(raw) {
+            Ok(u) => u,
+            Err(e) => return fail(format!("invalid URL '{}': {}", raw, e)),
+        };
+        if let Err(e) = self.rules.check(&url) {
+            return fail(e);
+        }
+        let start = args.get("start").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        let max_chars = args
+            .get("max_chars")
+            .and_then(|v| v.as_u64())
+            .map_or(DEFAULT_MAX_CHARS, |n| n.max(1) as usize);
+
+        let (final_url, content_type, body, truncated) = match self.download(url).await {
+            Ok(r) => r,
+            Err(e) => return fail(e),
+        };
+        let (title, text) = match render(&final_url, &content_type, &body) {
+            Ok(r) => r,
+            Err(e) => return fail(e),
+        };
+
+        let mut out = format!("URL: {}\n", final_url);
+        if !content_type.is_empty() {
+            out.push_str(&format!("Content-Type: {}\n", content_type));
+        }
+        if let Some(title) = title {
+            out.push_str(&format!("Title: {}\n", title));
+        }
+        if truncated {
+            out.push_str(&format!(
+                "[response cut off after {} bytes (tools.fetch.max_bytes)]\n",
+                self.config.max_bytes
+            ));
+        }
+        out.push('\n');
+        let total = text.chars().count();
+        if start >= total && total > 0 {
+            return fail(format!(
+                "start={} is past the end of the content ({} characters)",
+                start, total
+            ));
+        }
+        out.extend(text.chars().skip(start).take(max_chars));
+        let end = (start + max_chars).min(total);
+        if start > 0 || end < total {
+            out.push_str(&format!(
+                "\n\n[showing characters {}-{} of {}",
+                start, end, total
+            ));
+            if end < total {
+                out.push_str(&format!("; call again with start={} to continue", end));
+            }
+            out.push(']');
+        }
+        ToolResult {
+            success: true,
+            content: out,
+            error: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Minimal HTTP/1.1 server answering each connection from `route`.
+    async fn serve(route: fn(&str, u16) -> (&'static str, String, String)) -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                let (status, headers, body) = route(&path, port);
+                let response = format!(
+                    "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    headers,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_fetch_url() {
+        let port = serve(|path, port| match path {
+            "/doc" => (
+                "200 OK",
+                "Content-Type: text/html; charset=utf-8\r\n".into(),
+                "<html><head><title>Guide</title></head><body><nav>menu</nav><h2>Usage</h2><p>See <a href=\"/api\">the API</a>.</p></body></html>".into(),
+            ),
+            "/data" => (
+                "200 OK",
+                "Content-Type: application/json\r\n".into(),
+                r#"{"name":"mini","tags":["a"]}"#.into(),
+            ),
+            "/big" => ("200 OK", "Content-Type: text/plain\r\n".into(), "x".repeat(5000)),
+            "/away" => (
+                "302 Found",
+                format!("Location: http://localhost:{}/doc\r\n", port),
+                String::new(),
+            ),
+            _ => ("404 Not Found", String::new(), "no such page".into()),
+        })
+        .await;
+        let base = format!("http://127.0.0.1:{}", port);
+        let tool = FetchTool::new(FetchConfig {
+            allow_domains: vec!["127.0.0.1".into()],
+            deny_domains: vec!["localhost".into()],
+            max_bytes: 1000,
+            ..FetchConfig::default()
+        })
+        .unwrap();
+
+        let res = tool.execute(json!({"url": format!("{base}/doc")})).await;
+        assert!(res.success, "{:?}", res.error);
+        assert_eq!(
+            res.content,
+            format!(
+                "URL: {base}/doc\nContent-Type: text/html; charset=utf-8\nTitle: Guide\n\n## Usage\n\nSee [the API]({base}/api)."
+            )
+        );
+
+        let res = tool.execute(json!({"url": format!("{base}/data")})).await;
+        assert!(
+            res.content
+                .ends_with("{\n  \"name\": \"mini\",\n  \"tags\": [\n    \"a\"\n  ]\n}")
+        );
+
+        let res = tool
+            .execute(json!({"url": format!("{base}/big"), "start": 900, "max_chars": 50}))
+            .await;
+        assert!(res.content.contains("[response cut off after 1000 bytes"));
+        assert!(res.content.ends_with(
+            "[showing characters 900-950 of 1000; call again with start=950 to continue]"
+        ));
+
+        let res = tool
+            .execute(json!({"url": format!("{base}/missing")}))
+            .await;
+        assert!(res.error.unwrap().starts_with("HTTP 404 Not Found"));
+
+        // denied hosts are refused up front and as redirect targets
+        let res = tool
+            .execute(json!({"url": format!("http://localhost:{port}/doc")}))
+            .await;
+        assert!(res.error.unwrap().contains("deny_domains"));
+        let res = tool.execute(json!({"url": format!("{base}/away")})).await;
+        assert!(res.error.unwrap().contains("deny_domains"));
+
+        let res = tool.execute(json!({"url": "file:///etc/passwd"})).await;
+        assert!(res.error.unwrap().contains("only http and https"));
+
+        let allow_only = FetchTool::new(FetchConfig {
+            allow_domains: vec!["*.example.com".into()],
+            ..FetchConfig::default()
+        })
+        .unwrap();
+        assert!(
+            allow_only
+                .rules
+                .check(&Url::parse("https://docs.example.com/x").unwrap())
+                .is_ok()
+        );
+        assert!(
+            allow_only
+                .rules
+                .check(&Url::parse("https://badexample.com/").unwrap())
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_private_addresses_need_allow_listing() {
+        let port = serve(|_, _| {
+            (
+                "200 OK",
+                "Content-Type: text/plain\r\n".into(),
+                "secret".into(),
+            )
+        })
+        .await;
+        let tool = FetchTool::new(FetchConfig::default()).unwrap();
+        for url in [
+            format!("http://127.0.0.1:{port}/"),
+            format!("http://[::ffff:127.0.0.1]:{port}/"),
+            "http://169.254.169.254/latest/meta-data/".to_string(),
+            "http://10.0.0.1/".to_string(),
+            // a name is refused once it resolves to a local address
+            format!("http://localhost:{port}/"),
+        ] {
+            let res = tool.execute(json!({ "url": url })).await;
+            let error = res.error.unwrap_or_default();
+            assert!(error.contains("private or local address"), "{url}: {error}");
+        }
+
+        let trusted = FetchTool::new(FetchConfig {
+            allow_domains: vec!["localhost".into()],
+            ..FetchConfig::default()
+        })
+        .unwrap();
+        let res = trusted
+            .execute(json!({"url": format!("http://localhost:{port}/")}))
+            .await;
+        assert!(res.content.ends_with("secret"), "{:?}", res.error);
+
+        assert!(is_public("93.184.216.34".parse().unwrap()));
+        assert!(is_public("2606:4700::1111".parse().unwrap()));
+        assert!(!is_public("100.64.0.1".parse().unwrap()));
+        assert!(!is_public("fd00::1".parse().unwrap()));
+        assert!(!is_public("fe80::1".parse().unwrap()));
+    }
+}
@@ -1,36 +1,60 @@
+use crate::tools::atomic::{atomic_write, check_write_size};
 use crate::tools::base::{Tool, ToolResult};
+use crate::tools::edit::{Edit, apply_edits, unified_diff};
+use crate::tools::notebook::is_notebook;
+use crate::tools::paths::PathPolicy;
+use crate::tools::read_tracker::{FileStamp, ReadTracker};
+use crate::tools::text::{self, LineEnding, TextFormat};
 use async_trait::async_trait;
+use encoding_rs::Encoding;
 use serde_json::{Value, json};
-use std::path::{Path, PathBuf};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
 
 fn schema_for_path_content() -> Value {
     json!({
         "type": "object",
         "properties": {
             "path": {"type": "string", "description": "Relative or absolute file path"},
-            "content": {"type": "string", "description": "File content (UTF-8)"}
+            "content": {"type": "string", "description": "File content"}
         },
         "required": ["path"],
     })
 }
 
-fn resolve_path(workspace: &Path, input: &str) -> PathBuf {
-    let path = PathBuf::from(input);
-    if path.is_absolute() {
-        path
-    } else {
-        workspace.join(path)
+fn path_error(e: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        content: String::new(),
+        error: Some(e),
     }
 }
 
+/// Lines returned by `read_file` when no `limit` is given.
+const DEFAULT_READ_LINES: usize = 2000;
+/// Output cap for `read_file`; reading stops at the last whole line below it.
+const MAX_READ_BYTES: usize = 50_000;
+/// Longer lines are cut so one minified line can't fill the output.
+const MAX_LINE_CHARS: usize = 2000;
+/// Files up to this size are read whole; larger ones are streamed up to the requested page.
+const MAX_WHOLE_READ_BYTES: u64 = 8 * 1024 * 1024;
+/// Prefix of a streamed file used to tell its type and encoding.
+const SNIFF_BYTES: usize = 8192;
+
 pub struct ReadTool {
-    pub workspace: PathBuf,
+    pub paths: Arc<PathPolicy>,
+    pub tracker: Arc<ReadTracker>,
 }
 pub struct WriteTool {
-    pub workspace: PathBuf,
+    pub paths: Arc<PathPolicy>,
+    pub tracker: Arc<ReadTracker>,
+    pub max_write_bytes: usize,
 }
 pub struct EditTool {
-    pub workspace: PathBuf,
+    pub paths: Arc<PathPolicy>,
+    pub tracker: Arc<ReadTracker>,
+    pub max_write_bytes: usize,
 }
 
 #[async_trait]
@@ -39,12 +63,16 @@ impl Tool for ReadTool {
         "read_file"
     }
     fn description(&self) -> &str {
-        "Read a text file from workspace (UTF-8)"
+        "Read a text file with line numbers. Large files are returned in pages: pass `offset` (first line, 1-based) and `limit` (number of lines) to read further"
     }
     fn parameters(&self) -> Value {
         json!({
             "type": "object",
-            "properties": { "path": {"type": "string"} },
+            "properties": {
+                "path": {"type": "string"},
+                "offset": {"type": "integer", "description": "First line to read, 1-based (default 1)"},
+                "limit": {"type": "integer", "description": "Maximum number of lines (default 2000)"}
+            },
             "required": ["path"],
         })
     }
@@ -60,20 +88,257 @@ impl Tool for ReadTool {
                 };
             }
         };
-        let full = resolve_path(&self.workspace, path);
-        match tokio::fs::read_to_string(&full).await {
-            Ok(c) => ToolResult {
+        let offset = args.get("offset").and_then(|v| v.as_u64()).unwrap_or(1) as usize;
+        let limit = args
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .map(|n| n as usize)
+            .unwrap_or(DEFAULT_READ_LINES);
+        let full = match self.paths.resolve_read(path) {
+            Ok(p) => p,
+            Err(e) => return path_error(e),
+        };
+        let stamp = FileStamp::of(&full).await;
+        if stamp.is_some_and(|s| s.len > MAX_WHOLE_READ_BYTES) {
+            return self.read_streamed(&full, stamp, offset.max(1), limit).await;
+        }
+        let bytes = match tokio::fs::read(&full).await {
+            Ok(b) => b,
+            Err(e) => {
+                return ToolResult {
+                    success: false,
+                    content: String::new(),
+                    error: Some(format!("read error: {}", e)),
+                };
+            }
+        };
+        self.tracker.record_read(&full, stamp, &bytes);
+        let (text, format) = match text::decode(&bytes) {
+            Ok(decoded) => decoded,
+            Err(kind) => {
+                return ToolResult {
+                    success: true,
+                    content: format!(
+                        "{} is a binary file ({}, {} bytes); not shown",
+                        full.display(),
+                        kind,
+                        bytes.len()
+                    ),
+                    error: None,
+                };
+            }
+        };
+        match number_lines(&text, offset.max(1), limit) {
+            Ok(mut c) => {
+                if !format.is_default() {
+                    c = format!(
+                        "[file format: {}; write_file and edit_file keep it]\n{}",
+                        format.describe(),
+                        c
+                    );
+                }
+                if is_notebook(&full) {
+                    c = format!(
+                        "[Jupyter notebook: read_notebook shows its cells and edit_notebook changes them]\n{}",
+                        c
+                    );
+                }
+                ToolResult {
+                    success: true,
+                    content: c,
+                    error: None,
+                }
+            }
+            Err(e) => path_error(e),
+        }
+    }
+}
+
+impl ReadTool {
+    /// Page through a file too large to load whole: its type and encoding are judged from
+    /// the first bytes, and only the lines up to the requested page are read.
+    async fn read_streamed(
+        &self,
+        full: &Path,
+        stamp: Option<FileStamp>,
+        offset: usize,
+        limit: usize,
+    ) -> ToolResult {
+        let read_error = |e: std::io::Error| ToolResult {
+            success: false,
+            content: String::new(),
+            error: Some(format!("read error: {}", e)),
+        };
+        let mut reader = match tokio::fs::File::open(full).await {
+            Ok(f) => BufReader::with_capacity(SNIFF_BYTES, f),
+            Err(e) => return read_error(e),
+        };
+        let size = stamp.map_or(0, |s| s.len);
+        let (encoding, bom_len) = match reader.fill_buf().await.map(text::sniff) {
+            Ok(Ok(sniffed)) => sniffed,
+            Ok(Err(kind)) => {
+                return ToolResult {
+                    success: true,
+                    content: format!(
+                        "{} is a binary file ({}, {} bytes); not shown",
+                        full.display(),
+                        kind,
+                        size
+                    ),
+                    error: None,
+                };
+            }
+            Err(e) => return read_error(e),
+        };
+        if !encoding.is_ascii_compatible() {
+            return path_error(format!(
+                "{} is a {} file of {} bytes, too large to read in pages; only ASCII-compatible encodings can be paged",
+                full.display(),
+                encoding.name(),
+                size
+            ));
+        }
+        reader.consume(bom_len);
+        self.tracker.record_partial(full, stamp);
+        match stream_lines(reader, encoding, offset, limit).await {
+            Ok(Ok(c)) => ToolResult {
                 success: true,
                 content: c,
                 error: None,
             },
-            Err(e) => ToolResult {
-                success: false,
-                content: String::new(),
-                error: Some(format!("read error: {}", e)),
-            },
+            Ok(Err(e)) => path_error(e),
+            Err(e) => read_error(e),
+        }
+    }
+}
+
+/// Read one line into `line`, keeping at most `max` bytes of it. False at the end of input.
+async fn read_line_capped<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    max: usize,
+    line: &mut Vec<u8>,
+) -> std::io::Result<bool> {
+    line.clear();
+    let mut any = false;
+    loop {
+        let buf = reader.fill_buf().await?;
+        if buf.is_empty() {
+            return Ok(any);
+        }
+        any = true;
+        let (take, done) = match buf.iter().position(|&b| b == b'\n') {
+            Some(i) => (i + 1, true),
+            None => (buf.len(), false),
+        };
+        let room = max.saturating_sub(line.len());
+        line.extend_from_slice(&buf[..take.min(room)]);
+        reader.consume(take);
+        if done {
+            return Ok(true);
+        }
+    }
+}
+
+/// `number_lines` for a stream: lines before `offset` are skipped without being kept, and
+/// reading stops once the page is full, so the total is only known if the page reaches the end.
+async fn stream_lines<R: AsyncBufRead + Unpin>(
+    mut reader: R,
+    encoding: &'static Encoding,
+    offset: usize,
+    limit: usize,
+) -> std::io::Result<Result<String, String>> {
+    // enough bytes for MAX_LINE_CHARS characters of any width
+    let max_line_bytes = MAX_LINE_CHARS * 4 + 4;
+    let mut line = Vec::new();
+    let mut n = 0;
+    while n + 1 < offset {
+        if !read_line_capped(&mut reader, max_line_bytes, &mut line).await? {
+            return Ok(Err(format!(
+                "offset {} is past the end of the file ({} lines)",
+                offset, n
+            )));
+        }
+        n += 1;
+    }
+    let mut out = String::new();
+    let mut full = false;
+    while n + 1 - offset < limit {
+        if !read_line_capped(&mut reader, max_line_bytes, &mut line).await? {
+            break;
+        }
+        let (decoded, _) = encoding.decode_without_bom_handling(&line);
+        let text = decoded.trim_end_matches('\n');
+        let numbered = numbered_line(n + 1, text.strip_suffix('\r').unwrap_or(text));
+        if !out.is_empty() && out.len() + numbered.len() > MAX_READ_BYTES {
+            full = true;
+            break;
+        }
+        out.push_str(&numbered);
+        n += 1;
+    }
+    if n + 1 == offset && limit > 0 {
+        return Ok(Err(format!(
+            "offset {} is past the end of the file ({} lines)",
+            offset, n
+        )));
+    }
+    let more = full || !reader.fill_buf().await?.is_empty();
+    if more {
+        out.push_str(&format!(
+            "\n[showing lines {}-{}; use offset={} to read more]",
+            offset,
+            n,
+            n + 1
+        ));
+    } else if offset > 1 {
+        out.push_str(&format!("\n[showing lines {}-{} of {}]", offset, n, n));
+    }
+    Ok(Ok(out))
+}
+
+/// One `cat -n` style line, cut at `MAX_LINE_CHARS`.
+fn numbered_line(n: usize, line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((cut, _)) => format!("{:>6}\t{}... [line truncated]\n", n, &line[..cut]),
+        None => format!("{:>6}\t{}\n", n, line),
+    }
+}
+
+/// Render lines `offset..offset + limit` as `cat -n` style output, stopping early at
+/// `MAX_READ_BYTES`. A trailing note says where the page ended and how many lines exist.
+fn number_lines(text: &str, offset: usize, limit: usize) -> Result<String, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let total = lines.len();
+    if total == 0 {
+        return Ok("(empty file)".into());
+    }
+    if offset > total {
+        return Err(format!(
+            "offset {} is past the end of the file ({} lines)",
+            offset, total
+        ));
+    }
+    let mut out = String::new();
+    let mut last = offset - 1;
+    for (i, line) in lines.iter().enumerate().skip(offset - 1).take(limit) {
+        let numbered = numbered_line(i + 1, line.strip_suffix('\r').unwrap_or(line));
+        if !out.is_empty() && out.len() + numbered.len() > MAX_READ_BYTES {
+            break;
+        }
+        out.push_str(&numbered);
+        last = i + 1;
+    }
+    if offset > 1 || last < total {
+        out.push_str(&format!(
+            "\n[showing lines {}-{} of {}",
+            offset, last, total
+        ));
+        if last < total {
+            out.push_str(&format!("; use offset={} to read more", last + 1));
         }
+        out.push(']');
     }
+    Ok(out)
 }
 
 #[async_trait]
@@ -82,7 +347,7 @@ impl Tool for WriteTool {
         "write_file"
     }
     fn description(&self) -> &str {
-        "Write text to a file (create/overwrite, UTF-8)"
+        "Write text to a file (create/overwrite). An existing file keeps its encoding, BOM and line endings; new files are UTF-8"
     }
     fn parameters(&self) -> Value {
         schema_for_path_content()
@@ -97,14 +362,45 @@ impl Tool for WriteTool {
                 error: Some("missing 'path'".into()),
             };
         };
-        let full = resolve_path(&self.workspace, p);
-        if let Some(parent) = full.parent() {
-            let _ = tokio::fs::create_dir_all(parent).await;
+        let full = match self.paths.resolve_write(p) {
+            Ok(p) => p,
+            Err(e) => return path_error(e),
+        };
+        if let Err(e) = self.tracker.check(&full).await {
+            return path_error(e);
+        }
+        // keep the format of the file being replaced
+        let format = match tokio::fs::read(&full).await {
+            Ok(old) => text::decode(&old).map(|(_, f)| f).unwrap_or_default(),
+            Err(_) => TextFormat {
+                line_ending: LineEnding::detect(content),
+                ..TextFormat::default()
+            },
+        };
+        let bytes = match format.encode(content) {
+            Ok(b) => b,
+            Err(e) => return path_error(e),
+        };
+        if let Err(e) = check_write_size(&full, bytes.len(), self.max_write_bytes) {
+            return path_error(e);
         }
-        match tokio::fs::write(&full, content).await {
+        let written = atomic_write(&full, &bytes).await;
+        if written.is_ok() {
+            self.tracker.record(&full, &bytes).await;
+        }
+        match written {
             Ok(_) => ToolResult {
                 success: true,
-                content: format!("wrote {} bytes to {}", content.len(), full.display()),
+                content: if format.is_default() {
+                    format!("wrote {} bytes to {}", bytes.len(), full.display())
+                } else {
+                    format!(
+                        "wrote {} bytes to {} ({})",
+                        bytes.len(),
+                        full.display(),
+                        format.describe()
+                    )
+                },
                 error: None,
             },
             Err(e) => ToolResult {
@@ -122,17 +418,31 @@ impl Tool for EditTool {
         "edit_file"
     }
     fn description(&self) -> &str {
-        "Search and replace text within a file"
+        "Replace text in a file. `old_str` must match exactly once (include surrounding lines to make it unique) unless `replace_all` is set. Pass `edits` to apply several replacements to one file at once; nothing is written if any of them fails. Returns a unified diff"
     }
     fn parameters(&self) -> Value {
         json!({
             "type": "object",
             "properties": {
                 "path": {"type": "string"},
-                "old_str": {"type": "string"},
-                "new_str": {"type": "string"}
+                "old_str": {"type": "string", "description": "Exact text to replace"},
+                "new_str": {"type": "string"},
+                "replace_all": {"type": "boolean", "description": "Replace every occurrence (default false)"},
+                "edits": {
+                    "type": "array",
+                    "description": "Several replacements applied in order, instead of old_str/new_str",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "old_str": {"type": "string"},
+                            "new_str": {"type": "string"},
+                            "replace_all": {"type": "boolean"}
+                        },
+                        "required": ["old_str", "new_str"]
+                    }
+                }
             },
-            "required": ["path", "old_str", "new_str"],
+            "required": ["path"],
         })
     }
     async fn execute(&self, args: Value) -> ToolResult {
@@ -143,34 +453,74 @@ impl Tool for EditTool {
                 error: Some("missing 'path'".into()),
             };
         };
-        let Some(search) = args.get("old_str").and_then(|v| v.as_str()) else {
-            return ToolResult {
-                success: false,
-                content: String::new(),
-                error: Some("missing 'old_str'".into()),
-            };
+        let edits: Vec<Edit> = match args.get("edits") {
+            Some(list) => match serde_json::from_value(list.clone()) {
+                Ok(edits) => edits,
+                Err(e) => return path_error(format!("invalid 'edits': {}", e)),
+            },
+            None => match serde_json::from_value(args.clone()) {
+                Ok(edit) => vec![edit],
+                Err(_) => return path_error("missing 'old_str'/'new_str' (or 'edits')".into()),
+            },
         };
-        let Some(replace) = args.get("new_str").and_then(|v| v.as_str()) else {
-            return ToolResult {
-                success: false,
-                content: String::new(),
-                error: Some("missing 'new_str'".into()),
-            };
+        if edits.is_empty() {
+            return path_error("'edits' is empty".into());
+        }
+        let full = match self.paths.resolve_write(path) {
+            Ok(p) => p,
+            Err(e) => return path_error(e),
         };
-        let full = resolve_path(&self.workspace, path);
-        let Ok(mut content) = tokio::fs::read_to_string(&full).await else {
+        if is_notebook(&full) {
+            return path_error(format!(
+                "{} is a Jupyter notebook; use edit_notebook to change its cells so the JSON stays valid",
+                full.display()
+            ));
+        }
+        if let Err(e) = self.tracker.check(&full).await {
+            return path_error(e);
+        }
+        let Ok(bytes) = tokio::fs::read(&full).await else {
             return ToolResult {
                 success: false,
                 content: String::new(),
                 error: Some(format!("read error: {}", full.display())),
             };
         };
-        let count = content.matches(search).count();
-        content = content.replace(search, replace);
-        match tokio::fs::write(&full, &content).await {
+        let (content, format) = match text::decode(&bytes) {
+            Ok(decoded) => decoded,
+            Err(kind) => {
+                return path_error(format!("{} is a binary file ({})", full.display(), kind));
+            }
+        };
+        let (updated, count) = match apply_edits(&content, &edits) {
+            Ok(r) => r,
+            Err(e) => return path_error(format!("{} in {}", e, full.display())),
+        };
+        let bytes = match format.encode(&updated) {
+            Ok(b) => b,
+            Err(e) => return path_error(e),
+        };
+        if let Err(e) = check_write_size(&full, bytes.len(), self.max_write_bytes) {
+            return path_error(e);
+        }
+        let shown = full
+            .strip_prefix(self.paths.workspace())
+            .unwrap_or(&full)
+            .display()
+            .to_string();
+        let written = atomic_write(&full, &bytes).await;
+        if written.is_ok() {
+            self.tracker.record(&full, &bytes).await;
+        }
+        match written {
             Ok(_) => ToolResult {
                 success: true,
-                content: format!("replaced {} occurrence(s) in {}", count, full.display()),
+                content: format!(
+                    "replaced {} occurrence(s) in {}\n\n{}",
+                    count,
+                    full.display(),
+                    unified_diff(&shown, &content, &updated)
+                ),
                 error: None,
             },
             Err(e) => ToolResult {
@@ -181,3 +531,207 @@ impl Tool for EditTool {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::path::PathBuf;
+
+    /// read_file, write_file and edit_file sharing a tracker, confined to `ws`.
+    fn file_tools(ws: &Path, max_write_bytes: usize) -> (ReadTool, WriteTool, EditTool) {
+        let paths = Arc::new(PathPolicy::new(ws));
+        let tracker = Arc::new(ReadTracker::default());
+        (
+            ReadTool {
+                paths: paths.clone(),
+                tracker: tracker.clone(),
+            },
+            WriteTool {
+                paths: paths.clone(),
+                tracker: tracker.clone(),
+                max_write_bytes,
+            },
+            EditTool {
+                paths,
+                tracker,
+                max_write_bytes,
+            },
+        )
+    }
+
+    /// A workspace holding `run.sh`, executable on Unix.
+    fn workspace_with_script() -> (TempDir, PathBuf) {
+        let ws = TempDir::new("file");
+        let script = ws.join("run.sh");
+        std::fs::write(&script, "#!/bin/sh\necho one\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        (ws, script)
+    }
+
+    #[test]
+    fn test_number_lines_paging() {
+        let text: String = (1..=10).map(|i| format!("line {i}\r\n")).collect();
+        assert_eq!(
+            number_lines(&text, 1, 2).unwrap(),
+            "     1\tline 1\n     2\tline 2\n\n[showing lines 1-2 of 10; use offset=3 to read more]"
+        );
+        assert!(
+            number_lines(&text, 9, 5)
+                .unwrap()
+                .ends_with("\tline 10\n\n[showing lines 9-10 of 10]")
+        );
+        assert!(number_lines(&text, 11, 5).unwrap_err().contains("10 lines"));
+        assert_eq!(number_lines("", 1, 5).unwrap(), "(empty file)");
+    }
+
+    #[tokio::test]
+    async fn test_stream_lines_paging() {
+        let text: String = (1..=10).map(|i| format!("line {i}\r\n")).collect();
+        let page = |offset, limit| {
+            stream_lines(
+                std::io::Cursor::new(text.clone().into_bytes()),
+                encoding_rs::UTF_8,
+                offset,
+                limit,
+            )
+        };
+        assert_eq!(
+            page(1, 2).await.unwrap().unwrap(),
+            "     1\tline 1\n     2\tline 2\n\n[showing lines 1-2; use offset=3 to read more]"
+        );
+        assert!(
+            page(9, 5)
+                .await
+                .unwrap()
+                .unwrap()
+                .ends_with("\tline 10\n\n[showing lines 9-10 of 10]")
+        );
+        assert!(page(11, 5).await.unwrap().unwrap_err().contains("10 lines"));
+        // an endless line is cut, not buffered whole
+        let long = std::io::Cursor::new(vec![b'x'; 1_000_000]);
+        let out = stream_lines(long, encoding_rs::UTF_8, 1, 5)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(out.len() < 3 * MAX_LINE_CHARS);
+        assert!(out.ends_with("... [line truncated]\n"));
+    }
+
+    #[tokio::test]
+    async fn test_read_large_file_in_pages() {
+        let ws = TempDir::new("file");
+        let (read, _, _) = file_tools(&ws, 64);
+        let text: String = (1..=1_000_000).map(|i| format!("entry {i:09}\n")).collect();
+        assert!(text.len() as u64 > MAX_WHOLE_READ_BYTES);
+        std::fs::write(ws.join("big.log"), text).unwrap();
+        let r = read
+            .execute(json!({"path": "big.log", "offset": 500_000, "limit": 2}))
+            .await;
+        assert_eq!(
+            r.content,
+            "500000\tentry 000500000\n500001\tentry 000500001\n\n[showing lines 500000-500001; use offset=500002 to read more]"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_partial_read_counts_as_read_until_the_file_changes() {
+        let ws = TempDir::new("file");
+        let (read, _, _) = file_tools(&ws, 64);
+        let big = ws.join("big.log");
+        std::fs::write(&big, "line\n".repeat(2_000_000)).unwrap();
+        assert!(
+            read.execute(json!({"path": "big.log", "limit": 1}))
+                .await
+                .success
+        );
+        assert!(read.tracker.check(&big).await.is_ok());
+        std::fs::write(&big, "changed\n").unwrap();
+        assert!(read.tracker.check(&big).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_large_binary_file_is_described() {
+        let ws = TempDir::new("file");
+        let (read, _, _) = file_tools(&ws, 64);
+        let mut png = b"\x89PNG\r\n".to_vec();
+        png.resize(MAX_WHOLE_READ_BYTES as usize + 1, 0);
+        std::fs::write(ws.join("big.png"), png).unwrap();
+        let r = read.execute(json!({"path": "big.png"})).await;
+        assert!(r.content.contains("binary file (PNG image"));
+    }
+
+    #[tokio::test]
+    async fn test_write_refuses_unread_file() {
+        let (ws, script) = workspace_with_script();
+        let (read, write, _) = file_tools(&ws, 64);
+        let res = write
+            .execute(json!({"path": "run.sh", "content": "echo clobbered\n"}))
+            .await;
+        assert!(res.error.unwrap().contains("has not been read"));
+        assert_eq!(
+            std::fs::read_to_string(&script).unwrap(),
+            "#!/bin/sh\necho one\n"
+        );
+        assert!(read.execute(json!({"path": "run.sh"})).await.success);
+        let res = write
+            .execute(json!({"path": "run.sh", "content": "echo two\n"}))
+            .await;
+        assert!(res.success, "{:?}", res.error);
+    }
+
+    #[tokio::test]
+    async fn test_edit_keeps_permissions() {
+        let (ws, script) = workspace_with_script();
+        let (read, _, edit) = file_tools(&ws, 64);
+        assert!(read.execute(json!({"path": "run.sh"})).await.success);
+        let res = edit
+            .execute(json!({"path": "run.sh", "old_str": "one", "new_str": "two"}))
+            .await;
+        assert!(res.success, "{:?}", res.error);
+        assert!(res.content.contains("-echo one\n+echo two\n"));
+        assert_eq!(
+            std::fs::read_to_string(&script).unwrap(),
+            "#!/bin/sh\necho two\n"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&script).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_edit_refuses_oversized_result() {
+        let (ws, script) = workspace_with_script();
+        let (read, _, edit) = file_tools(&ws, 64);
+        assert!(read.execute(json!({"path": "run.sh"})).await.success);
+        let res = edit
+            .execute(json!({"path": "run.sh", "old_str": "one", "new_str": "x".repeat(100)}))
+            .await;
+        assert!(res.error.unwrap().contains("max_write_bytes"));
+        assert_eq!(
+            std::fs::read_to_string(&script).unwrap(),
+            "#!/bin/sh\necho one\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_new_files_need_no_read() {
+        let ws = TempDir::new("file");
+        let (_, write, _) = file_tools(&ws, 64);
+        let res = write
+            .execute(json!({"path": "new/notes.txt", "content": "hi\n"}))
+            .await;
+        assert!(res.success, "{:?}", res.error);
+        assert_eq!(
+            std::fs::read_to_string(ws.join("new/notes.txt")).unwrap(),
+            "hi\n"
+        );
+    }
+}
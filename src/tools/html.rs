@@ -0,0 +1,320 @@
+//! HTML to Markdown conversion for `fetch_url`: keeps headings, paragraphs, links, lists,
+//! code and tables, and drops scripts, styles and navigation chrome.
+
+use ego_tree::NodeRef;
+use scraper::{ElementRef, Html, Node, Selector};
+use url::Url;
+
+/// Elements whose content is never useful to the model.
+const SKIPPED: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "canvas", "iframe", "head", "nav", "footer",
+    "form", "button", "select",
+];
+
+/// Nesting depth past which an element's content is kept as plain text; the converter
+/// recurses per element, and pages can nest tags thousands deep.
+const MAX_DEPTH: usize = 128;
+
+/// Convert an HTML document to Markdown, returning its `<title>` too. Only `<main>` (or
+/// `<article>`) is converted when the page has one.
+pub fn html_to_markdown(html: &str, base: Option<&Url>) -> (Option<String>, String) {
+    let doc = Html::parse_document(html);
+    let title = Selector::parse("title")
+        .ok()
+        .and_then(|s| doc.select(&s).next())
+        .map(|t| {
+            collapse_whitespace(&t.text().collect::<String>())
+                .trim()
+                .to_string()
+        })
+        .filter(|t| !t.is_empty());
+    let root = ["main", "article", "body"]
+        .iter()
+        .filter_map(|name| Selector::parse(name).ok())
+        .find_map(|s| doc.select(&s).next());
+    let converter = Converter { base };
+    let markdown = match root {
+        Some(el) => converter.children(*el, 0),
+        None => converter.children(*doc.root_element(), 0),
+    };
+    (title, tidy(&markdown))
+}
+
+struct Converter<'a> {
+    base: Option<&'a Url>,
+}
+
+impl Converter<'_> {
+    fn children(&self, node: NodeRef<'_, Node>, depth: usize) -> String {
+        if depth > MAX_DEPTH {
+            let text: String = node
+                .descendants()
+                .filter_map(|d| d.value().as_text().map(|t| &**t))
+                .collect();
+            return collapse_whitespace(&text);
+        }
+        node.children().map(|c| self.node(c, depth + 1)).collect()
+    }
+
+    fn node(&self, node: NodeRef<'_, Node>, depth: usize) -> String {
+        match node.value() {
+            Node::Text(t) => collapse_whitespace(t),
+            Node::Element(_) => match ElementRef::wrap(node) {
+                Some(el) => self.element(el, depth),
+                None => String::new(),
+            },
+            _ => String::new(),
+        }
+    }
+
+    fn element(&self, el: ElementRef<'_>, depth: usize) -> String {
+        let name = el.value().name();
+        if SKIPPED.contains(&name) || el.value().attr("hidden").is_some() {
+            return String::new();
+        }
+        let inner = || self.children(*el, depth);
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = name[1..].parse::<usize>().unwrap_or(1);
+                let text = one_line(&inner());
+                if text.is_empty() {
+                    return String::new();
+                }
+                format!("\n\n{} {}\n\n", "#".repeat(level), text)
+            }
+            "p" | "div" | "section" | "article" | "main" | "header" | "aside" | "figure"
+            | "figcaption" | "details" | "summary" | "dl" => format!("\n\n{}\n\n", inner().trim()),
+            "dt" => format!("\n**{}**\n", one_line(&inner())),
+            "dd" => format!("\n: {}\n", inner().trim()),
+            "br" => "\n".into(),
+            "hr" => "\n\n---\n\n".into(),
+            "strong" | "b" => wrap_inline(&inner(), "**"),
+            "em" | "i" => wrap_inline(&inner(), "*"),
+            "code" | "kbd" | "samp" => {
+                let text = el.text().collect::<String>();
+                if text.contains('`') {
+                    format!("`` {} ``", text)
+                } else {
+                    format!("`{}`", text)
+                }
+            }
+            "pre" => {
+                let text = el.text().collect::<String>();
+                let lang = el
+                    .children()
+                    .filter_map(ElementRef::wrap)
+                    .find(|c| c.value().name() == "code")
+                    .and_then(|c| {
+                        c.value()
+                            .classes()
+                            .find_map(|cl| cl.strip_prefix("language-"))
+                    })
+                    .unwrap_or("");
+                format!("\n\n```{}\n{}\n```\n\n", lang, text.trim_end_matches('\n'))
+            }
+            "a" => {
+                let text = one_line(&inner());
+                match el.value().attr("href").and_then(|h| self.resolve(h)) {
+                    Some(href) if !text.is_empty() => format!("[{}]({})", text, href),
+                    _ => text,
+                }
+            }
+            "img" => {
+                let alt = el.value().attr("alt").unwrap_or("").trim();
+                match el.value().attr("src").and_then(|s| self.resolve(s)) {
+                    Some(src) if !alt.is_empty() => format!("![{}]({})", alt, src),
+                    _ => String::new(),
+                }
+            }
+            "ul" | "ol" => self.list(el, name == "ol", depth),
+            "blockquote" => {
+                let body = tidy(&inner());
+                let quoted: Vec<String> = body.lines().map(|l| format!("> {}", l)).collect();
+                format!("\n\n{}\n\n", quoted.join("\n"))
+            }
+            "table" => self.table(el, depth),
+            _ => inner(),
+        }
+    }
+
+    fn list(&self, el: ElementRef<'_>, ordered: bool, depth: usize) -> String {
+        let mut out = String::from("\n\n");
+        let items = el
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|c| c.value().name() == "li");
+        for (i, li) in items.enumerate() {
+            let marker = if ordered {
+                format!("{}. ", i + 1)
+            } else {
+                "- ".to_string()
+            };
+            let body = tidy(&self.children(*li, depth + 1));
+            let indent = " ".repeat(marker.len());
+            for (n, line) in body.lines().filter(|l| !l.trim().is_empty()).enumerate() {
+                if n == 0 {
+                    out.push_str(&marker);
+                } else {
+                    out.push_str(&indent);
+                }
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        out.push('\n');
+        out
+    }
+
+    fn table(&self, el: ElementRef<'_>, depth: usize) -> String {
+        let Ok(row_sel) = Selector::parse("tr") else {
+            return String::new();
+        };
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        for tr in el.select(&row_sel) {
+            let cells: Vec<String> = tr
+                .children()
+                .filter_map(ElementRef::wrap)
+                .filter(|c| matches!(c.value().name(), "td" | "th"))
+                .map(|c| one_line(&self.children(*c, depth + 2)).replace('|', "\\|"))
+                .collect();
+            if !cells.is_empty() {
+                rows.push(cells);
+            }
+        }
+        if rows.is_empty() {
+            return String::new();
+        }
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut out = String::from("\n\n");
+        for (i, row) in rows.iter().enumerate() {
+            let mut cells = row.clone();
+            cells.resize(width, String::new());
+            out.push_str(&format!("| {} |\n", cells.join(" | ")));
+            if i == 0 {
+                out.push_str(&format!("|{}\n", " --- |".repeat(width)));
+            }
+        }
+        out.push('\n');
+        out
+    }
+
+    fn resolve(&self, href: &str) -> Option<String> {
+        let href = href.trim();
+        if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") {
+            return None;
+        }
+        match self.base {
+            Some(base) => base.join(href).ok().map(String::from),
+            None => Some(href.to_string()),
+        }
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !space {
+                out.push(' ');
+            }
+            space = true;
+        } else {
+            out.push(c);
+            space = false;
+        }
+    }
+    out
+}
+
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn wrap_inline(text: &str, mark: &str) -> String {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return text.to_string();
+    }
+    // keep surrounding spaces outside the markers
+    let lead = if text.starts_with(' ') { " " } else { "" };
+    let trail = if text.ends_with(' ') { " " } else { "" };
+    format!("{}{}{}{}{}", lead, mark, trimmed, mark, trail)
+}
+
+/// Trim line ends, drop indentation left over from the HTML source, and squeeze blank
+/// lines, leaving code blocks alone.
+fn tidy(markdown: &str) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut in_fence = false;
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            out.push(line.trim().to_string());
+            continue;
+        }
+        if in_fence {
+            out.push(line.to_string());
+            continue;
+        }
+        // nested list lines are indented on purpose; a single leading space is HTML noise
+        let line = line.trim_end();
+        let kept = if line.starts_with("  ") {
+            line
+        } else {
+            line.trim_start()
+        };
+        if kept.trim().is_empty() && out.last().is_some_and(|l| l.trim().is_empty()) {
+            continue;
+        }
+        out.push(kept.to_string());
+    }
+    out.join("\n").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_markdown() {
+        let html = r#"<html><head><title> Docs  Page </title><style>p{}</style></head>
+<body><nav><a href="/">Home</a></nav>
+<main>
+  <h1>Getting <em>started</em></h1>
+  <p>Install the <a href="/install#cli">CLI</a> and run <code>tool init</code>.</p>
+  <ul><li>fast</li><li>small<ul><li>nested</li></ul></li></ul>
+  <pre><code class="language-sh">cargo install tool
+tool --help</code></pre>
+  <table><tr><th>Flag</th><th>Meaning</th></tr><tr><td>-v</td><td>verbose</td></tr></table>
+  <script>alert(1)</script>
+</main></body></html>"#;
+        let base = Url::parse("https://example.com/docs/").unwrap();
+        let (title, md) = html_to_markdown(html, Some(&base));
+        assert_eq!(title.as_deref(), Some("Docs Page"));
+        assert_eq!(
+            md,
+            "# Getting *started*\n\nInstall the [CLI](https://example.com/install#cli) and run `tool init`.\n\n- fast\n- small\n  - nested\n\n```sh\ncargo install tool\ntool --help\n```\n\n| Flag | Meaning |\n| --- | --- |\n| -v | verbose |"
+        );
+    }
+
+    #[test]
+    fn test_deeply_nested_html() {
+        // run on a 2 MiB stack, the size of a tokio worker thread's
+        let md = std::thread::Builder::new()
+            .stack_size(2 * 1024 * 1024)
+            .spawn(|| {
+                let depth = 5000;
+                let html = format!(
+                    "<body>{}deep text{}</body>",
+                    "<div>".repeat(depth),
+                    "</div>".repeat(depth)
+                );
+                html_to_markdown(&html, None).1
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(md, "deep text");
+    }
+}
@@ -1,4 +1,5 @@
 use crate::tools::base::{Tool, ToolResult};
+use crate::tools::env::EnvPolicy;
 use async_trait::async_trait;
 use once_cell::sync::OnceCell;
 use rmcp::service::ServiceExt;
@@ -92,7 +93,12 @@ impl Tool for McpTool {
     }
 }
 
-pub async fn load_mcp_tools(config_path: &Path) -> anyhow::Result<Vec<Arc<dyn Tool>>> {
+/// Start the enabled servers in `config_path` (with `env` applied; a server's own `env`
+/// entries are added afterwards) and collect their tools.
+pub async fn load_mcp_tools(
+    config_path: &Path,
+    env: &EnvPolicy,
+) -> anyhow::Result<Vec<Arc<dyn Tool>>> {
     if !config_path.exists() {
         return Ok(Vec::new());
     }
@@ -109,6 +115,7 @@ pub async fn load_mcp_tools(config_path: &Path) -> anyhow::Result<Vec<Arc<dyn To
         for a in &server.args {
             cmd.arg(a);
         }
+        env.apply(&mut cmd);
         for (k, v) in &server.env {
             cmd.env(k, v);
         }
@@ -143,9 +150,9 @@ pub async fn load_mcp_tools(config_path: &Path) -> anyhow::Result<Vec<Arc<dyn To
 }
 
 // Global registry to cleanup MCP connections
-static REGISTRY: OnceCell<
-    Mutex<Vec<Arc<rmcp::service::RunningService<rmcp::service::RoleClient, ()>>>>,
-> = OnceCell::new();
+type McpService = rmcp::service::RunningService<rmcp::service::RoleClient, ()>;
+
+static REGISTRY: OnceCell<Mutex<Vec<Arc<McpService>>>> = OnceCell::new();
 
 pub async fn cleanup_mcp() {
     if let Some(reg) = REGISTRY.get() {
@@ -1,8 +1,26 @@
+pub mod atomic;
+pub mod background;
 pub mod base;
 pub mod bash;
+pub mod command_rules;
+pub mod edit;
+pub mod env;
+pub mod fetch;
 pub mod file;
+pub mod html;
 pub mod mcp;
 pub mod note;
+pub mod note_store;
+pub mod notebook;
+pub mod output;
+pub mod patch;
+pub mod paths;
+pub mod read_tracker;
+pub mod sandbox;
+pub mod search;
+pub mod shell;
 pub mod skills;
+pub mod text;
+pub mod tree;
 
-pub use base::{Tool, ToolResult};
+pub use base::{OutputSink, Tool, ToolResult};
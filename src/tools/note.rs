@@ -1,49 +1,179 @@
+use crate::token::TokenEstimator;
 use crate::tools::base::{Tool, ToolResult};
+use crate::tools::note_store::{
+    Note, NoteScope, NoteStores, NoteUpdate, recall_score, search, terms,
+};
 use async_trait::async_trait;
 use serde_json::{Value, json};
-use std::path::PathBuf;
+
+/// Notes listed by `recall_notes` when no `limit` is given.
+const DEFAULT_RECALL_LIMIT: usize = 20;
+/// Results returned by `search_notes` when no `limit` is given.
+const DEFAULT_SEARCH_LIMIT: usize = 10;
 
 pub struct RecordNoteTool {
-    pub memory_file: PathBuf,
+    pub stores: NoteStores,
 }
 pub struct RecallNotesTool {
-    pub memory_file: PathBuf,
+    pub stores: NoteStores,
+}
+pub struct SearchNotesTool {
+    pub stores: NoteStores,
+}
+pub struct UpdateNoteTool {
+    pub stores: NoteStores,
+}
+pub struct DeleteNoteTool {
+    pub stores: NoteStores,
 }
 
-fn load_notes(path: &PathBuf) -> Vec<serde_json::Value> {
-    if !path.exists() {
-        return vec![];
+fn failure(e: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        content: String::new(),
+        error: Some(e),
     }
-    std::fs::read_to_string(path)
-        .ok()
-        .and_then(|s| serde_json::from_str(&s).ok())
-        .unwrap_or_else(|| vec![])
 }
 
-fn save_notes(path: &PathBuf, notes: &[serde_json::Value]) -> std::io::Result<()> {
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent)?;
+fn tags_arg(args: &Value) -> Option<Vec<String>> {
+    args.get("tags").and_then(|v| v.as_array()).map(|tags| {
+        tags.iter()
+            .filter_map(|t| t.as_str().map(str::to_string))
+            .collect()
+    })
+}
+
+fn id_arg(args: &Value) -> Result<u64, String> {
+    args.get("id")
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| "missing 'id' (the number shown as #id)".into())
+}
+
+/// The optional `scope` argument.
+fn scope_arg(args: &Value) -> Result<Option<NoteScope>, String> {
+    args.get("scope")
+        .and_then(|v| v.as_str())
+        .map(NoteScope::parse)
+        .transpose()
+}
+
+fn scope_schema() -> Value {
+    json!({
+        "type": "string",
+        "enum": ["workspace", "global"],
+        "description": "workspace: notes about this project; global: notes for every project, such as user preferences"
+    })
+}
+
+/// `#3 [category] content (tags: a, b; updated 2025-01-02 03:04)`, prefixed with `global` for
+/// global notes; continuation lines are indented.
+pub fn format_note(scope: NoteScope, note: &Note) -> String {
+    let mut meta = Vec::new();
+    if !note.tags.is_empty() {
+        meta.push(format!("tags: {}", note.tags.join(", ")));
     }
-    std::fs::write(
-        path,
-        serde_json::to_string_pretty(notes).unwrap_or_default(),
+    meta.push(format!(
+        "updated {}",
+        note.updated_at.format("%Y-%m-%d %H:%M")
+    ));
+    format!(
+        "{}#{} [{}] {} ({})",
+        if scope == NoteScope::Global {
+            "global "
+        } else {
+            ""
+        },
+        note.id,
+        note.category,
+        note.content.replace('\n', "\n   "),
+        meta.join("; ")
     )
 }
 
+/// Notes whose category and tag match the optional `category`/`tag` arguments.
+fn filter_notes(notes: Vec<(NoteScope, Note)>, args: &Value) -> Vec<(NoteScope, Note)> {
+    let category = args.get("category").and_then(|v| v.as_str());
+    let tag = args
+        .get("tag")
+        .and_then(|v| v.as_str())
+        .map(|t| t.trim().to_lowercase());
+    notes
+        .into_iter()
+        .filter(|(_, n)| category.is_none_or(|c| n.category == c))
+        .filter(|(_, n)| tag.as_ref().is_none_or(|t| n.tags.contains(t)))
+        .collect()
+}
+
+/// The "## Memory" system prompt section: notes ranked by keyword overlap with `query` and
+/// recency, added until `budget_tokens` is used up. `None` when no note fits.
+pub fn memory_prompt(
+    notes: &[(NoteScope, Note)],
+    query: Option<&str>,
+    budget_tokens: usize,
+    estimator: &dyn TokenEstimator,
+) -> Option<String> {
+    let query_terms = query.map(terms).unwrap_or_default();
+    let now = chrono::Local::now();
+    let mut ranked: Vec<(f64, &(NoteScope, Note))> = notes
+        .iter()
+        .map(|entry| (recall_score(&entry.1, &query_terms, now), entry))
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut section = String::from(
+        "\n\n## Memory\nNotes recorded in earlier sessions, most relevant first (`global` ones apply to every project). They may be outdated: check before relying on them, and fix or remove wrong ones with update_note/delete_note.\n",
+    );
+    let more = |n: usize| format!("- ({} more notes; use search_notes to find them)\n", n);
+    let lines: Vec<(String, usize)> = ranked
+        .iter()
+        .map(|(_, (scope, note))| {
+            let line = format!("- {}\n", format_note(*scope, note));
+            let tokens = estimator.count_text(&line);
+            (line, tokens)
+        })
+        .collect();
+    let mut used = estimator.count_text(&section);
+    // unless every note fits, keep room for the line counting the ones left out
+    let reserve = if used + lines.iter().map(|(_, t)| t).sum::<usize>() <= budget_tokens {
+        0
+    } else {
+        estimator.count_text(&more(ranked.len()))
+    };
+    let mut shown = 0;
+    for (line, tokens) in &lines {
+        // a long note that doesn't fit may leave room for shorter, less relevant ones
+        if used + tokens + reserve > budget_tokens {
+            continue;
+        }
+        used += tokens;
+        section.push_str(line);
+        shown += 1;
+    }
+    if shown == 0 {
+        return None;
+    }
+    if shown < ranked.len() {
+        section.push_str(&more(ranked.len() - shown));
+    }
+    Some(section)
+}
+
 #[async_trait]
 impl Tool for RecordNoteTool {
     fn name(&self) -> &str {
         "record_note"
     }
     fn description(&self) -> &str {
-        "Record important information as session notes for future reference (timestamped)."
+        "Record important information (decisions, conventions, gotchas, user preferences) as a note for later sessions. Use scope=global for things that apply to every project. Returns the note id for update_note/delete_note"
     }
     fn parameters(&self) -> Value {
         json!({
             "type": "object",
             "properties": {
                 "content": {"type": "string", "description": "Note content"},
-                "category": {"type": "string", "description": "Optional category"}
+                "category": {"type": "string", "description": "Optional category (default: general)"},
+                "tags": {"type": "array", "items": {"type": "string"}, "description": "Optional keywords to find the note by"},
+                "scope": scope_schema()
             },
             "required": ["content"],
         })
@@ -51,33 +181,29 @@ impl Tool for RecordNoteTool {
 
     async fn execute(&self, args: Value) -> ToolResult {
         let Some(content) = args.get("content").and_then(|v| v.as_str()) else {
-            return ToolResult {
-                success: false,
-                content: String::new(),
-                error: Some("missing 'content'".into()),
-            };
+            return failure("missing 'content'".into());
         };
         let category = args
             .get("category")
             .and_then(|v| v.as_str())
+            .filter(|c| !c.trim().is_empty())
             .unwrap_or("general");
-        let mut notes = load_notes(&self.memory_file);
-        notes.push(json!({
-            "timestamp": chrono::Local::now().to_rfc3339(),
-            "category": category,
-            "content": content,
-        }));
-        match save_notes(&self.memory_file, &notes) {
-            Ok(_) => ToolResult {
+        let tags = tags_arg(&args).unwrap_or_default();
+        let scope = match scope_arg(&args) {
+            Ok(s) => s.unwrap_or(NoteScope::Workspace),
+            Err(e) => return failure(e),
+        };
+        let store = match self.stores.get(scope) {
+            Ok(s) => s,
+            Err(e) => return failure(e),
+        };
+        match store.add(content, category, &tags).await {
+            Ok(note) => ToolResult {
                 success: true,
-                content: format!("Recorded note: {} (category: {})", content, category),
+                content: format!("Recorded note {}", format_note(scope, &note)),
                 error: None,
             },
-            Err(e) => ToolResult {
-                success: false,
-                content: String::new(),
-                error: Some(format!("Failed to record note: {}", e)),
-            },
+            Err(e) => failure(format!("Failed to record note: {}", e)),
         }
     }
 }
@@ -88,19 +214,29 @@ impl Tool for RecallNotesTool {
         "recall_notes"
     }
     fn description(&self) -> &str {
-        "Recall all previously recorded session notes (optionally filter by category)."
+        "List the most recently updated notes from the workspace and global memory (optionally only one scope, category or tag). Use search_notes to find notes about a topic"
     }
     fn parameters(&self) -> Value {
         json!({
             "type": "object",
             "properties": {
-                "category": {"type": "string", "description": "Optional category filter"}
+                "scope": scope_schema(),
+                "category": {"type": "string", "description": "Optional category filter"},
+                "tag": {"type": "string", "description": "Optional tag filter"},
+                "limit": {"type": "integer", "description": "Maximum number of notes (default 20)"}
             }
         })
     }
 
     async fn execute(&self, args: Value) -> ToolResult {
-        let notes = load_notes(&self.memory_file);
+        let scope = match scope_arg(&args) {
+            Ok(s) => s,
+            Err(e) => return failure(e),
+        };
+        let notes = match self.stores.load(scope).await {
+            Ok(n) => n,
+            Err(e) => return failure(e),
+        };
         if notes.is_empty() {
             return ToolResult {
                 success: true,
@@ -108,43 +244,28 @@ impl Tool for RecallNotesTool {
                 error: None,
             };
         }
-        let filter = args.get("category").and_then(|v| v.as_str());
-        let filtered: Vec<_> = notes
-            .into_iter()
-            .filter(|n| match filter {
-                Some(cat) => n.get("category").and_then(|v| v.as_str()) == Some(cat),
-                None => true,
-            })
-            .collect();
+        let mut filtered = filter_notes(notes, &args);
         if filtered.is_empty() {
             return ToolResult {
                 success: true,
-                content: format!(
-                    "No notes found{}",
-                    filter
-                        .map(|c| format!(" in category: {}", c))
-                        .unwrap_or_default()
-                ),
+                content: "No notes match the given category/tag.".into(),
                 error: None,
             };
         }
-        let mut out = String::from("Recorded Notes:\n");
-        for (idx, n) in filtered.iter().enumerate() {
-            let ts = n
-                .get("timestamp")
-                .and_then(|v| v.as_str())
-                .unwrap_or("unknown time");
-            let cat = n
-                .get("category")
-                .and_then(|v| v.as_str())
-                .unwrap_or("general");
-            let ct = n.get("content").and_then(|v| v.as_str()).unwrap_or("");
+        filtered.sort_by_key(|(_, n)| std::cmp::Reverse(n.updated_at));
+        let limit = args
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .map_or(DEFAULT_RECALL_LIMIT, |n| n.max(1) as usize);
+        let mut out = String::from("Recorded notes (most recent first):\n");
+        for (scope, note) in filtered.iter().take(limit) {
+            out.push_str(&format_note(*scope, note));
+            out.push('\n');
+        }
+        if filtered.len() > limit {
             out.push_str(&format!(
-                "{}. [{}] {}\n   (recorded at {})\n",
-                idx + 1,
-                cat,
-                ct,
-                ts
+                "[{} older notes not shown; use search_notes or a higher limit]\n",
+                filtered.len() - limit
             ));
         }
         ToolResult {
@@ -154,3 +275,230 @@ impl Tool for RecallNotesTool {
         }
     }
 }
+
+#[async_trait]
+impl Tool for SearchNotesTool {
+    fn name(&self) -> &str {
+        "search_notes"
+    }
+    fn description(&self) -> &str {
+        "Search recorded notes (workspace and global) by keywords. Matches in content, tags and category are ranked, best first"
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {"type": "string", "description": "Keywords to look for"},
+                "scope": scope_schema(),
+                "category": {"type": "string", "description": "Optional category filter"},
+                "tag": {"type": "string", "description": "Optional tag filter"},
+                "limit": {"type": "integer", "description": "Maximum number of results (default 10)"}
+            },
+            "required": ["query"],
+        })
+    }
+
+    async fn execute(&self, args: Value) -> ToolResult {
+        let Some(query) = args.get("query").and_then(|v| v.as_str()) else {
+            return failure("missing 'query'".into());
+        };
+        let scope = match scope_arg(&args) {
+            Ok(s) => s,
+            Err(e) => return failure(e),
+        };
+        let notes = match self.stores.load(scope).await {
+            Ok(n) => n,
+            Err(e) => return failure(e),
+        };
+        let candidates = filter_notes(notes, &args);
+        let limit = args
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .map_or(DEFAULT_SEARCH_LIMIT, |n| n.max(1) as usize);
+        let hits = search(&candidates, query, limit);
+        let content = if hits.is_empty() {
+            format!("No notes match '{}'.", query)
+        } else {
+            let mut out = format!("{} matching notes (best first):\n", hits.len());
+            for (scope, note) in hits {
+                out.push_str(&format_note(*scope, note));
+                out.push('\n');
+            }
+            out
+        };
+        ToolResult {
+            success: true,
+            content,
+            error: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for UpdateNoteTool {
+    fn name(&self) -> &str {
+        "update_note"
+    }
+    fn description(&self) -> &str {
+        "Change a recorded note by id: its content, category and/or tags (tags replace the old ones). Pass scope=global for notes shown as `global #id`. Use it to correct outdated notes instead of recording duplicates"
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": {"type": "integer", "description": "Note id"},
+                "scope": scope_schema(),
+                "content": {"type": "string"},
+                "category": {"type": "string"},
+                "tags": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["id"],
+        })
+    }
+
+    async fn execute(&self, args: Value) -> ToolResult {
+        let id = match id_arg(&args) {
+            Ok(id) => id,
+            Err(e) => return failure(e),
+        };
+        let scope = match scope_arg(&args) {
+            Ok(s) => s.unwrap_or(NoteScope::Workspace),
+            Err(e) => return failure(e),
+        };
+        let update = NoteUpdate {
+            content: args
+                .get("content")
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+            category: args
+                .get("category")
+                .and_then(|v| v.as_str())
+                .filter(|c| !c.trim().is_empty())
+                .map(str::to_string),
+            tags: tags_arg(&args),
+        };
+        if update.content.is_none() && update.category.is_none() && update.tags.is_none() {
+            return failure("nothing to update: pass content, category and/or tags".into());
+        }
+        let result = match self.stores.get(scope) {
+            Ok(store) => store.update(id, update).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(note) => ToolResult {
+                success: true,
+                content: format!("Updated note {}", format_note(scope, &note)),
+                error: None,
+            },
+            Err(e) => failure(e),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for DeleteNoteTool {
+    fn name(&self) -> &str {
+        "delete_note"
+    }
+    fn description(&self) -> &str {
+        "Delete a recorded note by id, e.g. when it is wrong or no longer relevant. Pass scope=global for notes shown as `global #id`"
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": {"type": "integer", "description": "Note id"},
+                "scope": scope_schema()
+            },
+            "required": ["id"],
+        })
+    }
+
+    async fn execute(&self, args: Value) -> ToolResult {
+        let id = match id_arg(&args) {
+            Ok(id) => id,
+            Err(e) => return failure(e),
+        };
+        let scope = match scope_arg(&args) {
+            Ok(s) => s.unwrap_or(NoteScope::Workspace),
+            Err(e) => return failure(e),
+        };
+        let result = match self.stores.get(scope) {
+            Ok(store) => store.delete(id).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(note) => ToolResult {
+                success: true,
+                content: format!("Deleted note {}", format_note(scope, &note)),
+                error: None,
+            },
+            Err(e) => failure(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::ApproxEstimator;
+    use chrono::{Duration, Local};
+
+    #[test]
+    fn test_memory_prompt_ranking_and_budget() {
+        let note = |id: u64, content: &str, days_old: i64| {
+            let at = Local::now() - Duration::days(days_old);
+            Note {
+                id,
+                content: content.into(),
+                category: "general".into(),
+                tags: Vec::new(),
+                created_at: at,
+                updated_at: at,
+            }
+        };
+        let notes = vec![
+            (
+                NoteScope::Workspace,
+                note(1, "Tests need DATABASE_URL set", 90),
+            ),
+            (
+                NoteScope::Workspace,
+                note(2, "Release checklist: RELEASE.md", 1),
+            ),
+            (
+                NoteScope::Global,
+                note(1, "User prefers short commit messages", 0),
+            ),
+        ];
+        let est = ApproxEstimator;
+        let prompt = memory_prompt(&notes, Some("why do the tests fail?"), 1000, &est).unwrap();
+        let lines: Vec<&str> = prompt.lines().filter(|l| l.starts_with("- ")).collect();
+        assert!(lines[0].starts_with("- #1 [general] Tests need DATABASE_URL"));
+        assert!(lines[1].starts_with("- global #1 [general] User prefers"));
+        assert!(lines[2].starts_with("- #2 "));
+
+        // a tight budget keeps the best note and points at search_notes for the rest
+        let header = est.count_text(prompt.split("- #1").next().unwrap());
+        let first = est.count_text(&format!("{}\n", lines[0]));
+        let more = est.count_text("- (3 more notes; use search_notes to find them)\n");
+        let budget = header + first + more;
+        let prompt = memory_prompt(&notes, Some("tests"), budget, &est).unwrap();
+        assert!(prompt.contains("Tests need DATABASE_URL"));
+        assert!(prompt.contains("(2 more notes; use search_notes"));
+        assert!(est.count_text(&prompt) <= budget);
+        assert!(memory_prompt(&notes, Some("tests"), budget - 1, &est).is_none());
+
+        // a note too long for the budget is skipped, not the end of the list
+        let mut notes = notes;
+        notes[0].1.content = "Tests need DATABASE_URL set ".repeat(50);
+        let budget = header + 2 * first + more;
+        let prompt = memory_prompt(&notes, Some("tests"), budget, &est).unwrap();
+        assert!(!prompt.contains("Tests need DATABASE_URL"));
+        assert!(prompt.contains("User prefers short commit messages"));
+        assert!(prompt.contains("more notes; use search_notes"));
+        assert!(est.count_text(&prompt) <= budget);
+        assert!(memory_prompt(&notes, None, 10, &est).is_none());
+        assert!(memory_prompt(&[], None, 1000, &est).is_none());
+    }
+}
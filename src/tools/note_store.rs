@@ -0,0 +1,657 @@
+//! Typed note storage behind the note tools: notes with ids, tags and timestamps in a JSON
+//! file, updated under a lock file so concurrent agent processes don't lose each other's notes.
+
+use crate::tools::atomic::atomic_write;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::TryLockError;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long a write waits for another process holding the store lock.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    pub id: u64,
+    pub content: String,
+    pub category: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+}
+
+/// Fields to change in `NoteStore::update`; `None` keeps the current value.
+#[derive(Debug, Clone, Default)]
+pub struct NoteUpdate {
+    pub content: Option<String>,
+    pub category: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct NoteFile {
+    next_id: u64,
+    notes: Vec<Note>,
+}
+
+/// The untyped array written by earlier versions.
+#[derive(Deserialize)]
+struct LegacyNote {
+    content: String,
+    category: Option<String>,
+    timestamp: Option<DateTime<Local>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredNotes {
+    Current(NoteFile),
+    Legacy(Vec<LegacyNote>),
+}
+
+impl From<Vec<LegacyNote>> for NoteFile {
+    fn from(legacy: Vec<LegacyNote>) -> Self {
+        let now = Local::now();
+        let notes: Vec<Note> = legacy
+            .into_iter()
+            .enumerate()
+            .map(|(i, n)| Note {
+                id: i as u64 + 1,
+                content: n.content,
+                category: n.category.unwrap_or_else(|| "general".into()),
+                tags: Vec::new(),
+                created_at: n.timestamp.unwrap_or(now),
+                updated_at: n.timestamp.unwrap_or(now),
+            })
+            .collect();
+        NoteFile {
+            next_id: notes.len() as u64 + 1,
+            notes,
+        }
+    }
+}
+
+/// Trim, lowercase and deduplicate tags, dropping empty ones.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if !tag.is_empty() && !out.contains(&tag) {
+            out.push(tag);
+        }
+    }
+    out
+}
+
+/// Held while a process reads, changes and rewrites the store.
+struct StoreLock(std::fs::File);
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        let _ = self.0.unlock();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NoteStore {
+    path: PathBuf,
+}
+
+impl NoteStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    async fn read(&self) -> Result<NoteFile, String> {
+        let text = match tokio::fs::read_to_string(&self.path).await {
+            Ok(t) => t,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("failed to read {}: {}", self.path.display(), e)),
+        };
+        let mut file = if text.trim().is_empty() {
+            NoteFile::default()
+        } else {
+            // refuse rather than start over, which would drop every existing note on the next write
+            let stored: StoredNotes = serde_json::from_str(&text).map_err(|e| {
+                format!(
+                    "{} is not a valid note store ({}); fix or move it",
+                    self.path.display(),
+                    e
+                )
+            })?;
+            match stored {
+                StoredNotes::Current(f) => f,
+                StoredNotes::Legacy(notes) => notes.into(),
+            }
+        };
+        // ids start at 1
+        let max_id = file.notes.iter().map(|n| n.id).max().unwrap_or(0);
+        file.next_id = file.next_id.max(max_id + 1);
+        Ok(file)
+    }
+
+    /// Lock the store against other processes; the lock is a `.lock` file next to it since the
+    /// store itself is replaced on every write.
+    async fn lock(&self) -> Result<StoreLock, String> {
+        let mut lock_path = self.path.clone().into_os_string();
+        lock_path.push(".lock");
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("failed to create {}: {}", parent.display(), e))?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|e| {
+                format!(
+                    "failed to open {}: {}",
+                    PathBuf::from(&lock_path).display(),
+                    e
+                )
+            })?;
+        let deadline = Instant::now() + LOCK_TIMEOUT;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(StoreLock(file)),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    tokio::time::sleep(Duration::from_millis(25)).await;
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(format!(
+                        "{} is locked by another process",
+                        self.path.display()
+                    ));
+                }
+                Err(TryLockError::Error(e)) => {
+                    return Err(format!("failed to lock {}: {}", self.path.display(), e));
+                }
+            }
+        }
+    }
+
+    /// Read, change and rewrite the store while holding the lock.
+    async fn modify<T>(
+        &self,
+        change: impl FnOnce(&mut NoteFile) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let _lock = self.lock().await?;
+        let mut file = self.read().await?;
+        let result = change(&mut file)?;
+        let json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+        atomic_write(&self.path, json.as_bytes())
+            .await
+            .map_err(|e| format!("failed to write {}: {}", self.path.display(), e))?;
+        Ok(result)
+    }
+
+    /// All notes, oldest first. A missing file is an empty store.
+    pub async fn load(&self) -> Result<Vec<Note>, String> {
+        Ok(self.read().await?.notes)
+    }
+
+    pub async fn add(
+        &self,
+        content: &str,
+        category: &str,
+        tags: &[String],
+    ) -> Result<Note, String> {
+        let content = content.trim();
+        if content.is_empty() {
+            return Err("note content is empty".into());
+        }
+        self.modify(|file| {
+            let now = Local::now();
+            let note = Note {
+                id: file.next_id,
+                content: content.to_string(),
+                category: category.trim().to_string(),
+                tags: normalize_tags(tags),
+                created_at: now,
+                updated_at: now,
+            };
+            file.next_id += 1;
+            file.notes.push(note.clone());
+            Ok(note)
+        })
+        .await
+    }
+
+    pub async fn update(&self, id: u64, update: NoteUpdate) -> Result<Note, String> {
+        self.modify(|file| {
+            let note = file
+                .notes
+                .iter_mut()
+                .find(|n| n.id == id)
+                .ok_or_else(|| format!("no note with id {}", id))?;
+            if let Some(content) = update.content {
+                if content.trim().is_empty() {
+                    return Err("note content is empty".into());
+                }
+                note.content = content.trim().to_string();
+            }
+            if let Some(category) = update.category {
+                note.category = category.trim().to_string();
+            }
+            if let Some(tags) = update.tags {
+                note.tags = normalize_tags(&tags);
+            }
+            note.updated_at = Local::now();
+            Ok(note.clone())
+        })
+        .await
+    }
+
+    /// Add `notes` under fresh ids, skipping those whose content and category are already
+    /// stored. Returns how many were added.
+    pub async fn import(&self, notes: Vec<Note>) -> Result<usize, String> {
+        self.modify(|file| {
+            let mut added = 0;
+            for mut note in notes {
+                note.content = note.content.trim().to_string();
+                if note.content.is_empty()
+                    || file
+                        .notes
+                        .iter()
+                        .any(|n| n.content == note.content && n.category == note.category)
+                {
+                    continue;
+                }
+                note.id = file.next_id;
+                note.tags = normalize_tags(&note.tags);
+                file.next_id += 1;
+                file.notes.push(note);
+                added += 1;
+            }
+            Ok(added)
+        })
+        .await
+    }
+
+    pub async fn delete(&self, id: u64) -> Result<Note, String> {
+        self.modify(|file| {
+            let index = file
+                .notes
+                .iter()
+                .position(|n| n.id == id)
+                .ok_or_else(|| format!("no note with id {}", id))?;
+            Ok(file.notes.remove(index))
+        })
+        .await
+    }
+}
+
+/// Where a note lives: the workspace's `.agent_memory.json` or the user-global file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteScope {
+    Workspace,
+    Global,
+}
+
+impl NoteScope {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "workspace" | "project" => Ok(Self::Workspace),
+            "global" | "user" => Ok(Self::Global),
+            other => Err(format!(
+                "unknown scope '{}': expected workspace or global",
+                other
+            )),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Workspace => "workspace",
+            Self::Global => "global",
+        }
+    }
+}
+
+/// The workspace store plus the global one (unless disabled by `tools.notes.global_path`).
+#[derive(Debug, Clone)]
+pub struct NoteStores {
+    pub workspace: NoteStore,
+    pub global: Option<NoteStore>,
+}
+
+impl NoteStores {
+    pub fn get(&self, scope: NoteScope) -> Result<&NoteStore, String> {
+        match scope {
+            NoteScope::Workspace => Ok(&self.workspace),
+            NoteScope::Global => self
+                .global
+                .as_ref()
+                .ok_or_else(|| "global notes are disabled (tools.notes.global_path)".into()),
+        }
+    }
+
+    /// Notes of `scope`, or of both scopes (workspace first) when `None`.
+    pub async fn load(&self, scope: Option<NoteScope>) -> Result<Vec<(NoteScope, Note)>, String> {
+        let mut out = Vec::new();
+        for s in [NoteScope::Workspace, NoteScope::Global] {
+            if scope.is_some_and(|wanted| wanted != s) {
+                continue;
+            }
+            let Ok(store) = self.get(s) else {
+                continue;
+            };
+            out.extend(store.load().await?.into_iter().map(|n| (s, n)));
+        }
+        Ok(out)
+    }
+}
+
+/// Notes from the JSON of a note store, either format.
+pub fn parse_notes(json: &str) -> Result<Vec<Note>, String> {
+    let stored: StoredNotes =
+        serde_json::from_str(json).map_err(|e| format!("not a note store: {}", e))?;
+    Ok(match stored {
+        StoredNotes::Current(f) => f.notes,
+        StoredNotes::Legacy(notes) => NoteFile::from(notes).notes,
+    })
+}
+
+/// Markdown export of `sections` (title, notes): a `### #id [category]` heading per note with
+/// its tags and timestamps as a list and the content as a block quote. Read back by
+/// `from_markdown`.
+pub fn to_markdown(sections: &[(&str, &[Note])]) -> String {
+    let mut out = String::from("# Notes\n");
+    for (title, notes) in sections {
+        out.push_str(&format!("\n## {}\n", title));
+        for note in *notes {
+            out.push_str(&format!("\n### #{} [{}]\n\n", note.id, note.category));
+            if !note.tags.is_empty() {
+                out.push_str(&format!("- tags: {}\n", note.tags.join(", ")));
+            }
+            out.push_str(&format!("- created: {}\n", note.created_at.to_rfc3339()));
+            out.push_str(&format!("- updated: {}\n\n", note.updated_at.to_rfc3339()));
+            for line in note.content.lines() {
+                out.push_str(if line.is_empty() { ">" } else { "> " });
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/// Notes from a `to_markdown` export; everything outside the note sections is ignored.
+pub fn from_markdown(text: &str) -> Result<Vec<Note>, String> {
+    let mut notes = Vec::new();
+    let mut current: Option<Note> = None;
+    let mut body: Vec<&str> = Vec::new();
+    let finish = |current: &mut Option<Note>, body: &mut Vec<&str>, notes: &mut Vec<Note>| {
+        if let Some(mut note) = current.take() {
+            note.content = body.join("\n").trim().to_string();
+            notes.push(note);
+        }
+        body.clear();
+    };
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.starts_with('#') {
+            finish(&mut current, &mut body, &mut notes);
+            let Some(heading) = line.strip_prefix("### #") else {
+                continue;
+            };
+            let (id, category) = heading
+                .split_once(' ')
+                .and_then(|(id, rest)| {
+                    let category = rest.trim().strip_prefix('[')?.strip_suffix(']')?;
+                    Some((id.parse::<u64>().ok()?, category))
+                })
+                .ok_or_else(|| format!("line {}: expected `### #<id> [<category>]`", i + 1))?;
+            let now = Local::now();
+            current = Some(Note {
+                id,
+                content: String::new(),
+                category: category.to_string(),
+                tags: Vec::new(),
+                created_at: now,
+                updated_at: now,
+            });
+        } else if let Some(note) = current.as_mut() {
+            if let Some(quoted) = line.strip_prefix('>') {
+                body.push(quoted.strip_prefix(' ').unwrap_or(quoted));
+            } else if let Some((key, value)) =
+                line.strip_prefix("- ").and_then(|l| l.split_once(':'))
+            {
+                let time = || {
+                    DateTime::parse_from_rfc3339(value.trim())
+                        .map(|t| t.with_timezone(&Local))
+                        .map_err(|e| format!("line {}: bad {} time: {}", i + 1, key, e))
+                };
+                match key {
+                    "tags" => {
+                        let tags: Vec<String> = value.split(',').map(str::to_string).collect();
+                        note.tags = normalize_tags(&tags);
+                    }
+                    "created" => note.created_at = time()?,
+                    "updated" => note.updated_at = time()?,
+                    _ => {}
+                }
+            }
+        }
+    }
+    finish(&mut current, &mut body, &mut notes);
+    Ok(notes)
+}
+
+/// Lowercase words of at least two characters, without duplicates.
+pub fn terms(text: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| w.chars().count() >= 2)
+    {
+        let word = word.to_lowercase();
+        if !out.contains(&word) {
+            out.push(word);
+        }
+    }
+    out
+}
+
+/// Keyword relevance of `note` for `query_terms`: a whole-word match in the content counts 2,
+/// a partial one 1, a matching tag 3 and a matching category 2.
+pub fn score(note: &Note, query_terms: &[String]) -> f64 {
+    let content = note.content.to_lowercase();
+    let words = terms(&content);
+    let category = note.category.to_lowercase();
+    let mut total = 0.0;
+    for term in query_terms {
+        if words.contains(term) {
+            total += 2.0;
+        } else if content.contains(term.as_str()) {
+            total += 1.0;
+        }
+        if note.tags.contains(term) {
+            total += 3.0;
+        }
+        if category == *term {
+            total += 2.0;
+        }
+    }
+    total
+}
+
+/// Relevance for automatic recall: the keyword score plus a recency bonus of up to 1 that
+/// halves every 30 days since the note was last updated.
+pub fn recall_score(note: &Note, query_terms: &[String], now: DateTime<Local>) -> f64 {
+    let age_days = (now - note.updated_at).num_minutes().max(0) as f64 / (24.0 * 60.0);
+    score(note, query_terms) + 0.5f64.powf(age_days / 30.0)
+}
+
+/// Notes matching `query`, best first (ties: most recently updated first).
+pub fn search<'a>(
+    notes: &'a [(NoteScope, Note)],
+    query: &str,
+    limit: usize,
+) -> Vec<&'a (NoteScope, Note)> {
+    let query_terms = terms(query);
+    let mut hits: Vec<(f64, &(NoteScope, Note))> = notes
+        .iter()
+        .map(|entry| (score(&entry.1, &query_terms), entry))
+        .filter(|(s, _)| *s > 0.0)
+        .collect();
+    hits.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then_with(|| b.1.1.updated_at.cmp(&a.1.1.updated_at))
+    });
+    hits.into_iter().take(limit).map(|(_, n)| n).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    /// A store whose file holds one note in the old format, without ids.
+    fn legacy_store() -> (TempDir, NoteStore) {
+        let dir = TempDir::new("notes");
+        let path = dir.join(".agent_memory.json");
+        std::fs::write(
+            &path,
+            r#"[{"timestamp": "2025-01-02T03:04:05+00:00", "category": "build", "content": "Run cargo build --offline"}]"#,
+        )
+        .unwrap();
+        let store = NoteStore::new(&path);
+        (dir, store)
+    }
+
+    #[tokio::test]
+    async fn test_legacy_notes_get_ids() {
+        let (_dir, store) = legacy_store();
+        let notes = store.load().await.unwrap();
+        assert_eq!((notes[0].id, notes[0].category.as_str()), (1, "build"));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_writers_all_land() {
+        let (_dir, store) = legacy_store();
+        // separate handles, as separate processes would have
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let store = NoteStore::new(&store.path);
+                tokio::spawn(async move {
+                    store
+                        .add(&format!("note {}", i), "general", &[])
+                        .await
+                        .unwrap()
+                })
+            })
+            .collect();
+        for w in writers {
+            w.await.unwrap();
+        }
+        let mut ids: Vec<u64> = store.load().await.unwrap().iter().map(|n| n.id).collect();
+        ids.sort();
+        assert_eq!(ids, (1..=9).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_tags_and_search() {
+        let (_dir, store) = legacy_store();
+        store.add("Use tabs", "style", &[]).await.unwrap();
+        let tagged = store
+            .add(
+                "Database URL lives in .env",
+                "config",
+                &[" DB ".into(), "db".into()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(tagged.tags, vec!["db"]);
+        let stores = NoteStores {
+            workspace: store.clone(),
+            global: None,
+        };
+        let notes = stores.load(None).await.unwrap();
+        let hits: Vec<u64> = search(&notes, "where is the db config?", 5)
+            .iter()
+            .map(|(_, n)| n.id)
+            .collect();
+        assert_eq!(hits, vec![tagged.id]);
+        assert_eq!(search(&notes, "cargo", 5)[0].1.id, 1);
+        assert!(stores.get(NoteScope::Global).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_update_and_delete() {
+        let (_dir, store) = legacy_store();
+        store.add("second", "general", &[]).await.unwrap();
+        let updated = store
+            .update(
+                1,
+                NoteUpdate {
+                    content: Some("Run cargo build".into()),
+                    ..NoteUpdate::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated.category, "build");
+        assert!(updated.updated_at > updated.created_at);
+        store.delete(2).await.unwrap();
+        assert!(
+            store
+                .delete(2)
+                .await
+                .unwrap_err()
+                .contains("no note with id 2")
+        );
+        // ids are never reused
+        assert_eq!(store.add("later", "general", &[]).await.unwrap().id, 3);
+    }
+
+    #[tokio::test]
+    async fn test_markdown_export_and_import() {
+        let (dir, store) = legacy_store();
+        store
+            .add("Database URL lives in .env", "config", &["db".into()])
+            .await
+            .unwrap();
+        // a Markdown export reads back with the same content; importing it again adds nothing
+        let notes = store.load().await.unwrap();
+        let markdown = to_markdown(&[("Workspace", &notes)]);
+        let parsed = from_markdown(&markdown).unwrap();
+        assert_eq!(parsed, notes);
+        assert_eq!(store.import(parsed).await.unwrap(), 0);
+        let other = NoteStore::new(dir.join("other.json"));
+        assert_eq!(other.add("first", "general", &[]).await.unwrap().id, 1);
+        assert_eq!(
+            other
+                .import(parse_notes(&std::fs::read_to_string(&store.path).unwrap()).unwrap())
+                .await
+                .unwrap(),
+            notes.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_file_is_left_alone() {
+        let (_dir, store) = legacy_store();
+        std::fs::write(&store.path, "{ not json").unwrap();
+        assert!(store.add("x", "general", &[]).await.is_err());
+        assert_eq!(std::fs::read_to_string(&store.path).unwrap(), "{ not json");
+    }
+
+    #[tokio::test]
+    async fn test_new_store_ids_start_at_1() {
+        let dir = TempDir::new("notes");
+        let store = NoteStore::new(dir.join(".agent_memory.json"));
+        assert!(store.load().await.unwrap().is_empty());
+        assert_eq!(store.add("first", "general", &[]).await.unwrap().id, 1);
+        assert_eq!(store.add("second", "general", &[]).await.unwrap().id, 2);
+    }
+}
@@ -0,0 +1,651 @@
+//! Jupyter notebook tools: `read_notebook` renders cells (with ids and truncated outputs) and
+//! `edit_notebook` inserts, replaces or deletes whole cells, so the notebook JSON stays valid.
+
+use crate::tools::atomic::{atomic_write, check_write_size};
+use crate::tools::base::{Tool, ToolResult};
+use crate::tools::output::{CappedOutput, strip_ansi};
+use crate::tools::paths::PathPolicy;
+use crate::tools::read_tracker::{FileStamp, ReadTracker};
+use crate::tools::text::{self, TextFormat};
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Each cell output is cut to this many bytes (head and tail kept).
+const MAX_OUTPUT_BYTES: usize = 2000;
+/// Output cap for `read_notebook`; rendering stops at the last whole cell below it.
+const MAX_READ_BYTES: usize = 50_000;
+
+fn error(e: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        content: String::new(),
+        error: Some(e),
+    }
+}
+
+/// Whether `path` looks like a notebook, for the hints in `read_file` / `edit_file`.
+pub fn is_notebook(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("ipynb"))
+}
+
+/// Parse notebook JSON, checking it is an nbformat 4 document with a `cells` array.
+fn parse(text: &str) -> Result<Value, String> {
+    let nb: Value =
+        serde_json::from_str(text).map_err(|e| format!("not valid notebook JSON: {}", e))?;
+    let major = nb.get("nbformat").and_then(Value::as_u64);
+    if major != Some(4) {
+        return Err(format!(
+            "unsupported notebook format (nbformat {}); only nbformat 4 is supported",
+            major.map_or("missing".to_string(), |n| n.to_string())
+        ));
+    }
+    if !nb.get("cells").is_some_and(Value::is_array) {
+        return Err("notebook has no 'cells' array".into());
+    }
+    Ok(nb)
+}
+
+/// Serialize the way Jupyter does: sorted keys, one-space indent, trailing newline.
+fn serialize(nb: &Value) -> String {
+    let mut buf = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b" ");
+    let mut ser = serde_json::Serializer::with_formatter(&mut buf, formatter);
+    // serializing a Value can't fail
+    let _ = nb.serialize(&mut ser);
+    let mut out = String::from_utf8(buf).unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+fn cells(nb: &Value) -> &[Value] {
+    nb["cells"].as_array().map(Vec::as_slice).unwrap_or(&[])
+}
+
+fn cells_mut(nb: &mut Value) -> &mut Vec<Value> {
+    if !nb["cells"].is_array() {
+        nb["cells"] = json!([]);
+    }
+    nb["cells"].as_array_mut().unwrap()
+}
+
+/// Multiline text fields are a string or a list of lines.
+fn join_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+/// Store `text` as a list of lines, each keeping its `\n`, like Jupyter.
+fn split_lines(text: &str) -> Value {
+    Value::Array(
+        text.split_inclusive('\n')
+            .map(|l| Value::String(l.to_string()))
+            .collect(),
+    )
+}
+
+fn cell_type(cell: &Value) -> &str {
+    cell.get("cell_type").and_then(Value::as_str).unwrap_or("?")
+}
+
+fn cell_id(cell: &Value) -> Option<&str> {
+    cell.get("id").and_then(Value::as_str)
+}
+
+fn language(nb: &Value) -> Option<&str> {
+    nb.pointer("/metadata/kernelspec/language")
+        .or_else(|| nb.pointer("/metadata/language_info/name"))
+        .and_then(Value::as_str)
+}
+
+/// Cell ids are required from nbformat 4.5 on; older notebooks are addressed by index.
+fn uses_ids(nb: &Value) -> bool {
+    nb.get("nbformat_minor")
+        .and_then(Value::as_u64)
+        .is_some_and(|m| m >= 5)
+        || cells(nb).iter().any(|c| cell_id(c).is_some())
+}
+
+fn new_cell_id(nb: &Value) -> String {
+    loop {
+        let mut id = uuid::Uuid::new_v4().simple().to_string();
+        id.truncate(8);
+        if !cells(nb).iter().any(|c| cell_id(c) == Some(id.as_str())) {
+            return id;
+        }
+    }
+}
+
+/// The cell named by `cell_id` or `index` in `args`.
+fn find_cell(nb: &Value, args: &Value) -> Result<Option<usize>, String> {
+    let count = cells(nb).len();
+    if let Some(id) = args.get("cell_id").and_then(Value::as_str) {
+        return cells(nb)
+            .iter()
+            .position(|c| cell_id(c) == Some(id))
+            .map(Some)
+            .ok_or_else(|| format!("no cell with id '{}'; read_notebook lists the cell ids", id));
+    }
+    match args.get("index").and_then(Value::as_u64) {
+        Some(i) if (i as usize) < count => Ok(Some(i as usize)),
+        Some(i) => Err(format!(
+            "cell index {} is out of range (the notebook has {} cells)",
+            i, count
+        )),
+        None => Ok(None),
+    }
+}
+
+fn capped(text: &str) -> String {
+    let mut out = CappedOutput::new(MAX_OUTPUT_BYTES);
+    out.push(text.as_bytes());
+    out.render().trim_end().to_string()
+}
+
+/// One cell output: stream text, the text form of a result, or an error with traceback.
+fn render_output(output: &Value) -> String {
+    let kind = output
+        .get("output_type")
+        .and_then(Value::as_str)
+        .unwrap_or("?");
+    match kind {
+        "stream" => {
+            let name = output
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or("stdout");
+            format!(
+                "<output stream=\"{}\">\n{}\n</output>",
+                name,
+                capped(&strip_ansi(&join_text(&output["text"])))
+            )
+        }
+        "execute_result" | "display_data" => {
+            let empty = Map::new();
+            let data = output
+                .get("data")
+                .and_then(Value::as_object)
+                .unwrap_or(&empty);
+            let mut parts = Vec::new();
+            if let Some(text) = ["text/plain", "text/markdown", "text/html"]
+                .iter()
+                .find_map(|mime| data.get(*mime))
+            {
+                parts.push(capped(&join_text(text)));
+            }
+            for (mime, value) in data {
+                if !mime.starts_with("text/") {
+                    parts.push(format!(
+                        "[{} output, {} bytes]",
+                        mime,
+                        match value {
+                            Value::Object(_) => value.to_string().len(),
+                            _ => join_text(value).len(),
+                        }
+                    ));
+                }
+            }
+            format!("<output {}>\n{}\n</output>", kind, parts.join("\n"))
+        }
+        "error" => {
+            let ename = output.get("ename").and_then(Value::as_str).unwrap_or("");
+            let evalue = output.get("evalue").and_then(Value::as_str).unwrap_or("");
+            let traceback: Vec<&str> = output
+                .get("traceback")
+                .and_then(Value::as_array)
+                .map(|t| t.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            let body = if traceback.is_empty() {
+                format!("{}: {}", ename, evalue)
+            } else {
+                strip_ansi(&traceback.join("\n"))
+            };
+            format!("<output error=\"{}\">\n{}\n</output>", ename, capped(&body))
+        }
+        other => format!("<output {}/>", other),
+    }
+}
+
+fn render_cell(index: usize, cell: &Value, include_outputs: bool) -> String {
+    let mut out = format!("<cell index={}", index);
+    if let Some(id) = cell_id(cell) {
+        out.push_str(&format!(" id=\"{}\"", id));
+    }
+    out.push_str(&format!(" type=\"{}\"", cell_type(cell)));
+    if let Some(n) = cell.get("execution_count").and_then(Value::as_u64) {
+        out.push_str(&format!(" execution_count={}", n));
+    }
+    out.push_str(">\n");
+    let source = join_text(&cell["source"]);
+    if !source.is_empty() {
+        out.push_str(source.trim_end_matches('\n'));
+        out.push('\n');
+    }
+    if include_outputs {
+        for output in cell
+            .get("outputs")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            out.push_str(&render_output(output));
+            out.push('\n');
+        }
+    }
+    out.push_str("</cell>\n");
+    out
+}
+
+/// Render cells from `offset` on, stopping at `limit` cells or `MAX_READ_BYTES`.
+fn render_notebook(nb: &Value, offset: usize, limit: usize, include_outputs: bool) -> String {
+    let all = cells(nb);
+    let code = all.iter().filter(|c| cell_type(c) == "code").count();
+    let mut out = format!(
+        "{} cells ({} code, {} other){}\n\n",
+        all.len(),
+        code,
+        all.len() - code,
+        language(nb)
+            .map(|l| format!(", language: {}", l))
+            .unwrap_or_default()
+    );
+    let mut end = offset;
+    for (i, cell) in all.iter().enumerate().skip(offset).take(limit) {
+        let rendered = render_cell(i, cell, include_outputs);
+        if end > offset && out.len() + rendered.len() > MAX_READ_BYTES {
+            break;
+        }
+        out.push_str(&rendered);
+        end = i + 1;
+    }
+    if offset > 0 || end < all.len() {
+        out.push_str(&format!(
+            "\n[showing cells {}-{} of {}",
+            offset,
+            end.saturating_sub(1),
+            all.len()
+        ));
+        if end < all.len() {
+            out.push_str(&format!("; use offset={} to read more", end));
+        }
+        out.push(']');
+    }
+    out
+}
+
+/// A new cell of `kind` (code, markdown or raw) with the fields nbformat requires.
+fn make_cell(kind: &str, source: &str, id: Option<String>) -> Value {
+    let mut cell = json!({
+        "cell_type": kind,
+        "metadata": {},
+        "source": split_lines(source),
+    });
+    if kind == "code" {
+        cell["execution_count"] = Value::Null;
+        cell["outputs"] = json!([]);
+    }
+    if let Some(id) = id {
+        cell["id"] = Value::String(id);
+    }
+    cell
+}
+
+/// Change a cell's type, adding or dropping the fields only code cells have.
+fn set_cell_type(cell: &mut Value, kind: &str) {
+    let Some(obj) = cell.as_object_mut() else {
+        return;
+    };
+    obj.insert("cell_type".into(), Value::String(kind.into()));
+    if kind == "code" {
+        obj.remove("attachments");
+        obj.entry("execution_count").or_insert(Value::Null);
+        obj.entry("outputs").or_insert_with(|| json!([]));
+    } else {
+        obj.remove("execution_count");
+        obj.remove("outputs");
+    }
+}
+
+fn parse_cell_type(args: &Value) -> Result<Option<&str>, String> {
+    match args.get("cell_type").and_then(Value::as_str) {
+        None => Ok(None),
+        Some(t @ ("code" | "markdown" | "raw")) => Ok(Some(t)),
+        Some(other) => Err(format!(
+            "invalid cell_type '{}': expected code, markdown or raw",
+            other
+        )),
+    }
+}
+
+/// Apply one `edit_notebook` operation to `nb`, returning a description of the change and
+/// the index of the inserted or replaced cell.
+fn apply_edit(nb: &mut Value, args: &Value) -> Result<(String, Option<usize>), String> {
+    let operation = args
+        .get("operation")
+        .and_then(Value::as_str)
+        .ok_or("missing 'operation' (insert, replace or delete)")?;
+    let target = find_cell(nb, args)?;
+    let kind = parse_cell_type(args)?;
+    let source = args.get("source").and_then(Value::as_str);
+    match operation {
+        "insert" => {
+            let source = source.ok_or("insert needs 'source'")?;
+            let kind = kind.unwrap_or("code");
+            let id = uses_ids(nb).then(|| new_cell_id(nb));
+            let before = args.get("before").and_then(Value::as_bool).unwrap_or(false);
+            let at = match (target, before) {
+                (Some(i), false) => i + 1,
+                (Some(i), true) => i,
+                (None, false) => cells(nb).len(),
+                (None, true) => 0,
+            };
+            cells_mut(nb).insert(at, make_cell(kind, source, id));
+            Ok((format!("inserted {} cell at index {}", kind, at), Some(at)))
+        }
+        "replace" => {
+            let i = target.ok_or("replace needs 'cell_id' or 'index'")?;
+            if source.is_none() && kind.is_none() {
+                return Err("replace needs 'source' and/or 'cell_type'".into());
+            }
+            let cell = &mut cells_mut(nb)[i];
+            if let Some(kind) = kind {
+                set_cell_type(cell, kind);
+            }
+            if let Some(source) = source {
+                cell["source"] = split_lines(source);
+                if cell_type(cell) == "code" {
+                    // outputs of the old source would be misleading
+                    cell["outputs"] = json!([]);
+                    cell["execution_count"] = Value::Null;
+                }
+            }
+            Ok((format!("replaced cell at index {}", i), Some(i)))
+        }
+        "delete" => {
+            let i = target.ok_or("delete needs 'cell_id' or 'index'")?;
+            let removed = cells_mut(nb).remove(i);
+            Ok((
+                format!(
+                    "deleted {} cell{} at index {}",
+                    cell_type(&removed),
+                    cell_id(&removed)
+                        .map(|id| format!(" {}", id))
+                        .unwrap_or_default(),
+                    i
+                ),
+                None,
+            ))
+        }
+        other => Err(format!(
+            "unknown operation '{}': expected insert, replace or delete",
+            other
+        )),
+    }
+}
+
+/// Read and parse a notebook, returning it with the file's text format.
+async fn load(path: &Path) -> Result<(Vec<u8>, Value, TextFormat), String> {
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("read error: {}", e))?;
+    let (content, format) = text::decode(&bytes)
+        .map_err(|kind| format!("{} is a binary file ({})", path.display(), kind))?;
+    let nb = parse(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok((bytes, nb, format))
+}
+
+pub struct ReadNotebookTool {
+    pub paths: Arc<PathPolicy>,
+    pub tracker: Arc<ReadTracker>,
+}
+
+pub struct EditNotebookTool {
+    pub paths: Arc<PathPolicy>,
+    pub tracker: Arc<ReadTracker>,
+    pub max_write_bytes: usize,
+}
+
+#[async_trait]
+impl Tool for ReadNotebookTool {
+    fn name(&self) -> &str {
+        "read_notebook"
+    }
+    fn description(&self) -> &str {
+        "Read a Jupyter notebook (.ipynb) as a list of cells with their index, id, type, source and (truncated) outputs. Pass cell_id or index for a single cell, or offset/limit to page through large notebooks"
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string"},
+                "cell_id": {"type": "string", "description": "Show only this cell"},
+                "index": {"type": "integer", "description": "Show only the cell at this 0-based index"},
+                "offset": {"type": "integer", "description": "First cell to show, 0-based (default 0)"},
+                "limit": {"type": "integer", "description": "Maximum number of cells"},
+                "include_outputs": {"type": "boolean", "description": "Show cell outputs (default true)"}
+            },
+            "required": ["path"],
+        })
+    }
+    async fn execute(&self, args: Value) -> ToolResult {
+        let Some(path) = args.get("path").and_then(|v| v.as_str()) else {
+            return error("missing 'path'".into());
+        };
+        let full = match self.paths.resolve_read(path) {
+            Ok(p) => p,
+            Err(e) => return error(e),
+        };
+        let stamp = FileStamp::of(&full).await;
+        let (bytes, nb, _) = match load(&full).await {
+            Ok(loaded) => loaded,
+            Err(e) => return error(e),
+        };
+        self.tracker.record_read(&full, stamp, &bytes);
+        let include_outputs = args
+            .get("include_outputs")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        let content = match find_cell(&nb, &args) {
+            Ok(Some(i)) => render_cell(i, &cells(&nb)[i], include_outputs),
+            Ok(None) => render_notebook(
+                &nb,
+                args.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+                args.get("limit")
+                    .and_then(|v| v.as_u64())
+                    .map_or(usize::MAX, |n| n as usize),
+                include_outputs,
+            ),
+            Err(e) => return error(e),
+        };
+        ToolResult {
+            success: true,
+            content,
+            error: None,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for EditNotebookTool {
+    fn name(&self) -> &str {
+        "edit_notebook"
+    }
+    fn description(&self) -> &str {
+        "Change a Jupyter notebook (.ipynb) one cell at a time, keeping the notebook valid. operation=insert adds a cell after cell_id/index (or at the end; before=true inserts before it), replace sets a cell's source and/or cell_type (clearing stale outputs), delete removes a cell. Use this instead of edit_file for notebooks"
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string"},
+                "operation": {"type": "string", "enum": ["insert", "replace", "delete"]},
+                "cell_id": {"type": "string", "description": "Target cell (for insert: the cell to insert after)"},
+                "index": {"type": "integer", "description": "Target cell by 0-based index, for notebooks without cell ids"},
+                "source": {"type": "string", "description": "New cell source"},
+                "cell_type": {"type": "string", "enum": ["code", "markdown", "raw"], "description": "Type of the new cell (default code), or the new type on replace"},
+                "before": {"type": "boolean", "description": "insert before the target cell instead of after it"}
+            },
+            "required": ["path", "operation"],
+        })
+    }
+    async fn execute(&self, args: Value) -> ToolResult {
+        let Some(path) = args.get("path").and_then(|v| v.as_str()) else {
+            return error("missing 'path'".into());
+        };
+        let full = match self.paths.resolve_write(path) {
+            Ok(p) => p,
+            Err(e) => return error(e),
+        };
+        if let Err(e) = self.tracker.check(&full).await {
+            return error(e);
+        }
+        let (_, mut nb, format) = match load(&full).await {
+            Ok(loaded) => loaded,
+            Err(e) => return error(e),
+        };
+        let (summary, changed) = match apply_edit(&mut nb, &args) {
+            Ok(r) => r,
+            Err(e) => return error(format!("{} ({})", e, full.display())),
+        };
+        let bytes = match format.encode(&serialize(&nb)) {
+            Ok(b) => b,
+            Err(e) => return error(e),
+        };
+        if let Err(e) = check_write_size(&full, bytes.len(), self.max_write_bytes) {
+            return error(e);
+        }
+        if let Err(e) = atomic_write(&full, &bytes).await {
+            return error(format!("write error: {}", e));
+        }
+        self.tracker.record(&full, &bytes).await;
+        let mut content = format!(
+            "{} in {} ({} cells)",
+            summary,
+            full.display(),
+            cells(&nb).len()
+        );
+        if let Some(i) = changed {
+            content.push_str("\n\n");
+            content.push_str(&render_cell(i, &cells(&nb)[i], false));
+        }
+        ToolResult {
+            success: true,
+            content,
+            error: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    /// A workspace holding `a.ipynb` (a markdown and a code cell with outputs), and the
+    /// notebook tools sharing a tracker.
+    fn workspace() -> (TempDir, ReadNotebookTool, EditNotebookTool) {
+        let ws = TempDir::new("nb");
+        let nb = json!({
+            "cells": [
+                {"cell_type": "markdown", "id": "intro", "metadata": {}, "source": ["# Title\n", "text"]},
+                {"cell_type": "code", "id": "calc", "metadata": {}, "execution_count": 2,
+                 "source": "print(1)\n1/0",
+                 "outputs": [
+                    {"output_type": "stream", "name": "stdout", "text": ["1\n"]},
+                    {"output_type": "display_data", "metadata": {}, "data": {"image/png": "iVBORw0K", "text/plain": ["<Figure>"]}},
+                    {"output_type": "error", "ename": "ZeroDivisionError", "evalue": "division by zero",
+                     "traceback": ["\u{1b}[0;31mZeroDivisionError\u{1b}[0m: division by zero"]}
+                 ]}
+            ],
+            "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+            "nbformat": 4,
+            "nbformat_minor": 5
+        });
+        std::fs::write(ws.join("a.ipynb"), serialize(&nb)).unwrap();
+        let paths = Arc::new(PathPolicy::new(&ws));
+        let tracker = Arc::new(ReadTracker::default());
+        let read = ReadNotebookTool {
+            paths: paths.clone(),
+            tracker: tracker.clone(),
+        };
+        let edit = EditNotebookTool {
+            paths,
+            tracker,
+            max_write_bytes: 1 << 20,
+        };
+        (ws, read, edit)
+    }
+
+    /// Runs `op` on `a.ipynb` after reading it, and returns the saved cells.
+    async fn edit_cells(op: Value) -> Vec<Value> {
+        let (ws, read, edit) = workspace();
+        assert!(read.execute(json!({"path": "a.ipynb"})).await.success);
+        let mut args = json!({"path": "a.ipynb"});
+        args.as_object_mut()
+            .unwrap()
+            .extend(op.as_object().unwrap().clone());
+        let res = edit.execute(args).await;
+        assert!(res.success, "{:?}", res.error);
+        let nb = parse(&std::fs::read_to_string(ws.join("a.ipynb")).unwrap()).unwrap();
+        cells(&nb).to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_read_notebook() {
+        let (_ws, read, _) = workspace();
+        let res = read.execute(json!({"path": "a.ipynb"})).await;
+        assert_eq!(
+            res.content,
+            "2 cells (1 code, 1 other), language: python\n\n<cell index=0 id=\"intro\" type=\"markdown\">\n# Title\ntext\n</cell>\n<cell index=1 id=\"calc\" type=\"code\" execution_count=2>\nprint(1)\n1/0\n<output stream=\"stdout\">\n1\n</output>\n<output display_data>\n<Figure>\n[image/png output, 8 bytes]\n</output>\n<output error=\"ZeroDivisionError\">\nZeroDivisionError: division by zero\n</output>\n</cell>\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insert_cell_after_id() {
+        let cells = edit_cells(json!({"operation": "insert", "cell_id": "intro", "cell_type": "markdown", "source": "## Setup\n"})).await;
+        assert_eq!(cells.len(), 3);
+        assert_eq!(cells[0]["id"], "intro");
+        assert_eq!(cells[1]["cell_type"], "markdown");
+        assert_eq!(cells[1]["source"], json!(["## Setup\n"]));
+        assert_eq!(cells[1]["id"].as_str().unwrap().len(), 8);
+        assert!(cells[1].get("outputs").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_replace_clears_outputs() {
+        let cells =
+            edit_cells(json!({"operation": "replace", "cell_id": "calc", "source": "print(2)"}))
+                .await;
+        assert_eq!(
+            cells[1],
+            json!({"cell_type": "code", "id": "calc", "metadata": {}, "execution_count": null, "outputs": [], "source": ["print(2)"]})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_cell() {
+        let (ws, read, edit) = workspace();
+        assert!(read.execute(json!({"path": "a.ipynb"})).await.success);
+        let res = edit
+            .execute(json!({"path": "a.ipynb", "operation": "delete", "cell_id": "nope"}))
+            .await;
+        assert!(res.error.unwrap().contains("no cell with id 'nope'"));
+        let res = edit
+            .execute(json!({"path": "a.ipynb", "operation": "delete", "index": 0}))
+            .await;
+        assert!(
+            res.content
+                .starts_with("deleted markdown cell intro at index 0")
+        );
+        let saved = std::fs::read_to_string(ws.join("a.ipynb")).unwrap();
+        assert_eq!(cells(&parse(&saved).unwrap()).len(), 1);
+        // Jupyter's own layout, so saving from Jupyter afterwards produces no diff
+        assert!(saved.starts_with("{\n \"cells\": [\n  {\n   \"cell_type\": \"code\""));
+    }
+}
@@ -0,0 +1,137 @@
+//! Helpers for presenting command output to the model: ANSI stripping and bounded capture.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::VecDeque;
+
+static ANSI_RE: Lazy<Regex> = Lazy::new(|| {
+    // CSI sequences, OSC sequences (terminated by BEL or ST), and two-byte escapes
+    Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)|\x1b[@-Z\\-_]").unwrap()
+});
+
+/// Remove terminal escape sequences (colors, cursor movement, hyperlinks).
+pub fn strip_ansi(s: &str) -> String {
+    ANSI_RE.replace_all(s, "").into_owned()
+}
+
+/// Read size for command output, and how much of a line without a newline is buffered
+/// before it is passed on anyway.
+pub const CHUNK_BYTES: usize = 8192;
+
+/// How much of `buf`, a line still waiting for its newline, can be passed on now: nothing
+/// while it is shorter than `CHUNK_BYTES`, then all of it except a trailing partial UTF-8
+/// character or escape sequence, which would be mangled if split.
+pub fn partial_line_len(buf: &[u8]) -> usize {
+    if buf.len() < CHUNK_BYTES {
+        return 0;
+    }
+    let mut end = buf.len();
+    let window = end.saturating_sub(64);
+    if let Some(esc) = buf[window..].iter().rposition(|&b| b == 0x1b) {
+        let start = window + esc;
+        let rest = String::from_utf8_lossy(&buf[start..]);
+        if ANSI_RE.find(&rest).is_none_or(|m| m.start() != 0) {
+            end = start;
+        }
+    }
+    match std::str::from_utf8(&buf[..end]) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => end,
+    }
+}
+
+/// Output collector that keeps only the first and last bytes once a cap is exceeded,
+/// so memory stays bounded no matter how much a command prints.
+pub struct CappedOutput {
+    head: Vec<u8>,
+    tail: VecDeque<u8>,
+    head_cap: usize,
+    tail_cap: usize,
+    total: usize,
+}
+
+impl CappedOutput {
+    /// Keep at most `max_bytes`: half from the start of the output, half from the end.
+    pub fn new(max_bytes: usize) -> Self {
+        let head_cap = max_bytes / 2;
+        Self {
+            head: Vec::new(),
+            tail: VecDeque::new(),
+            head_cap,
+            tail_cap: max_bytes - head_cap,
+            total: 0,
+        }
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.total += bytes.len();
+        let room = self.head_cap.saturating_sub(self.head.len());
+        let (to_head, rest) = bytes.split_at(room.min(bytes.len()));
+        self.head.extend_from_slice(to_head);
+        self.tail.extend(rest);
+        if self.tail.len() > self.tail_cap {
+            let excess = self.tail.len() - self.tail_cap;
+            self.tail.drain(..excess);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// Bytes dropped from the middle of the output.
+    pub fn elided(&self) -> usize {
+        self.total - self.head.len() - self.tail.len()
+    }
+
+    /// The kept output, with a note in place of the elided middle part.
+    pub fn render(&self) -> String {
+        let (a, b) = self.tail.as_slices();
+        let tail = [a, b].concat();
+        let elided = self.elided();
+        if elided == 0 {
+            return String::from_utf8_lossy(&[self.head.as_slice(), &tail].concat()).into_owned();
+        }
+        format!(
+            "{}\n[... {} bytes elided ...]\n{}",
+            String::from_utf8_lossy(&self.head),
+            elided,
+            String::from_utf8_lossy(&tail)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi_and_cap() {
+        assert_eq!(
+            strip_ansi("\x1b[1;31merror\x1b[0m: \x1b]8;;http://x\x07link\x1b]8;;\x07"),
+            "error: link"
+        );
+
+        let mut out = CappedOutput::new(8);
+        out.push(b"0123");
+        out.push(b"456789abcdef");
+        assert_eq!(out.elided(), 8);
+        assert_eq!(out.render(), "0123\n[... 8 bytes elided ...]\ncdef");
+
+        let mut small = CappedOutput::new(8);
+        small.push(b"abc");
+        small.push(b"def");
+        assert_eq!(small.render(), "abcdef");
+
+        // a long line is split, but not inside a character or an escape sequence
+        assert_eq!(partial_line_len(b"short"), 0);
+        let mut long = vec![b'x'; CHUNK_BYTES - 1];
+        long.extend_from_slice("é".as_bytes());
+        assert_eq!(partial_line_len(&long[..CHUNK_BYTES]), CHUNK_BYTES - 1);
+        assert_eq!(partial_line_len(&long), CHUNK_BYTES + 1);
+        long.extend_from_slice(b"\x1b[3");
+        assert_eq!(partial_line_len(&long), CHUNK_BYTES + 1);
+        long.push(b'm');
+        assert_eq!(partial_line_len(&long), long.len());
+    }
+}
@@ -0,0 +1,817 @@
+//! `apply_patch`: multi-file changes from a unified diff or a `*** Begin Patch` envelope.
+//!
+//! Every hunk is located in the current file (near its stated line, tolerating whitespace
+//! differences and up to two lines of stale outer context) before anything is written; if
+//! any file fails, no file is changed.
+
+use crate::tools::atomic::{atomic_write, check_write_size};
+use crate::tools::base::{Tool, ToolResult};
+use crate::tools::paths::PathPolicy;
+use crate::tools::read_tracker::ReadTracker;
+use crate::tools::text::{self, TextFormat};
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Outer context lines a hunk may lose and still apply.
+const MAX_CONTEXT_FUZZ: usize = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Change {
+    Add(Vec<String>),
+    Delete,
+    Update {
+        hunks: Vec<Hunk>,
+        move_to: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FilePatch {
+    path: String,
+    change: Change,
+    /// The added file ends without a newline
+    no_newline: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Hunk {
+    /// `@@ ... @@` line, for messages
+    header: String,
+    /// 1-based line the hunk starts at in the old file, if known
+    old_start: Option<usize>,
+    /// Envelope `@@ fn foo()` anchors: the hunk is searched after this line
+    anchor: Option<String>,
+    /// (' ' | '-' | '+', text)
+    lines: Vec<(char, String)>,
+    /// `\ No newline at end of file` followed a `-` or context line
+    no_newline_old: bool,
+    /// `\ No newline at end of file` followed a `+` or context line
+    no_newline_new: bool,
+}
+
+impl Hunk {
+    fn counts(&self) -> (usize, usize) {
+        let added = self.lines.iter().filter(|(k, _)| *k == '+').count();
+        let removed = self.lines.iter().filter(|(k, _)| *k == '-').count();
+        (added, removed)
+    }
+}
+
+fn parse(patch: &str) -> Result<Vec<FilePatch>, String> {
+    let patch = patch.replace("\r\n", "\n");
+    let files = if patch.trim_start().starts_with("*** Begin Patch") {
+        parse_envelope(&patch)?
+    } else {
+        parse_unified(&patch)?
+    };
+    if files.is_empty() {
+        return Err("no file changes found in the patch".into());
+    }
+    Ok(files)
+}
+
+/// `*** Begin Patch` / `*** Add File:` / `*** Update File:` (+ `*** Move to:`) /
+/// `*** Delete File:` / `*** End Patch`.
+fn parse_envelope(patch: &str) -> Result<Vec<FilePatch>, String> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    for (n, line) in patch.lines().enumerate() {
+        let n = n + 1;
+        if line == "*** Begin Patch" || line == "*** End Patch" || line == "*** End of File" {
+            continue;
+        }
+        if let Some(p) = line.strip_prefix("*** Add File: ") {
+            files.push(FilePatch {
+                path: p.trim().into(),
+                change: Change::Add(Vec::new()),
+                no_newline: false,
+            });
+            continue;
+        }
+        if let Some(p) = line.strip_prefix("*** Delete File: ") {
+            files.push(FilePatch {
+                path: p.trim().into(),
+                change: Change::Delete,
+                no_newline: false,
+            });
+            continue;
+        }
+        if let Some(p) = line.strip_prefix("*** Update File: ") {
+            files.push(FilePatch {
+                path: p.trim().into(),
+                change: Change::Update {
+                    hunks: Vec::new(),
+                    move_to: None,
+                },
+                no_newline: false,
+            });
+            continue;
+        }
+        let Some(current) = files.last_mut() else {
+            if line.trim().is_empty() {
+                continue;
+            }
+            return Err(format!("line {}: expected a `*** ... File:` header", n));
+        };
+        match &mut current.change {
+            Change::Add(content) => match line.strip_prefix('+') {
+                Some(text) => content.push(text.into()),
+                None => return Err(format!("line {}: added file lines must start with '+'", n)),
+            },
+            Change::Delete => {
+                if !line.trim().is_empty() {
+                    return Err(format!(
+                        "line {}: unexpected text after `*** Delete File`",
+                        n
+                    ));
+                }
+            }
+            Change::Update { hunks, move_to } => {
+                if let Some(p) = line.strip_prefix("*** Move to: ") {
+                    *move_to = Some(p.trim().into());
+                } else if let Some(anchor) = line.strip_prefix("@@") {
+                    let anchor = anchor.trim();
+                    hunks.push(Hunk {
+                        header: line.into(),
+                        anchor: (!anchor.is_empty()).then(|| anchor.into()),
+                        ..Default::default()
+                    });
+                } else {
+                    if hunks.is_empty() {
+                        hunks.push(Hunk {
+                            header: "@@".into(),
+                            ..Default::default()
+                        });
+                    }
+                    let hunk = hunks.last_mut().expect("pushed above");
+                    match line.chars().next() {
+                        Some(k @ (' ' | '-' | '+')) => hunk.lines.push((k, line[1..].into())),
+                        None => hunk.lines.push((' ', String::new())),
+                        _ => {
+                            return Err(format!(
+                                "line {}: hunk lines must start with ' ', '-' or '+'",
+                                n
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// `diff -u` / `git diff` output, including new, deleted and renamed files.
+fn parse_unified(patch: &str) -> Result<Vec<FilePatch>, String> {
+    let lines: Vec<&str> = patch.lines().collect();
+    let mut files = Vec::new();
+    let mut renames: (Option<String>, Option<String>) = (None, None);
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.starts_with("diff --git ") {
+            if let (Some(from), Some(to)) = renames.clone() {
+                // a pure rename has no ---/+++ headers
+                files.push(rename_only(from, to));
+            }
+            renames = (None, None);
+        } else if let Some(p) = line.strip_prefix("rename from ") {
+            renames.0 = Some(p.into());
+        } else if let Some(p) = line.strip_prefix("rename to ") {
+            renames.1 = Some(p.into());
+        } else if let Some(old) = line.strip_prefix("--- ")
+            && let Some(new) = lines.get(i + 1).and_then(|l| l.strip_prefix("+++ "))
+        {
+            renames = (None, None);
+            let (old, new) = strip_git_prefixes(header_path(old), header_path(new));
+            i += 2;
+            let mut hunks = Vec::new();
+            let mut no_newline = false;
+            while let Some(header) = lines.get(i).filter(|l| l.starts_with("@@")) {
+                let (hunk, next) = parse_hunk(&lines, i, header)?;
+                no_newline = hunk.no_newline_new;
+                hunks.push(hunk);
+                i = next;
+            }
+            let change = match (old.as_str(), new.as_str()) {
+                ("/dev/null", _) => Change::Add(
+                    hunks
+                        .iter()
+                        .flat_map(|h| h.lines.iter())
+                        .filter(|(k, _)| *k == '+')
+                        .map(|(_, t)| t.clone())
+                        .collect(),
+                ),
+                (_, "/dev/null") => Change::Delete,
+                _ => Change::Update {
+                    move_to: (old != new).then(|| new.clone()),
+                    hunks,
+                },
+            };
+            let path = if old == "/dev/null" { new } else { old };
+            files.push(FilePatch {
+                path,
+                change,
+                no_newline,
+            });
+            continue;
+        }
+        i += 1;
+    }
+    if let (Some(from), Some(to)) = renames {
+        files.push(rename_only(from, to));
+    }
+    Ok(files)
+}
+
+fn rename_only(from: String, to: String) -> FilePatch {
+    FilePatch {
+        path: from,
+        change: Change::Update {
+            hunks: Vec::new(),
+            move_to: Some(to),
+        },
+        no_newline: false,
+    }
+}
+
+/// Path from a `---`/`+++` line, without a trailing timestamp.
+fn header_path(rest: &str) -> String {
+    rest.split('\t').next().unwrap_or(rest).trim().to_string()
+}
+
+/// Drop git's `a/` and `b/` prefixes when both sides use them.
+fn strip_git_prefixes(old: String, new: String) -> (String, String) {
+    let old_ok = old == "/dev/null" || old.starts_with("a/");
+    let new_ok = new == "/dev/null" || new.starts_with("b/");
+    if old_ok && new_ok {
+        let strip = |p: String, prefix: &str| match p.strip_prefix(prefix) {
+            Some(rest) => rest.to_string(),
+            None => p,
+        };
+        (strip(old, "a/"), strip(new, "b/"))
+    } else {
+        (old, new)
+    }
+}
+
+/// Parse the hunk starting at `lines[start]`; its length comes from the header counts.
+fn parse_hunk(lines: &[&str], start: usize, header: &str) -> Result<(Hunk, usize), String> {
+    let bad = || format!("malformed hunk header `{}`", header);
+    let ranges = header
+        .strip_prefix("@@ ")
+        .and_then(|h| h.split(" @@").next())
+        .ok_or_else(bad)?;
+    let mut parts = ranges.split_whitespace();
+    let parse_range = |r: Option<&str>, sign: char| -> Option<(usize, usize)> {
+        let r = r?.strip_prefix(sign)?;
+        match r.split_once(',') {
+            Some((a, b)) => Some((a.parse().ok()?, b.parse().ok()?)),
+            None => Some((r.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, mut old_left) = parse_range(parts.next(), '-').ok_or_else(bad)?;
+    let (_, mut new_left) = parse_range(parts.next(), '+').ok_or_else(bad)?;
+    let mut hunk = Hunk {
+        header: header.into(),
+        // `-5,0` means "after line 5"
+        old_start: Some(if old_left == 0 {
+            old_start + 1
+        } else {
+            old_start
+        }),
+        ..Default::default()
+    };
+    let mut i = start + 1;
+    while i < lines.len() && (old_left > 0 || new_left > 0 || lines[i].starts_with('\\')) {
+        let line = lines[i];
+        match line.chars().next() {
+            Some('\\') => match hunk.lines.last() {
+                Some(('+', _)) => hunk.no_newline_new = true,
+                Some(('-', _)) => hunk.no_newline_old = true,
+                Some(_) => {
+                    hunk.no_newline_old = true;
+                    hunk.no_newline_new = true;
+                }
+                None => {}
+            },
+            Some('-') => {
+                hunk.lines.push(('-', line[1..].into()));
+                old_left = old_left.saturating_sub(1);
+            }
+            Some('+') => {
+                hunk.lines.push(('+', line[1..].into()));
+                new_left = new_left.saturating_sub(1);
+            }
+            // editors often strip the space of empty context lines
+            Some(' ') | None => {
+                hunk.lines.push((' ', line.get(1..).unwrap_or("").into()));
+                old_left = old_left.saturating_sub(1);
+                new_left = new_left.saturating_sub(1);
+            }
+            _ => return Err(format!("{}: unexpected line `{}`", header, line)),
+        }
+        i += 1;
+    }
+    if old_left > 0 || new_left > 0 {
+        return Err(format!("{}: hunk is shorter than its header says", header));
+    }
+    Ok((hunk, i))
+}
+
+/// How closely hunk text must match file text.
+#[derive(Clone, Copy)]
+enum Fuzz {
+    Exact,
+    TrailingWhitespace,
+    Whitespace,
+}
+
+impl Fuzz {
+    fn eq(self, a: &str, b: &str) -> bool {
+        match self {
+            Fuzz::Exact => a == b,
+            Fuzz::TrailingWhitespace => a.trim_end() == b.trim_end(),
+            Fuzz::Whitespace => a.split_whitespace().eq(b.split_whitespace()),
+        }
+    }
+}
+
+/// Apply `hunks` to `lines`, returning notes about hunks that needed fuzz.
+fn apply_hunks(lines: &mut Vec<String>, hunks: &[Hunk]) -> Result<Vec<String>, String> {
+    let mut notes = Vec::new();
+    let mut cursor = 0;
+    let mut delta: isize = 0;
+    for (n, hunk) in hunks.iter().enumerate() {
+        let n = n + 1;
+        if let Some(anchor) = &hunk.anchor {
+            let found = lines[cursor..]
+                .iter()
+                .position(|l| l.trim_start().starts_with(anchor.as_str()))
+                .ok_or_else(|| format!("hunk {}: anchor line `{}` not found", n, anchor))?;
+            cursor += found + 1;
+        }
+        let hint = hunk
+            .old_start
+            .map(|s| (s as isize - 1 + delta).max(0) as usize);
+        let (start, body, note) = locate(lines, hunk, cursor, hint).ok_or_else(|| {
+            let expected: Vec<&str> = hunk
+                .lines
+                .iter()
+                .filter(|(k, _)| *k != '+')
+                .map(|(_, t)| t.as_str())
+                .collect();
+            format!(
+                "hunk {} ({}) does not match the file; expected:\n{}",
+                n,
+                hunk.header,
+                expected.join("\n")
+            )
+        })?;
+        if let Some(note) = note {
+            notes.push(format!("hunk {} {}", n, note));
+        }
+        // context comes from the file so fuzzy matches keep its whitespace
+        let mut replacement = Vec::new();
+        let mut at = start;
+        for (kind, text) in body {
+            match kind {
+                ' ' => {
+                    replacement.push(lines[at].clone());
+                    at += 1;
+                }
+                '-' => at += 1,
+                _ => replacement.push(text.clone()),
+            }
+        }
+        let added = replacement.len();
+        lines.splice(start..at, replacement);
+        delta += added as isize - (at - start) as isize;
+        cursor = start + added;
+    }
+    Ok(notes)
+}
+
+/// Start line, hunk lines used and an optional fuzz note.
+type Located<'h> = (usize, &'h [(char, String)], Option<String>);
+
+/// Find where `hunk` applies at or after `cursor`, preferring the position closest to
+/// `hint`. Returns the start line, the hunk lines used (outer context may be dropped) and a
+/// note if the match wasn't exact.
+fn locate<'h>(
+    lines: &[String],
+    hunk: &'h Hunk,
+    cursor: usize,
+    hint: Option<usize>,
+) -> Option<Located<'h>> {
+    let all = hunk.lines.as_slice();
+    let lead = all.iter().take_while(|(k, _)| *k == ' ').count();
+    let trail = all.iter().rev().take_while(|(k, _)| *k == ' ').count();
+    for dropped in 0..=MAX_CONTEXT_FUZZ {
+        let (skip_front, skip_back) = (dropped.min(lead), dropped.min(trail));
+        if dropped > 0 && skip_front + skip_back == 0 {
+            break;
+        }
+        if skip_front + skip_back >= all.len() {
+            break;
+        }
+        let body = &all[skip_front..all.len() - skip_back];
+        let old: Vec<&str> = body
+            .iter()
+            .filter(|(k, _)| *k != '+')
+            .map(|(_, t)| t.as_str())
+            .collect();
+        let hint = hint.map(|h| h + skip_front);
+        if old.is_empty() {
+            // pure insertion: at the stated line, or the end of the file
+            let at = hint.unwrap_or(lines.len()).clamp(cursor, lines.len());
+            return Some((at, body, None));
+        }
+        for fuzz in [Fuzz::Exact, Fuzz::TrailingWhitespace, Fuzz::Whitespace] {
+            let candidates = (cursor..=lines.len().saturating_sub(old.len()))
+                .filter(|&p| old.iter().zip(&lines[p..]).all(|(o, l)| fuzz.eq(o, l)));
+            let best = match hint {
+                Some(h) => candidates.min_by_key(|&p| p.abs_diff(h)),
+                None => candidates.min(),
+            };
+            if let Some(p) = best {
+                let mut notes = Vec::new();
+                if let Some(h) = hint
+                    && p != h
+                {
+                    notes.push(format!(
+                        "applied at line {} (offset {:+})",
+                        p + 1,
+                        p as isize - h as isize
+                    ));
+                }
+                match fuzz {
+                    Fuzz::Exact => {}
+                    Fuzz::TrailingWhitespace => notes.push("ignoring trailing whitespace".into()),
+                    Fuzz::Whitespace => notes.push("ignoring whitespace".into()),
+                }
+                if dropped > 0 {
+                    notes.push(format!(
+                        "with {} line(s) of context ignored",
+                        skip_front + skip_back
+                    ));
+                }
+                let note = (!notes.is_empty()).then(|| notes.join(", "));
+                return Some((p, body, note));
+            }
+        }
+    }
+    None
+}
+
+/// A file as the patch sees it: `None` content means it doesn't exist (or is deleted).
+struct Staged {
+    original: Option<Vec<u8>>,
+    content: Option<(Vec<String>, bool, TextFormat)>,
+}
+
+fn split_lines(text: &str) -> (Vec<String>, bool) {
+    match text.strip_suffix('\n') {
+        Some(body) => (body.split('\n').map(String::from).collect(), true),
+        None if text.is_empty() => (Vec::new(), true),
+        None => (text.split('\n').map(String::from).collect(), false),
+    }
+}
+
+fn join_lines(lines: &[String], trailing_newline: bool) -> String {
+    let mut text = lines.join("\n");
+    if trailing_newline && !lines.is_empty() {
+        text.push('\n');
+    }
+    text
+}
+
+pub struct ApplyPatchTool {
+    pub paths: Arc<PathPolicy>,
+    pub tracker: Arc<ReadTracker>,
+    pub max_write_bytes: usize,
+}
+
+impl ApplyPatchTool {
+    async fn stage<'a>(
+        &self,
+        staged: &'a mut HashMap<PathBuf, Staged>,
+        path: &Path,
+    ) -> Result<&'a mut Staged, String> {
+        if !staged.contains_key(path) {
+            let original = match tokio::fs::read(path).await {
+                Ok(b) => Some(b),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(format!("read error: {}", e)),
+            };
+            let content = match &original {
+                Some(bytes) => {
+                    let (text, format) = text::decode(bytes)
+                        .map_err(|kind| format!("is a binary file ({})", kind))?;
+                    let (lines, trailing) = split_lines(&text);
+                    Some((lines, trailing, format))
+                }
+                None => None,
+            };
+            staged.insert(path.to_path_buf(), Staged { original, content });
+        }
+        Ok(staged.get_mut(path).expect("inserted above"))
+    }
+
+    /// Apply one file's change to the staged state, returning its summary line.
+    async fn stage_change(
+        &self,
+        staged: &mut HashMap<PathBuf, Staged>,
+        file: &FilePatch,
+    ) -> Result<String, String> {
+        let full = self.paths.resolve_write(&file.path)?;
+        if !matches!(file.change, Change::Add(_)) {
+            self.tracker.check(&full).await?;
+        }
+        let entry = self.stage(staged, &full).await?;
+        match &file.change {
+            Change::Add(lines) => {
+                if entry.content.is_some() {
+                    return Err("already exists".into());
+                }
+                entry.content = Some((lines.clone(), !file.no_newline, TextFormat::default()));
+                Ok(format!("A {} (+{})", file.path, lines.len()))
+            }
+            Change::Delete => {
+                if entry.content.take().is_none() {
+                    return Err("does not exist".into());
+                }
+                Ok(format!("D {}", file.path))
+            }
+            Change::Update { hunks, move_to } => {
+                let Some((mut lines, mut trailing, format)) = entry.content.take() else {
+                    return Err("does not exist".into());
+                };
+                let notes = apply_hunks(&mut lines, hunks)?;
+                if let Some(last) = hunks.last() {
+                    if last.no_newline_new {
+                        trailing = false;
+                    } else if last.no_newline_old {
+                        trailing = true;
+                    }
+                }
+                let (added, removed) = hunks.iter().fold((0, 0), |(a, r), h| {
+                    let (ha, hr) = h.counts();
+                    (a + ha, r + hr)
+                });
+                let mut summary = match move_to {
+                    Some(to) => {
+                        let target = self.paths.resolve_write(to)?;
+                        let dest = self.stage(staged, &target).await?;
+                        if dest.content.is_some() {
+                            return Err(format!("cannot move to {}: it already exists", to));
+                        }
+                        dest.content = Some((lines, trailing, format));
+                        format!("R {} -> {} (+{} -{})", file.path, to, added, removed)
+                    }
+                    None => {
+                        let entry = staged.get_mut(&full).expect("staged above");
+                        entry.content = Some((lines, trailing, format));
+                        format!("M {} (+{} -{})", file.path, added, removed)
+                    }
+                };
+                for note in notes {
+                    summary.push_str(&format!("\n    {}", note));
+                }
+                Ok(summary)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for ApplyPatchTool {
+    fn name(&self) -> &str {
+        "apply_patch"
+    }
+    fn description(&self) -> &str {
+        "Apply changes to several files at once. Accepts a unified diff (`diff -u` / `git diff`, including new, deleted and renamed files) or an envelope:\n*** Begin Patch\n*** Add File: path\n+line\n*** Update File: path\n*** Move to: new/path (optional)\n@@ optional line to search after\n context\n-old line\n+new line\n*** Delete File: path\n*** End Patch\nHunks are matched against the current files with some tolerance; if any hunk fails, no file is changed"
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "patch": {"type": "string", "description": "Unified diff or *** Begin Patch envelope"}
+            },
+            "required": ["patch"],
+        })
+    }
+    async fn execute(&self, args: Value) -> ToolResult {
+        let Some(patch) = args.get("patch").and_then(|v| v.as_str()) else {
+            return ToolResult {
+                success: false,
+                content: String::new(),
+                error: Some("missing 'patch'".into()),
+            };
+        };
+        let files = match parse(patch) {
+            Ok(f) => f,
+            Err(e) => {
+                return ToolResult {
+                    success: false,
+                    content: String::new(),
+                    error: Some(format!("invalid patch: {}", e)),
+                };
+            }
+        };
+
+        // stage every change in memory; write only if all of them apply
+        let mut staged: HashMap<PathBuf, Staged> = HashMap::new();
+        let mut summary = Vec::new();
+        let mut errors = Vec::new();
+        for file in &files {
+            match self.stage_change(&mut staged, file).await {
+                Ok(s) => summary.push(s),
+                Err(e) => errors.push(format!("{}: {}", file.path, e)),
+            }
+        }
+        let mut writes: Vec<(PathBuf, Option<Vec<u8>>)> = Vec::new();
+        for (path, entry) in &staged {
+            let bytes = match &entry.content {
+                Some((lines, trailing, format)) => {
+                    match format.encode(&join_lines(lines, *trailing)).and_then(|b| {
+                        check_write_size(path, b.len(), self.max_write_bytes).map(|_| b)
+                    }) {
+                        Ok(b) => Some(b),
+                        Err(e) => {
+                            errors.push(format!("{}: {}", path.display(), e));
+                            continue;
+                        }
+                    }
+                }
+                None => None,
+            };
+            if bytes != entry.original {
+                writes.push((path.clone(), bytes));
+            }
+        }
+        if !errors.is_empty() {
+            return ToolResult {
+                success: false,
+                content: String::new(),
+                error: Some(format!(
+                    "patch not applied, no files were changed:\n{}",
+                    errors.join("\n")
+                )),
+            };
+        }
+
+        let mut done: Vec<&PathBuf> = Vec::new();
+        for (path, bytes) in &writes {
+            let result = match bytes {
+                Some(b) => atomic_write(path, b).await,
+                None => tokio::fs::remove_file(path).await,
+            };
+            if let Err(e) = result {
+                // put back what was already written
+                for p in done {
+                    let _ = match &staged[p].original {
+                        Some(orig) => atomic_write(p, orig).await,
+                        None => tokio::fs::remove_file(p).await,
+                    };
+                }
+                return ToolResult {
+                    success: false,
+                    content: String::new(),
+                    error: Some(format!(
+                        "write error for {}: {}; changes were rolled back",
+                        path.display(),
+                        e
+                    )),
+                };
+            }
+            done.push(path);
+        }
+        for (path, bytes) in &writes {
+            match bytes {
+                Some(b) => self.tracker.record(path, b).await,
+                None => self.tracker.forget(path),
+            }
+        }
+        ToolResult {
+            success: true,
+            content: format!(
+                "applied patch to {} file(s):\n{}",
+                files.len(),
+                summary.join("\n")
+            ),
+            error: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    /// A workspace with a few files, and a patch tool that doesn't require reads first.
+    fn workspace() -> (TempDir, ApplyPatchTool) {
+        let ws = TempDir::new("patch");
+        std::fs::create_dir_all(ws.join("src")).unwrap();
+        std::fs::write(
+            ws.join("src/a.rs"),
+            "fn a() {\n    one();\n    two();\n}\n\nfn b() {}\n",
+        )
+        .unwrap();
+        std::fs::write(ws.join("old.txt"), "bye\n").unwrap();
+        std::fs::write(ws.join("crlf.txt"), "x\r\ny\r\n").unwrap();
+        let tool = ApplyPatchTool {
+            paths: Arc::new(PathPolicy::new(&ws)),
+            tracker: Arc::new(ReadTracker::new(false)),
+            max_write_bytes: 1 << 20,
+        };
+        (ws, tool)
+    }
+
+    const ENVELOPE: &str = "*** Begin Patch\n*** Update File: src/new.rs\n*** Move to: src/renamed.rs\n@@ // new\n-fn n() {}\n+fn n() { todo!() }\n*** Update File: src/a.rs\n@@\n-missing();\n+x();\n*** End Patch\n";
+
+    #[tokio::test]
+    async fn test_apply_patch_refuses_unread_files() {
+        let (ws, _) = workspace();
+        let unread = ApplyPatchTool {
+            paths: Arc::new(PathPolicy::new(&ws)),
+            tracker: Arc::new(ReadTracker::default()),
+            max_write_bytes: 1 << 20,
+        };
+        let res = unread
+            .execute(json!({ "patch": "--- a/old.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-bye\n" }))
+            .await;
+        assert!(res.error.unwrap().contains("has not been read"));
+        assert!(ws.join("old.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_apply_unified_diff() {
+        let (ws, tool) = workspace();
+        // the first hunk's line number is off and its context has different indentation
+        let diff = "diff --git a/src/a.rs b/src/a.rs\n--- a/src/a.rs\n+++ b/src/a.rs\n@@ -3,3 +3,3 @@\n fn a() {\n-  one();\n+  uno();\n   two();\n--- /dev/null\n+++ b/src/new.rs\n@@ -0,0 +1,2 @@\n+// new\n+fn n() {}\n--- a/old.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-bye\n--- a/crlf.txt\n+++ b/crlf.txt\n@@ -1,2 +1,2 @@\n x\n-y\n+z\n";
+        let res = tool.execute(json!({ "patch": diff })).await;
+        assert!(res.success, "{:?}", res.error);
+        assert!(
+            res.content.contains(
+                "M src/a.rs (+1 -1)\n    hunk 1 applied at line 1 (offset -2), ignoring whitespace"
+            ),
+            "{}",
+            res.content
+        );
+        assert_eq!(
+            std::fs::read_to_string(ws.join("src/a.rs")).unwrap(),
+            "fn a() {\n  uno();\n    two();\n}\n\nfn b() {}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(ws.join("src/new.rs")).unwrap(),
+            "// new\nfn n() {}\n"
+        );
+        assert!(!ws.join("old.txt").exists());
+        assert_eq!(std::fs::read(ws.join("crlf.txt")).unwrap(), b"x\r\nz\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_failing_hunk_leaves_every_file_untouched() {
+        let (ws, tool) = workspace();
+        std::fs::write(ws.join("src/new.rs"), "// new\nfn n() {}\n").unwrap();
+        let res = tool.execute(json!({ "patch": ENVELOPE })).await;
+        assert!(!res.success);
+        assert!(
+            res.error
+                .unwrap()
+                .contains("src/a.rs: hunk 1 (@@) does not match")
+        );
+        assert!(ws.join("src/new.rs").exists() && !ws.join("src/renamed.rs").exists());
+    }
+
+    #[tokio::test]
+    async fn test_envelope_moves_files() {
+        let (ws, tool) = workspace();
+        std::fs::write(ws.join("src/new.rs"), "// new\nfn n() {}\n").unwrap();
+        let envelope = ENVELOPE.replace("-missing();\n+x();", "-fn b() {}\n+fn b() { x() }");
+        let res = tool.execute(json!({ "patch": envelope })).await;
+        assert!(res.success, "{:?}", res.error);
+        assert_eq!(
+            std::fs::read_to_string(ws.join("src/renamed.rs")).unwrap(),
+            "// new\nfn n() { todo!() }\n"
+        );
+        assert!(!ws.join("src/new.rs").exists());
+    }
+
+    #[tokio::test]
+    async fn test_patch_cannot_escape_the_workspace() {
+        let (_ws, tool) = workspace();
+        let res = tool
+            .execute(json!({ "patch": "*** Begin Patch\n*** Add File: ../escape.txt\n+x\n*** End Patch" }))
+            .await;
+        assert!(
+            res.error
+                .unwrap()
+                .contains("outside the allowed directories")
+        );
+    }
+}
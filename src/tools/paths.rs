@@ -0,0 +1,250 @@
+//! Confinement of file tool paths to the workspace and configured extra directories.
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Expand `~/...` to the home directory and resolve relative paths against `base`.
+pub fn expand_path(base: &Path, input: &str) -> PathBuf {
+    match input.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+        None if input == "~" => dirs::home_dir().unwrap_or_default(),
+        None => base.join(input),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Root {
+    path: PathBuf,
+    read_only: bool,
+}
+
+/// Directories the file tools may touch. Paths are resolved against the workspace, then
+/// canonicalized (following symlinks) and must land inside one of the roots; the most
+/// specific root decides whether writing is allowed.
+#[derive(Debug, Clone)]
+pub struct PathPolicy {
+    workspace: PathBuf,
+    roots: Vec<Root>,
+}
+
+impl PathPolicy {
+    pub fn new(workspace: &Path) -> Self {
+        let workspace = canonical_or_absolute(workspace);
+        Self {
+            roots: vec![Root {
+                path: workspace.clone(),
+                read_only: false,
+            }],
+            workspace,
+        }
+    }
+
+    /// Allow reading and writing beneath `dirs` (missing directories are ignored).
+    pub fn with_dirs(mut self, dirs: &[PathBuf]) -> Self {
+        self.add_roots(dirs, false);
+        self
+    }
+
+    /// Allow reading beneath `dirs` but refuse writes, also inside otherwise writable roots.
+    pub fn with_read_only_dirs(mut self, dirs: &[PathBuf]) -> Self {
+        self.add_roots(dirs, true);
+        self
+    }
+
+    fn add_roots(&mut self, dirs: &[PathBuf], read_only: bool) {
+        for d in dirs {
+            if let Ok(path) = d.canonicalize() {
+                self.roots.push(Root { path, read_only });
+            }
+        }
+    }
+
+    pub fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    /// Roots other than the workspace, with a read-only flag.
+    pub fn extra_dirs(&self) -> impl Iterator<Item = (&Path, bool)> {
+        self.roots[1..]
+            .iter()
+            .map(|r| (r.path.as_path(), r.read_only))
+    }
+
+    /// Resolve a path for reading.
+    pub fn resolve_read(&self, input: &str) -> Result<PathBuf, String> {
+        self.resolve(input).map(|(p, _)| p)
+    }
+
+    /// Resolve a path for writing; fails for read-only roots.
+    pub fn resolve_write(&self, input: &str) -> Result<PathBuf, String> {
+        let (path, root) = self.resolve(input)?;
+        if root.read_only {
+            return Err(format!(
+                "'{}' is in the read-only directory {}",
+                input,
+                root.path.display()
+            ));
+        }
+        Ok(path)
+    }
+
+    fn resolve(&self, input: &str) -> Result<(PathBuf, &Root), String> {
+        let lexical = normalize(&expand_path(&self.workspace, input));
+        let real = canonicalize_existing_prefix(&lexical)
+            .map_err(|e| format!("cannot resolve '{}': {}", input, e))?;
+        if let Some(root) = self.root_for(&real) {
+            return Ok((real, root));
+        }
+        if self.root_for(&lexical).is_some() {
+            return Err(format!(
+                "'{}' resolves to {} through a symlink, which is outside the allowed directories ({})",
+                input,
+                real.display(),
+                self.describe_roots()
+            ));
+        }
+        Err(format!(
+            "'{}' is outside the allowed directories ({})",
+            input,
+            self.describe_roots()
+        ))
+    }
+
+    /// Most specific root containing `path`.
+    fn root_for(&self, path: &Path) -> Option<&Root> {
+        self.roots
+            .iter()
+            .filter(|r| path.starts_with(&r.path))
+            .max_by_key(|r| r.path.components().count())
+    }
+
+    fn describe_roots(&self) -> String {
+        self.roots
+            .iter()
+            .map(|r| {
+                if r.read_only {
+                    format!("{} (read-only)", r.path.display())
+                } else {
+                    r.path.display().to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn canonical_or_absolute(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| {
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            std::env::current_dir().unwrap_or_default().join(path)
+        }
+    })
+}
+
+/// Remove `.` and fold `..` without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// Canonicalize the longest existing ancestor of `path` and re-append the rest, so paths of
+/// files that don't exist yet still get their symlinked parents resolved. A dangling symlink
+/// is an error, since writing through it would create its target wherever it points.
+fn canonicalize_existing_prefix(path: &Path) -> io::Result<PathBuf> {
+    let mut existing = path.to_path_buf();
+    let mut rest = Vec::new();
+    loop {
+        match existing.canonicalize() {
+            Ok(mut real) => {
+                for part in rest.iter().rev() {
+                    real.push(part);
+                }
+                return Ok(real);
+            }
+            Err(_) if existing.symlink_metadata().is_ok() => {
+                return Err(io::Error::other(format!(
+                    "{} is a dangling symlink",
+                    existing.display()
+                )));
+            }
+            Err(e) => {
+                let Some(name) = existing.file_name().map(|n| n.to_os_string()) else {
+                    return Err(e);
+                };
+                rest.push(name);
+                existing.pop();
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn test_confinement() {
+        let root = TempDir::new("paths");
+        let ws = root.join("ws");
+        let outside = root.join("outside");
+        let shared = root.join("shared");
+        std::fs::create_dir_all(ws.join("vendor")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::create_dir_all(&shared).unwrap();
+        std::os::unix::fs::symlink(&outside, ws.join("escape")).unwrap();
+        std::os::unix::fs::symlink(outside.join("missing"), ws.join("dangling")).unwrap();
+
+        let policy = PathPolicy::new(&ws)
+            .with_dirs(std::slice::from_ref(&shared))
+            .with_read_only_dirs(&[ws.join("vendor")]);
+        let ws_real = ws.canonicalize().unwrap();
+
+        assert_eq!(
+            policy.resolve_write("src/./new.rs").unwrap(),
+            ws_real.join("src/new.rs")
+        );
+        assert!(
+            policy
+                .resolve_write(shared.join("x").to_str().unwrap())
+                .is_ok()
+        );
+        assert!(
+            policy
+                .resolve_read("../outside/secret")
+                .unwrap_err()
+                .contains("outside the allowed directories")
+        );
+        assert!(policy.resolve_read("/etc/passwd").is_err());
+        assert!(
+            policy
+                .resolve_read("escape/secret")
+                .unwrap_err()
+                .contains("through a symlink")
+        );
+        assert!(
+            policy
+                .resolve_write("dangling")
+                .unwrap_err()
+                .contains("dangling symlink")
+        );
+        assert!(policy.resolve_read("vendor/lib.rs").is_ok());
+        assert!(
+            policy
+                .resolve_write("vendor/lib.rs")
+                .unwrap_err()
+                .contains("read-only")
+        );
+    }
+}
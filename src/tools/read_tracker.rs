@@ -0,0 +1,226 @@
+//! Per-session record of the files the model has seen, so the file tools can refuse to
+//! overwrite files it never read or that changed on disk since it last read them.
+
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// File systems with coarse timestamps (FAT: 2 s) can give a write made shortly after a read
+/// the same mtime, so an unchanged mtime this close to the read proves nothing.
+const MTIME_SLACK: Duration = Duration::from_secs(2);
+
+/// Size and modification time of a file. Take it before reading the file, so a write that
+/// lands during the read shows up as a change rather than being taken for what was read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    /// `None` if the file doesn't exist (or can't be stat'ed).
+    pub async fn of(path: &Path) -> Option<Self> {
+        let meta = tokio::fs::metadata(path).await.ok()?;
+        Some(Self {
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Snapshot {
+    stamp: Option<FileStamp>,
+    recorded: SystemTime,
+    /// `None` if only part of the file was read.
+    hash: Option<u64>,
+}
+
+impl Snapshot {
+    /// Whether finding the same stamp again proves the content is unchanged.
+    fn stamp_conclusive(&self) -> bool {
+        self.stamp.and_then(|s| s.modified).is_some_and(|m| {
+            self.recorded
+                .duration_since(m)
+                .is_ok_and(|d| d >= MTIME_SLACK)
+        })
+    }
+}
+
+fn hash(bytes: &[u8]) -> u64 {
+    let mut h = DefaultHasher::new();
+    bytes.hash(&mut h);
+    h.finish()
+}
+
+pub struct ReadTracker {
+    enabled: bool,
+    seen: Mutex<HashMap<PathBuf, Snapshot>>,
+}
+
+impl ReadTracker {
+    /// With `enabled: false` every check passes (records are still kept).
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn insert(&self, path: &Path, stamp: Option<FileStamp>, hash: Option<u64>) {
+        let snapshot = Snapshot {
+            stamp,
+            recorded: SystemTime::now(),
+            hash,
+        };
+        self.seen
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), snapshot);
+    }
+
+    /// Remember `bytes`, read after taking `before`, as the content of `path` the model has
+    /// now seen.
+    pub fn record_read(&self, path: &Path, before: Option<FileStamp>, bytes: &[u8]) {
+        self.insert(path, before, Some(hash(bytes)));
+    }
+
+    /// Remember that the model read part of `path`, a file too large to load whole. Without
+    /// the content to compare, only an unchanged stamp shows it is still the same file.
+    pub fn record_partial(&self, path: &Path, before: Option<FileStamp>) {
+        self.insert(path, before, None);
+    }
+
+    /// Remember `bytes` as the content of `path` after one of the file tools wrote it.
+    pub async fn record(&self, path: &Path, bytes: &[u8]) {
+        let stamp = FileStamp::of(path).await;
+        self.insert(path, stamp, Some(hash(bytes)));
+    }
+
+    /// Forget `path`, e.g. after it was deleted.
+    pub fn forget(&self, path: &Path) {
+        self.seen.lock().unwrap().remove(path);
+    }
+
+    /// Check that `path` may be written: it doesn't exist yet, or it was read in this session
+    /// and hasn't changed since. The error tells the model to read the file again.
+    pub async fn check(&self, path: &Path) -> Result<(), String> {
+        let Some(now) = FileStamp::of(path).await else {
+            return Ok(());
+        };
+        if !self.enabled {
+            return Ok(());
+        }
+        let known = self.seen.lock().unwrap().get(path).copied();
+        let Some(known) = known else {
+            return Err(format!(
+                "{} exists but has not been read in this session; read it with read_file first so you don't overwrite content you haven't seen",
+                path.display()
+            ));
+        };
+        let changed = || {
+            Err(format!(
+                "{} has changed on disk since it was last read (edited by the user or another process); read it again with read_file before changing it",
+                path.display()
+            ))
+        };
+        if known.stamp.is_some_and(|s| s.len != now.len) {
+            return changed();
+        }
+        if known.stamp == Some(now) && (known.stamp_conclusive() || known.hash.is_none()) {
+            return Ok(());
+        }
+        let Some(known_hash) = known.hash else {
+            return changed();
+        };
+        // the timestamp moved, or is too recent to trust: only a content change counts
+        let bytes = tokio::fs::read(path)
+            .await
+            .map_err(|e| format!("read error: {}", e))?;
+        if hash(&bytes) != known_hash {
+            return changed();
+        }
+        self.record_read(path, Some(now), &bytes);
+        Ok(())
+    }
+}
+
+impl Default for ReadTracker {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[tokio::test]
+    async fn test_read_before_write() {
+        let dir = TempDir::new("tracker");
+        let file = dir.join("a.txt");
+        let tracker = ReadTracker::default();
+
+        assert!(tracker.check(&dir.join("new.txt")).await.is_ok());
+        std::fs::write(&file, "one").unwrap();
+        assert!(
+            tracker
+                .check(&file)
+                .await
+                .unwrap_err()
+                .contains("has not been read")
+        );
+
+        tracker.record_read(&file, FileStamp::of(&file).await, b"one");
+        assert!(tracker.check(&file).await.is_ok());
+
+        // rewritten with the same content: only the timestamp differs
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(&file, "one").unwrap();
+        assert!(tracker.check(&file).await.is_ok());
+
+        std::fs::write(&file, "two").unwrap();
+        assert!(
+            tracker
+                .check(&file)
+                .await
+                .unwrap_err()
+                .contains("changed on disk")
+        );
+        assert!(ReadTracker::new(false).check(&file).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_changes_hidden_from_the_timestamp() {
+        let dir = TempDir::new("tracker");
+        let file = dir.join("a.txt");
+        let tracker = ReadTracker::default();
+
+        // rewritten within the timestamp resolution: same size and mtime, new content
+        std::fs::write(&file, "one").unwrap();
+        let stamp = FileStamp::of(&file).await;
+        tracker.record_read(&file, stamp, b"one");
+        let mtime = std::fs::metadata(&file).unwrap().modified().unwrap();
+        std::fs::write(&file, "two").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        assert_eq!(FileStamp::of(&file).await, stamp);
+        assert!(tracker.check(&file).await.is_err());
+
+        // written between the stamp and the read: the model saw "one", the file holds "two"
+        std::fs::write(&file, "one").unwrap();
+        let stamp = FileStamp::of(&file).await;
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(&file, "two").unwrap();
+        tracker.record_read(&file, stamp, b"one");
+        assert!(tracker.check(&file).await.is_err());
+    }
+}
@@ -0,0 +1,595 @@
+//! Opt-in sandbox for commands run by the `bash` tool (Linux only).
+//!
+//! - Landlock: filesystem writes are only allowed beneath the workspace and configured paths,
+//!   minus read-only paths (reads are unrestricted)
+//! - seccomp: with networking disabled, IPv4/IPv6 sockets and io_uring are refused
+//! - rlimits: CPU time, address space and process count
+//!
+//! Everything is applied in the child between fork and exec, so the restrictions cover the
+//! command and every process it starts. If the kernel can't enforce the policy the command is
+//! not run at all.
+
+use crate::config::SandboxConfig;
+use crate::tools::paths::expand_path;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    /// Directories (or files) that may be written, besides reads which are always allowed.
+    /// The first entry is the workspace.
+    pub writable: Vec<PathBuf>,
+    /// Paths that may not be written even beneath a writable directory
+    pub read_only: Vec<PathBuf>,
+    pub network: bool,
+    pub cpu_time_secs: Option<u64>,
+    pub memory_mb: Option<u64>,
+    pub max_processes: Option<u64>,
+}
+
+impl Sandbox {
+    /// Sandbox policy for `workspace`, or `None` when the sandbox is disabled.
+    pub fn from_config(config: &SandboxConfig, workspace: &Path) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let mut writable = vec![workspace.to_path_buf()];
+        if config.allow_tmp {
+            writable.push(std::env::temp_dir());
+        }
+        for p in &config.writable_paths {
+            writable.push(expand_path(workspace, p));
+        }
+        let read_only = config
+            .read_only_paths
+            .iter()
+            .map(|p| expand_path(workspace, p))
+            .collect();
+        Some(Self {
+            writable,
+            read_only,
+            network: config.network,
+            cpu_time_secs: config.cpu_time_secs,
+            memory_mb: config.memory_mb,
+            max_processes: config.max_processes,
+        })
+    }
+
+    /// One-line summary of the policy, for the system prompt and error hints.
+    pub fn describe(&self) -> String {
+        let paths: Vec<String> = self
+            .writable
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect();
+        let mut s = format!("writes allowed only under {}", paths.join(", "));
+        if !self.read_only.is_empty() {
+            let read_only: Vec<String> = self
+                .read_only
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect();
+            s.push_str(&format!(" except {}", read_only.join(", ")));
+            let (_, partial) = self.write_grants();
+            if !partial.is_empty() {
+                let partial: Vec<String> =
+                    partial.iter().map(|p| p.display().to_string()).collect();
+                s.push_str(&format!(
+                    " (no new files or directories directly in {})",
+                    partial.join(", ")
+                ));
+            }
+        }
+        if !self.network {
+            s.push_str("; network disabled");
+        }
+        if let Some(n) = self.cpu_time_secs {
+            s.push_str(&format!("; CPU time limit {}s", n));
+        }
+        if let Some(n) = self.memory_mb {
+            s.push_str(&format!("; memory limit {} MB", n));
+        }
+        if let Some(n) = self.max_processes {
+            s.push_str(&format!("; process limit {}", n));
+        }
+        s
+    }
+
+    /// Best guess at why a sandboxed command failed, based on its exit status and stderr.
+    pub fn explain_failure(&self, status: &str, stderr: &str) -> Option<String> {
+        if status.contains("SIGXCPU") {
+            return self
+                .cpu_time_secs
+                .map(|n| format!("CPU time limit of {}s exceeded", n));
+        }
+        if let Some(path) = denied_paths(stderr).find(|p| !self.may_write(p)) {
+            return Some(format!(
+                "{} is not writable; {}",
+                path.display(),
+                self.describe()
+            ));
+        }
+        if !self.network
+            && (stderr.contains("Operation not permitted")
+                || stderr.contains("Network is unreachable"))
+        {
+            return Some("network access is disabled by the sandbox".into());
+        }
+        if let Some(n) = self.max_processes
+            && stderr.contains("Resource temporarily unavailable")
+        {
+            return Some(format!("process limit of {} reached", n));
+        }
+        if let Some(n) = self.memory_mb
+            && (stderr.contains("Cannot allocate memory") || stderr.contains("out of memory"))
+        {
+            return Some(format!("memory limit of {} MB reached", n));
+        }
+        None
+    }
+
+    /// Whether the policy lets commands write `path` (relative paths are taken from the
+    /// workspace): beneath a writable path, and not beneath a read-only one more specific.
+    fn may_write(&self, path: &Path) -> bool {
+        let path = canonical(
+            &self
+                .writable
+                .first()
+                .map_or(path.to_path_buf(), |ws| ws.join(path)),
+        );
+        let depth = |roots: &[PathBuf]| {
+            roots
+                .iter()
+                .map(|r| canonical(r))
+                .filter(|r| path.starts_with(r))
+                .map(|r| r.components().count())
+                .max()
+        };
+        match (depth(&self.writable), depth(&self.read_only)) {
+            (Some(w), Some(r)) => w > r,
+            (w, _) => w.is_some(),
+        }
+    }
+
+    /// The paths to grant write access beneath, and the directories only partly writable.
+    ///
+    /// Landlock rights always extend to everything below a rule, so a writable directory
+    /// holding a read-only path is replaced by its current entries, recursively down to the
+    /// read-only path. Those directories themselves get no rights: nothing new can be
+    /// created directly in them. Symlinks among the entries are left out, since a rule on
+    /// one would grant its target.
+    pub fn write_grants(&self) -> (Vec<PathBuf>, Vec<PathBuf>) {
+        let read_only: Vec<PathBuf> = self.read_only.iter().map(|p| canonical(p)).collect();
+        let mut grants = Vec::new();
+        let mut partial = Vec::new();
+        for w in &self.writable {
+            let w = canonical(w);
+            // the more specific path decides, as for the file tools
+            if read_only.iter().any(|r| w.starts_with(r)) {
+                continue;
+            }
+            split_around(&w, &read_only, &mut grants, &mut partial);
+        }
+        (grants, partial)
+    }
+
+    /// Restrict `command` so the process it spawns runs inside the sandbox.
+    #[cfg(target_os = "linux")]
+    pub fn apply(&self, command: &mut tokio::process::Command) -> io::Result<()> {
+        let ruleset = linux::landlock_ruleset(&self.write_grants().0)?;
+        let filter = if self.network {
+            None
+        } else {
+            Some(linux::network_filter()?)
+        };
+        let limits = linux::Limits {
+            cpu_time_secs: self.cpu_time_secs,
+            memory_bytes: self.memory_mb.map(|mb| mb * 1024 * 1024),
+            max_processes: self.max_processes,
+        };
+        // Safety: the hook only makes async-signal-safe syscalls on data prepared above.
+        unsafe {
+            command.pre_exec(move || linux::enter(&ruleset, filter.as_deref(), &limits));
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn apply(&self, _command: &mut tokio::process::Command) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the bash sandbox is only available on Linux",
+        ))
+    }
+}
+
+/// Push `dir` to `grants`, or if a read-only path lies beneath it, push it to `partial` and
+/// handle its entries other than the read-only path the same way.
+fn split_around(
+    dir: &Path,
+    read_only: &[PathBuf],
+    grants: &mut Vec<PathBuf>,
+    partial: &mut Vec<PathBuf>,
+) {
+    if !read_only.iter().any(|r| r != dir && r.starts_with(dir)) {
+        grants.push(dir.to_path_buf());
+        return;
+    }
+    partial.push(dir.to_path_buf());
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if read_only.contains(&path) || entry.file_type().is_ok_and(|t| t.is_symlink()) {
+            continue;
+        }
+        split_around(&path, read_only, grants, partial);
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Paths named in the "Permission denied" / "Read-only file system" lines of `stderr`, e.g.
+/// `touch: cannot touch 'x': Permission denied` or `bash: line 1: x: Permission denied`.
+fn denied_paths(stderr: &str) -> impl Iterator<Item = PathBuf> + '_ {
+    stderr
+        .lines()
+        .filter(|l| l.contains("Permission denied") || l.contains("Read-only file system"))
+        .flat_map(|line| {
+            let quoted: Vec<&str> = line
+                .split(['\'', '"', '\u{2018}', '\u{2019}'])
+                .skip(1)
+                .step_by(2)
+                .collect();
+            if !quoted.is_empty() {
+                return quoted;
+            }
+            // `program: [line N: ]path: error`
+            let parts: Vec<&str> = line.split(": ").collect();
+            let middle = parts
+                .get(1..parts.len().saturating_sub(1))
+                .unwrap_or_default();
+            middle
+                .iter()
+                .filter(|p| !p.starts_with("line "))
+                .copied()
+                .collect()
+        })
+        .map(PathBuf::from)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::PathBuf;
+
+    const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1;
+    const LANDLOCK_RULE_PATH_BENEATH: u32 = 1;
+
+    const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+    const ACCESS_FS_REMOVE_DIR: u64 = 1 << 4;
+    const ACCESS_FS_REMOVE_FILE: u64 = 1 << 5;
+    const ACCESS_FS_MAKE_CHAR: u64 = 1 << 6;
+    const ACCESS_FS_MAKE_DIR: u64 = 1 << 7;
+    const ACCESS_FS_MAKE_REG: u64 = 1 << 8;
+    const ACCESS_FS_MAKE_SOCK: u64 = 1 << 9;
+    const ACCESS_FS_MAKE_FIFO: u64 = 1 << 10;
+    const ACCESS_FS_MAKE_BLOCK: u64 = 1 << 11;
+    const ACCESS_FS_MAKE_SYM: u64 = 1 << 12;
+    const ACCESS_FS_REFER: u64 = 1 << 13; // ABI 2
+    const ACCESS_FS_TRUNCATE: u64 = 1 << 14; // ABI 3
+
+    /// Rights that only make sense on files (rules for non-directories may not use others).
+    const FILE_ACCESS: u64 = ACCESS_FS_WRITE_FILE | ACCESS_FS_TRUNCATE;
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    /// Create a Landlock ruleset that handles every write right and grants them beneath
+    /// `writable` (plus `/dev/null`). Missing paths are skipped.
+    pub fn landlock_ruleset(writable: &[PathBuf]) -> io::Result<OwnedFd> {
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0usize,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            )
+        };
+        if abi < 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Landlock is not available on this kernel (needs Linux 5.13+ with Landlock enabled)",
+            ));
+        }
+        let mut handled = ACCESS_FS_WRITE_FILE
+            | ACCESS_FS_REMOVE_DIR
+            | ACCESS_FS_REMOVE_FILE
+            | ACCESS_FS_MAKE_CHAR
+            | ACCESS_FS_MAKE_DIR
+            | ACCESS_FS_MAKE_REG
+            | ACCESS_FS_MAKE_SOCK
+            | ACCESS_FS_MAKE_FIFO
+            | ACCESS_FS_MAKE_BLOCK
+            | ACCESS_FS_MAKE_SYM;
+        if abi >= 2 {
+            handled |= ACCESS_FS_REFER;
+        }
+        if abi >= 3 {
+            handled |= ACCESS_FS_TRUNCATE;
+        }
+        let attr = RulesetAttr {
+            handled_access_fs: handled,
+        };
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                std::mem::size_of::<RulesetAttr>(),
+                0u32,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let ruleset = unsafe { OwnedFd::from_raw_fd(fd as i32) };
+
+        let dev_null = PathBuf::from("/dev/null");
+        for path in writable.iter().chain(std::iter::once(&dev_null)) {
+            let Ok(meta) = std::fs::metadata(path) else {
+                continue;
+            };
+            let mut cpath = path.as_os_str().as_bytes().to_vec();
+            cpath.push(0);
+            let pfd = unsafe {
+                libc::open(
+                    cpath.as_ptr() as *const libc::c_char,
+                    libc::O_PATH | libc::O_CLOEXEC,
+                )
+            };
+            if pfd < 0 {
+                continue;
+            }
+            let parent = unsafe { OwnedFd::from_raw_fd(pfd) };
+            let allowed = if meta.is_dir() {
+                handled
+            } else {
+                handled & FILE_ACCESS
+            };
+            let rule = PathBeneathAttr {
+                allowed_access: allowed,
+                parent_fd: parent.as_raw_fd(),
+            };
+            let rc = unsafe {
+                libc::syscall(
+                    libc::SYS_landlock_add_rule,
+                    ruleset.as_raw_fd(),
+                    LANDLOCK_RULE_PATH_BENEATH,
+                    &rule as *const PathBeneathAttr,
+                    0u32,
+                )
+            };
+            if rc < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(ruleset)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xC000_003E;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xC000_00B7;
+
+    /// Classic BPF program denying `socket(AF_INET | AF_INET6, ...)` and `io_uring_setup`
+    /// with EPERM. Syscalls from a foreign ABI (e.g. 32-bit) kill the process, since their
+    /// numbers differ and could bypass the check.
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    pub fn network_filter() -> io::Result<Vec<libc::sock_filter>> {
+        const LD_ABS: u16 = (libc::BPF_LD | libc::BPF_W | libc::BPF_ABS) as u16;
+        const JEQ: u16 = (libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K) as u16;
+        const JGE: u16 = (libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K) as u16;
+        const RET: u16 = (libc::BPF_RET | libc::BPF_K) as u16;
+        fn op(code: u16, jt: u8, jf: u8, k: u32) -> libc::sock_filter {
+            libc::sock_filter { code, jt, jf, k }
+        }
+        // offsets into struct seccomp_data
+        const NR: u32 = 0;
+        const ARCH: u32 = 4;
+        const ARG0: u32 = 16;
+        const DENY: u32 = libc::SECCOMP_RET_ERRNO | libc::EPERM as u32;
+        Ok(vec![
+            /* 0 */ op(LD_ABS, 0, 0, ARCH),
+            /* 1 */ op(JEQ, 0, 10, AUDIT_ARCH), // -> 12 (kill)
+            /* 2 */ op(LD_ABS, 0, 0, NR),
+            /* 3 */ op(JGE, 7, 0, 0x4000_0000), // x32 ABI -> 11 (deny)
+            /* 4 */ op(JEQ, 2, 0, libc::SYS_socket as u32), // -> 7
+            /* 5 */ op(JEQ, 5, 0, libc::SYS_io_uring_setup as u32), // -> 11
+            /* 6 */ op(RET, 0, 0, libc::SECCOMP_RET_ALLOW),
+            /* 7 */ op(LD_ABS, 0, 0, ARG0),
+            /* 8 */ op(JEQ, 2, 0, libc::AF_INET as u32), // -> 11
+            /* 9 */ op(JEQ, 1, 0, libc::AF_INET6 as u32), // -> 11
+            /* 10 */ op(RET, 0, 0, libc::SECCOMP_RET_ALLOW),
+            /* 11 */ op(RET, 0, 0, DENY),
+            /* 12 */ op(RET, 0, 0, libc::SECCOMP_RET_KILL_PROCESS),
+        ])
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    pub fn network_filter() -> io::Result<Vec<libc::sock_filter>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "disabling network in the sandbox is only supported on x86_64 and aarch64",
+        ))
+    }
+
+    pub struct Limits {
+        pub cpu_time_secs: Option<u64>,
+        pub memory_bytes: Option<u64>,
+        pub max_processes: Option<u64>,
+    }
+
+    fn set_limit(resource: libc::__rlimit_resource_t, soft: u64, hard: u64) -> io::Result<()> {
+        let lim = libc::rlimit {
+            rlim_cur: soft as libc::rlim_t,
+            rlim_max: hard as libc::rlim_t,
+        };
+        if unsafe { libc::setrlimit(resource, &lim) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Runs in the forked child right before exec: no allocation, only syscalls.
+    pub fn enter(
+        ruleset: &OwnedFd,
+        filter: Option<&[libc::sock_filter]>,
+        limits: &Limits,
+    ) -> io::Result<()> {
+        if let Some(n) = limits.cpu_time_secs {
+            // SIGXCPU at the soft limit; a second later the hard limit sends SIGKILL
+            set_limit(libc::RLIMIT_CPU, n, n + 1)?;
+        }
+        if let Some(n) = limits.memory_bytes {
+            set_limit(libc::RLIMIT_AS, n, n)?;
+        }
+        if let Some(n) = limits.max_processes {
+            set_limit(libc::RLIMIT_NPROC, n, n)?;
+        }
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0u32) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if let Some(filter) = filter {
+                let prog = libc::sock_fprog {
+                    len: filter.len() as u16,
+                    filter: filter.as_ptr() as *mut libc::sock_filter,
+                };
+                if libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    libc::SECCOMP_MODE_FILTER,
+                    &prog as *const libc::sock_fprog,
+                ) != 0
+                {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(
+    test,
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    async fn run(sb: &Sandbox, script: &str) -> std::process::Output {
+        let mut cmd = tokio::process::Command::new("bash");
+        cmd.arg("-c").arg(script);
+        sb.apply(&mut cmd).unwrap();
+        cmd.output().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sandbox_enforces_policy() {
+        let root = TempDir::new("sandbox");
+        let ws = root.join("ws");
+        let outside = root.join("outside");
+        std::fs::create_dir_all(&ws).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        let sb = Sandbox {
+            writable: vec![ws.clone()],
+            read_only: Vec::new(),
+            network: false,
+            cpu_time_secs: Some(1),
+            memory_mb: None,
+            max_processes: None,
+        };
+        if let Err(e) = sb.apply(&mut tokio::process::Command::new("true")) {
+            eprintln!("skipping: {}", e);
+            return;
+        }
+
+        let out = run(
+            &sb,
+            &format!(
+                "echo ok > {}/a; echo no > {}/b",
+                ws.display(),
+                outside.display()
+            ),
+        )
+        .await;
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(ws.join("a").exists());
+        assert!(!outside.join("b").exists());
+        assert!(stderr.contains("Permission denied"), "{stderr}");
+        assert!(sb.explain_failure("exit code: 1", &stderr).is_some());
+
+        // Landlock can't exclude vendor from the workspace grant, so the workspace is split
+        let ws = ws.canonicalize().unwrap();
+        let vendor = ws.join("vendor");
+        std::fs::create_dir_all(&vendor).unwrap();
+        std::fs::create_dir_all(ws.join("src")).unwrap();
+        let ro = Sandbox {
+            read_only: vec![vendor.clone()],
+            ..sb.clone()
+        };
+        let (mut grants, partial) = ro.write_grants();
+        grants.sort();
+        assert_eq!(grants, vec![ws.join("a"), ws.join("src")]);
+        assert_eq!(partial, vec![ws.clone()]);
+        let out = run(
+            &ro,
+            &format!(
+                "echo ok >> {0}/a; echo ok > {0}/src/e; echo no > {1}/c",
+                ws.display(),
+                vendor.display()
+            ),
+        )
+        .await;
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(ws.join("src/e").exists(), "{stderr}");
+        assert_eq!(std::fs::read_to_string(ws.join("a")).unwrap(), "ok\nok\n");
+        assert!(!vendor.join("c").exists());
+        let why = ro.explain_failure("exit code: 1", &stderr).unwrap();
+        assert!(why.contains("vendor/c is not writable"), "{why}");
+
+        let out = run(&sb, "echo > /dev/tcp/127.0.0.1/9").await;
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(stderr.contains("Operation not permitted"), "{stderr}");
+
+        // a command's own permission error inside the workspace is not the sandbox's doing
+        assert_eq!(
+            sb.explain_failure(
+                "exit code: 1",
+                "bash: line 1: ./build.sh: Permission denied"
+            ),
+            None
+        );
+
+        let out = run(&sb, "while :; do :; done").await;
+        use std::os::unix::process::ExitStatusExt;
+        assert_eq!(out.status.signal(), Some(libc::SIGXCPU));
+    }
+}
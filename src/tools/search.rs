@@ -0,0 +1,489 @@
+//! `glob` and `grep`: file search that honors `.gitignore` and gives the same output on
+//! every platform (paths relative to the workspace, `/` separators).
+
+use crate::tools::base::{Tool, ToolResult};
+use crate::tools::paths::PathPolicy;
+use crate::tools::text;
+use async_trait::async_trait;
+use globset::{Glob, GlobBuilder, GlobMatcher};
+use ignore::WalkBuilder;
+use ignore::types::{Types, TypesBuilder};
+use regex::{Regex, RegexBuilder};
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const DEFAULT_LIMIT: usize = 200;
+/// Files larger than this are not searched by `grep`.
+const MAX_GREP_FILE_BYTES: u64 = 10 * 1024 * 1024;
+/// Matched lines are cut to this many characters.
+const MAX_LINE_CHARS: usize = 500;
+
+fn error(e: String) -> ToolResult {
+    ToolResult {
+        success: false,
+        content: String::new(),
+        error: Some(e),
+    }
+}
+
+/// Path shown to the model: relative to the workspace when inside it, always with `/`.
+pub fn display_path(workspace: &Path, path: &Path) -> String {
+    match path.strip_prefix(workspace) {
+        Ok(rel) if rel.as_os_str().is_empty() => ".".into(),
+        Ok(rel) => rel
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        Err(_) => path.to_string_lossy().replace('\\', "/"),
+    }
+}
+
+/// Walker over `root` sorted by name, skipping `.git` and, unless `include_ignored`, hidden
+/// files and anything excluded by `.gitignore`/`.ignore` (also outside git repositories).
+pub fn walker(root: &Path, include_ignored: bool) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .standard_filters(!include_ignored)
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(|e| e.file_name() != ".git");
+    builder
+}
+
+/// Files beneath `root` in a stable order, filtered as by [`walker`].
+fn walk(root: &Path, include_ignored: bool) -> Vec<PathBuf> {
+    walker(root, include_ignored)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+        .map(|e| e.into_path())
+        .collect()
+}
+
+pub fn search_root(paths: &PathPolicy, args: &Value) -> Result<PathBuf, String> {
+    let input = args.get("path").and_then(|v| v.as_str()).unwrap_or(".");
+    let root = paths.resolve_read(input)?;
+    if !root.exists() {
+        return Err(format!("'{}' does not exist", input));
+    }
+    Ok(root)
+}
+
+fn glob_matcher(pattern: &str) -> Result<GlobMatcher, String> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map(|g| g.compile_matcher())
+        .map_err(|e| format!("invalid glob '{}': {}", pattern, e))
+}
+
+pub struct GlobTool {
+    pub paths: Arc<PathPolicy>,
+}
+
+#[async_trait]
+impl Tool for GlobTool {
+    fn name(&self) -> &str {
+        "glob"
+    }
+    fn description(&self) -> &str {
+        "Find files by glob pattern, e.g. `**/*.rs` or `src/**/mod.rs` (`*` doesn't cross `/`). Skips hidden and .gitignore'd files unless include_ignored is set. Returns workspace-relative paths, sorted"
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "pattern": {"type": "string", "description": "Glob relative to `path`"},
+                "path": {"type": "string", "description": "Directory to search (default: workspace)"},
+                "limit": {"type": "integer", "description": "Maximum number of paths (default 200)"},
+                "include_ignored": {"type": "boolean", "description": "Also list hidden and ignored files"}
+            },
+            "required": ["pattern"],
+        })
+    }
+    async fn execute(&self, args: Value) -> ToolResult {
+        let Some(pattern) = args.get("pattern").and_then(|v| v.as_str()) else {
+            return error("missing 'pattern'".into());
+        };
+        let matcher = match glob_matcher(pattern) {
+            Ok(m) => m,
+            Err(e) => return error(e),
+        };
+        let root = match search_root(&self.paths, &args) {
+            Ok(r) => r,
+            Err(e) => return error(e),
+        };
+        let limit = args
+            .get("limit")
+            .and_then(|v| v.as_u64())
+            .map_or(DEFAULT_LIMIT, |n| n as usize);
+        let include_ignored = args
+            .get("include_ignored")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let workspace = self.paths.workspace().to_path_buf();
+        let found = tokio::task::spawn_blocking(move || {
+            walk(&root, include_ignored)
+                .into_iter()
+                .filter(|p| matcher.is_match(p.strip_prefix(&root).unwrap_or(p)))
+                .map(|p| display_path(&workspace, &p))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap_or_default();
+        if found.is_empty() {
+            return ToolResult {
+                success: true,
+                content: format!("no files match '{}'", pattern),
+                error: None,
+            };
+        }
+        let mut out = found
+            .iter()
+            .take(limit)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n");
+        if found.len() > limit {
+            out.push_str(&format!(
+                "\n[{} more not shown; narrow the pattern or raise limit]",
+                found.len() - limit
+            ));
+        }
+        ToolResult {
+            success: true,
+            content: out,
+            error: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum OutputMode {
+    Content,
+    Files,
+    Count,
+}
+
+struct GrepQuery {
+    regex: Regex,
+    glob: Option<GlobMatcher>,
+    types: Option<Types>,
+    context: usize,
+    mode: OutputMode,
+    limit: usize,
+    include_ignored: bool,
+}
+
+impl GrepQuery {
+    fn from_args(args: &Value) -> Result<Self, String> {
+        let pattern = args
+            .get("pattern")
+            .and_then(|v| v.as_str())
+            .ok_or("missing 'pattern'")?;
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(
+                args.get("case_insensitive")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+            )
+            .build()
+            .map_err(|e| format!("invalid regex: {}", e))?;
+        // like ripgrep, a glob without `/` matches file names anywhere
+        let glob = match args.get("glob").and_then(|v| v.as_str()) {
+            Some(g) if g.contains('/') => Some(glob_matcher(g)?),
+            Some(g) => Some(
+                Glob::new(&format!("**/{}", g))
+                    .map_err(|e| format!("invalid glob '{}': {}", g, e))?
+                    .compile_matcher(),
+            ),
+            None => None,
+        };
+        let types = match args.get("type").and_then(|v| v.as_str()) {
+            Some(t) => {
+                let mut builder = TypesBuilder::new();
+                builder.add_defaults();
+                if !builder.definitions().iter().any(|d| d.name() == t) {
+                    return Err(format!(
+                        "unknown file type '{}' (e.g. rust, py, js, ts, go, java, c, cpp, md)",
+                        t
+                    ));
+                }
+                builder.select(t);
+                Some(builder.build().map_err(|e| e.to_string())?)
+            }
+            None => None,
+        };
+        let mode = match args.get("output_mode").and_then(|v| v.as_str()) {
+            None | Some("content") => OutputMode::Content,
+            Some("files") => OutputMode::Files,
+            Some("count") => OutputMode::Count,
+            Some(other) => {
+                return Err(format!(
+                    "unknown output_mode '{}' (content, files or count)",
+                    other
+                ));
+            }
+        };
+        Ok(Self {
+            regex,
+            glob,
+            types,
+            context: args.get("context").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+            mode,
+            limit: args
+                .get("limit")
+                .and_then(|v| v.as_u64())
+                .map_or(DEFAULT_LIMIT, |n| n as usize),
+            include_ignored: args
+                .get("include_ignored")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        })
+    }
+
+    fn wants(&self, root: &Path, path: &Path) -> bool {
+        if let Some(glob) = &self.glob
+            && !glob.is_match(path.strip_prefix(root).unwrap_or(path))
+        {
+            return false;
+        }
+        if let Some(types) = &self.types
+            && !types.matched(path, false).is_whitelist()
+        {
+            return false;
+        }
+        true
+    }
+
+    /// Run over `root` (a directory or a single file). Output entries are counted against
+    /// `limit`: matching lines, files or counts depending on the mode.
+    fn run(&self, root: &Path, workspace: &Path) -> String {
+        let files = if root.is_file() {
+            vec![root.to_path_buf()]
+        } else {
+            walk(root, self.include_ignored)
+        };
+        let mut out: Vec<String> = Vec::new();
+        let mut shown = 0;
+        let mut omitted = 0;
+        for path in files.iter().filter(|p| self.wants(root, p)) {
+            if std::fs::metadata(path).map_or(true, |m| m.len() > MAX_GREP_FILE_BYTES) {
+                continue;
+            }
+            let Ok(bytes) = std::fs::read(path) else {
+                continue;
+            };
+            // binary files are skipped
+            let Ok((content, _)) = text::decode(&bytes) else {
+                continue;
+            };
+            let lines: Vec<&str> = content.lines().collect();
+            let hits: Vec<usize> = (0..lines.len())
+                .filter(|&i| self.regex.is_match(lines[i]))
+                .collect();
+            if hits.is_empty() {
+                continue;
+            }
+            let name = display_path(workspace, path);
+            match self.mode {
+                OutputMode::Files | OutputMode::Count => {
+                    if shown == self.limit {
+                        omitted += 1;
+                        continue;
+                    }
+                    shown += 1;
+                    out.push(if self.mode == OutputMode::Files {
+                        name
+                    } else {
+                        format!("{}:{}", name, hits.len())
+                    });
+                }
+                OutputMode::Content => {
+                    let room = self.limit - shown;
+                    omitted += hits.len().saturating_sub(room);
+                    let hits = &hits[..hits.len().min(room)];
+                    shown += hits.len();
+                    self.render_hits(&mut out, &name, &lines, hits);
+                }
+            }
+        }
+        if out.is_empty() {
+            return format!("no matches for /{}/", self.regex.as_str());
+        }
+        let mut text = out.join("\n");
+        if omitted > 0 {
+            text.push_str(&format!(
+                "\n[{} more {} not shown; narrow the search or raise limit]",
+                omitted,
+                match self.mode {
+                    OutputMode::Content => "matching lines",
+                    _ => "files",
+                }
+            ));
+        }
+        text
+    }
+
+    /// `path:line:text` for matches and `path-line-text` for context, with `--` between
+    /// groups that aren't adjacent.
+    fn render_hits(&self, out: &mut Vec<String>, name: &str, lines: &[&str], hits: &[usize]) {
+        let mut last: Option<usize> = None;
+        for &hit in hits {
+            let start = hit.saturating_sub(self.context);
+            let end = (hit + self.context).min(lines.len() - 1);
+            let from = match last {
+                Some(l) if l + 1 >= start => l + 1,
+                Some(_) => {
+                    out.push("--".into());
+                    start
+                }
+                None => start,
+            };
+            for (i, text) in lines.iter().enumerate().take(end + 1).skip(from) {
+                let sep = if hits.binary_search(&i).is_ok() {
+                    ':'
+                } else {
+                    '-'
+                };
+                let text = match text.char_indices().nth(MAX_LINE_CHARS) {
+                    Some((cut, _)) => format!("{}...", &text[..cut]),
+                    None => text.to_string(),
+                };
+                out.push(format!("{}{}{}{}{}", name, sep, i + 1, sep, text));
+            }
+            last = Some(end.max(last.unwrap_or(0)));
+        }
+    }
+}
+
+pub struct GrepTool {
+    pub paths: Arc<PathPolicy>,
+}
+
+#[async_trait]
+impl Tool for GrepTool {
+    fn name(&self) -> &str {
+        "grep"
+    }
+    fn description(&self) -> &str {
+        "Search file contents with a regex (Rust regex syntax). Skips hidden, .gitignore'd and binary files unless include_ignored is set. Output: `path:line:text` (context lines use `-`), or file paths / per-file counts via output_mode"
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "pattern": {"type": "string", "description": "Regular expression"},
+                "path": {"type": "string", "description": "File or directory to search (default: workspace)"},
+                "glob": {"type": "string", "description": "Only files matching this glob, e.g. `*.rs` or `src/**/*.ts`"},
+                "type": {"type": "string", "description": "Only files of this type, e.g. rust, py, js, ts, go, md"},
+                "case_insensitive": {"type": "boolean"},
+                "context": {"type": "integer", "description": "Lines of context around each match"},
+                "output_mode": {"type": "string", "enum": ["content", "files", "count"]},
+                "limit": {"type": "integer", "description": "Maximum matching lines (or files) to return (default 200)"},
+                "include_ignored": {"type": "boolean"}
+            },
+            "required": ["pattern"],
+        })
+    }
+    async fn execute(&self, args: Value) -> ToolResult {
+        let query = match GrepQuery::from_args(&args) {
+            Ok(q) => q,
+            Err(e) => return error(e),
+        };
+        let root = match search_root(&self.paths, &args) {
+            Ok(r) => r,
+            Err(e) => return error(e),
+        };
+        let workspace = self.paths.workspace().to_path_buf();
+        match tokio::task::spawn_blocking(move || query.run(&root, &workspace)).await {
+            Ok(content) => ToolResult {
+                success: true,
+                content,
+                error: None,
+            },
+            Err(e) => error(format!("search failed: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    /// A small tree with an ignored `target/` dir and a binary file.
+    fn workspace() -> (TempDir, Arc<PathPolicy>) {
+        let ws = TempDir::new("search");
+        std::fs::create_dir_all(ws.join("src/nested")).unwrap();
+        std::fs::create_dir_all(ws.join("target")).unwrap();
+        std::fs::write(ws.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(ws.join("src/lib.rs"), "mod a;\n// TODO one\nfn x() {}\n").unwrap();
+        std::fs::write(ws.join("src/nested/a.rs"), "fn a() {}\n// todo two\n").unwrap();
+        std::fs::write(ws.join("notes.md"), "TODO docs\n").unwrap();
+        std::fs::write(ws.join("target/gen.rs"), "// TODO generated\n").unwrap();
+        std::fs::write(ws.join("blob.bin"), b"TODO\0\0").unwrap();
+        let paths = Arc::new(PathPolicy::new(&ws));
+        (ws, paths)
+    }
+
+    #[tokio::test]
+    async fn test_glob_skips_ignored_files() {
+        let (_ws, paths) = workspace();
+        let glob = GlobTool { paths };
+        let res = glob.execute(json!({"pattern": "**/*.rs"})).await;
+        assert_eq!(res.content, "src/lib.rs\nsrc/nested/a.rs");
+    }
+
+    #[tokio::test]
+    async fn test_glob_limit() {
+        let (_ws, paths) = workspace();
+        let glob = GlobTool { paths };
+        let res = glob
+            .execute(json!({"pattern": "**/*.rs", "include_ignored": true, "limit": 2}))
+            .await;
+        assert!(
+            res.content
+                .ends_with("\n[1 more not shown; narrow the pattern or raise limit]")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_grep_with_context() {
+        let (_ws, paths) = workspace();
+        let grep = GrepTool { paths };
+        let res = grep
+            .execute(
+                json!({"pattern": "todo", "case_insensitive": true, "type": "rust", "context": 1}),
+            )
+            .await;
+        assert_eq!(
+            res.content,
+            "src/lib.rs-1-mod a;\nsrc/lib.rs:2:// TODO one\nsrc/lib.rs-3-fn x() {}\nsrc/nested/a.rs-1-fn a() {}\nsrc/nested/a.rs:2:// todo two"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_grep_output_modes() {
+        let (_ws, paths) = workspace();
+        let grep = GrepTool { paths };
+        let res = grep
+            .execute(json!({"pattern": "TODO", "output_mode": "count"}))
+            .await;
+        assert_eq!(res.content, "notes.md:1\nsrc/lib.rs:1");
+        let res = grep
+            .execute(json!({"pattern": "TODO", "glob": "*.md", "output_mode": "files"}))
+            .await;
+        assert_eq!(res.content, "notes.md");
+    }
+
+    #[tokio::test]
+    async fn test_grep_unknown_type() {
+        let (_ws, paths) = workspace();
+        let grep = GrepTool { paths };
+        let res = grep.execute(json!({"pattern": "x", "type": "nope"})).await;
+        assert!(res.error.unwrap().contains("unknown file type"));
+    }
+}
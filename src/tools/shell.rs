@@ -0,0 +1,264 @@
+//! Long-lived bash process backing the `bash` tool's persistent mode.
+
+use crate::tools::background::terminate;
+use crate::tools::env::EnvPolicy;
+use crate::tools::output::partial_line_len;
+use crate::tools::sandbox::Sandbox;
+use once_cell::sync::OnceCell;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Weak};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+use tokio::sync::Mutex;
+
+/// A `bash` tool's persistent shell slot (empty until the first command starts one).
+pub type SharedSession = Arc<SessionSlot>;
+type SessionSlot = Mutex<Option<ShellSession>>;
+
+/// Every tool's slot, so `cleanup_shells` can stop the shells when the agent exits.
+static SESSIONS: OnceCell<std::sync::Mutex<Vec<Weak<SessionSlot>>>> = OnceCell::new();
+
+/// A new empty slot, registered for `cleanup_shells`.
+pub fn new_shared_session() -> SharedSession {
+    let session = SharedSession::default();
+    let mut sessions = SESSIONS.get_or_init(Default::default).lock().unwrap();
+    sessions.retain(|s| s.strong_count() > 0);
+    sessions.push(Arc::downgrade(&session));
+    session
+}
+
+/// Kill every persistent shell that is still running, with whatever it started.
+pub async fn cleanup_shells() {
+    let sessions: Vec<SharedSession> = match SESSIONS.get() {
+        Some(s) => s.lock().unwrap().iter().filter_map(Weak::upgrade).collect(),
+        None => return,
+    };
+    for session in sessions {
+        if let Some(mut shell) = session.lock().await.take() {
+            shell.kill().await;
+        }
+    }
+}
+
+pub struct ShellSession {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    stderr: BufReader<ChildStderr>,
+    marker: String,
+    cwd: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct ShellOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub exit_code: i32,
+    /// Working directory after the command ran.
+    pub cwd: PathBuf,
+}
+
+impl ShellSession {
+    /// Start `bash` in `workspace` with `env` applied, confined by `sandbox` if given.
+    pub fn spawn(workspace: &Path, env: &EnvPolicy, sandbox: Option<&Sandbox>) -> io::Result<Self> {
+        let mut command = Command::new("bash");
+        env.apply(&mut command);
+        if let Some(sb) = sandbox {
+            sb.apply(&mut command)?;
+        }
+        // own process group, so a timeout can kill the shell with whatever it started
+        #[cfg(unix)]
+        command.process_group(0);
+        let mut child = command
+            .arg("--login")
+            .arg("-s")
+            .current_dir(workspace)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        let stdout = BufReader::new(child.stdout.take().expect("piped stdout"));
+        let stderr = BufReader::new(child.stderr.take().expect("piped stderr"));
+        let nonce = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+            stderr,
+            marker: format!("__MINIAGENT_DONE_{}_{}", std::process::id(), nonce),
+            cwd: workspace.to_path_buf(),
+        })
+    }
+
+    pub fn cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    /// Run one command in the session and wait for it to finish, collecting its output.
+    pub async fn run(&mut self, command: &str) -> io::Result<ShellOutput> {
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        let (exit_code, cwd) = self
+            .run_streaming(command, &mut |b| stdout.extend_from_slice(b), &mut |b| {
+                stderr.extend_from_slice(b)
+            })
+            .await?;
+        Ok(ShellOutput {
+            stdout,
+            stderr,
+            exit_code,
+            cwd,
+        })
+    }
+
+    /// Run one command, passing stdout and stderr to the callbacks as lines arrive.
+    /// Returns the exit code and the working directory afterwards.
+    ///
+    /// The command is passed through a quoted heredoc to `eval`, so unbalanced quotes can't
+    /// swallow the end marker, and its stdin is `/dev/null` so it can't consume the script
+    /// that follows. State changes (`cd`, `export`, functions) persist because `eval` runs in
+    /// the shell itself. Returns `UnexpectedEof` if the shell exits (e.g. the command ran `exit`).
+    pub async fn run_streaming(
+        &mut self,
+        command: &str,
+        on_stdout: &mut (dyn FnMut(&[u8]) + Send),
+        on_stderr: &mut (dyn FnMut(&[u8]) + Send),
+    ) -> io::Result<(i32, PathBuf)> {
+        let m = &self.marker;
+        let script = format!(
+            "eval \"$(cat <<'{m}_CMD'\n{command}\n{m}_CMD\n)\" < /dev/null\n\
+             __miniagent_ec=$?\n\
+             printf '\\n{m} %d %s\\n' \"$__miniagent_ec\" \"$PWD\"\n\
+             printf '\\n{m}\\n' >&2\n"
+        );
+        self.stdin.write_all(script.as_bytes()).await?;
+        self.stdin.flush().await?;
+
+        let (out, err) = tokio::join!(
+            read_until_marker(&mut self.stdout, m, on_stdout),
+            read_until_marker(&mut self.stderr, m, on_stderr)
+        );
+        let status_line = out?;
+        err?;
+
+        // status line: "<marker> <exit code> <cwd>"
+        let rest = status_line[m.len()..].trim_start();
+        let (code, cwd) = rest.split_once(' ').unwrap_or((rest, ""));
+        let exit_code = code.trim().parse().unwrap_or(-1);
+        if !cwd.trim_end().is_empty() {
+            self.cwd = PathBuf::from(cwd.trim_end_matches(['\r', '\n']));
+        }
+        Ok((exit_code, self.cwd.clone()))
+    }
+
+    /// Kill the shell and everything it started. The session is unusable afterwards.
+    pub async fn kill(&mut self) {
+        terminate(&mut self.child).await;
+    }
+
+    /// Exit status of the shell if it has terminated.
+    pub fn try_exit_code(&mut self) -> Option<i32> {
+        self.child
+            .try_wait()
+            .ok()
+            .flatten()
+            .map(|s| s.code().unwrap_or(-1))
+    }
+}
+
+/// Pass output lines to `on_output` until a line starting with `marker`, and return that line.
+/// A line's trailing newline is held back until the next line arrives, so the newline printed
+/// just before the marker is never emitted. Lines longer than `CHUNK_BYTES` are passed on in
+/// pieces rather than buffered whole.
+async fn read_until_marker<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    marker: &str,
+    on_output: &mut (dyn FnMut(&[u8]) + Send),
+) -> io::Result<String> {
+    let mut buf = Vec::new();
+    let mut pending_newline = false;
+    let mut line_start = true;
+    loop {
+        loop {
+            let (len, ends_line) = match buf.iter().position(|&b| b == b'\n') {
+                Some(i) => (i + 1, true),
+                None => (partial_line_len(&buf), false),
+            };
+            if len == 0 {
+                break;
+            }
+            let mut piece: Vec<u8> = buf.drain(..len).collect();
+            if line_start && ends_line && piece.starts_with(marker.as_bytes()) {
+                return Ok(String::from_utf8_lossy(&piece).into_owned());
+            }
+            line_start = ends_line;
+            if pending_newline {
+                piece.insert(0, b'\n');
+            }
+            pending_newline = piece.last() == Some(&b'\n');
+            if pending_newline {
+                piece.pop();
+            }
+            if !piece.is_empty() {
+                on_output(&piece);
+            }
+        }
+        let data = reader.fill_buf().await?;
+        if data.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "shell exited before the command finished",
+            ));
+        }
+        let n = data.len();
+        buf.extend_from_slice(data);
+        reader.consume(n);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[tokio::test]
+    async fn test_state_persists_between_commands() {
+        let dir = TempDir::new("shell");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let mut shell = ShellSession::spawn(&dir, &EnvPolicy::default(), None).unwrap();
+
+        let out = shell.run("cd sub && export GREETING=hi").await.unwrap();
+        assert_eq!(out.exit_code, 0);
+        assert!(out.cwd.ends_with("sub"));
+
+        let out = shell
+            .run("echo \"$GREETING\"; echo oops >&2; false")
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "hi\n");
+        assert_eq!(String::from_utf8_lossy(&out.stderr), "oops\n");
+        assert_eq!(out.exit_code, 1);
+
+        // unbalanced quotes must not hang the session
+        let out = shell.run("echo 'unterminated").await.unwrap();
+        assert_ne!(out.exit_code, 0);
+        let out = shell.run("printf done").await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "done");
+
+        // a long line without a newline arrives in pieces and intact
+        let out = shell
+            .run("head -c 100000 /dev/zero | tr '\\0' x")
+            .await
+            .unwrap();
+        assert_eq!(out.stdout, vec![b'x'; 100000]);
+
+        assert!(shell.run("exit 3").await.is_err());
+    }
+}
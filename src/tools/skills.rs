@@ -183,6 +183,55 @@ impl SkillLoader {
     }
 }
 
+pub struct GetSkillTool {
+    pub loader: std::sync::Arc<tokio::sync::RwLock<SkillLoader>>,
+}
+
+#[async_trait]
+impl Tool for GetSkillTool {
+    fn name(&self) -> &str {
+        "get_skill"
+    }
+    fn description(&self) -> &str {
+        "Get full content of a named Claude Skill"
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {"skill_name": {"type": "string"}},
+            "required": ["skill_name"],
+        })
+    }
+    async fn execute(&self, args: Value) -> ToolResult {
+        let Some(name) = args.get("skill_name").and_then(|v| v.as_str()) else {
+            return ToolResult {
+                success: false,
+                content: String::new(),
+                error: Some("missing 'skill_name'".into()),
+            };
+        };
+        let loader = self.loader.read().await;
+        match loader.get(name) {
+            Some(s) => {
+                let txt = format!(
+                    "# Skill: {}\n\n{}\n\n---\n\n{}",
+                    s.name, s.description, s.content
+                );
+                ToolResult {
+                    success: true,
+                    content: txt,
+                    error: None,
+                }
+            }
+            None => ToolResult {
+                success: false,
+                content: String::new(),
+                error: Some(format!("Skill '{}' not found", name)),
+            },
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -205,9 +254,7 @@ mod tests {
         write(&skill_dir.join("reference.md"), "ref\n");
 
         // SKILL.md content with three patterns
-        let skill_md = format!(
-            "---\nname: demo\ndescription: demo\n---\n\n`scripts/hello.py`\n\nSee reference.md.\n\nRead [Guide](./reference.md)\n"
-        );
+        let skill_md = "---\nname: demo\ndescription: demo\n---\n\n`scripts/hello.py`\n\nSee reference.md.\n\nRead [Guide](./reference.md)\n".to_string();
         write(&skill_dir.join("SKILL.md"), &skill_md);
 
         // load (direct)
@@ -236,52 +283,3 @@ mod tests {
         assert!(skill.content.contains("scripts/missing.py"));
     }
 }
-
-pub struct GetSkillTool {
-    pub loader: std::sync::Arc<tokio::sync::RwLock<SkillLoader>>,
-}
-
-#[async_trait]
-impl Tool for GetSkillTool {
-    fn name(&self) -> &str {
-        "get_skill"
-    }
-    fn description(&self) -> &str {
-        "Get full content of a named Claude Skill"
-    }
-    fn parameters(&self) -> Value {
-        json!({
-            "type": "object",
-            "properties": {"skill_name": {"type": "string"}},
-            "required": ["skill_name"],
-        })
-    }
-    async fn execute(&self, args: Value) -> ToolResult {
-        let Some(name) = args.get("skill_name").and_then(|v| v.as_str()) else {
-            return ToolResult {
-                success: false,
-                content: String::new(),
-                error: Some("missing 'skill_name'".into()),
-            };
-        };
-        let loader = self.loader.read().await;
-        match loader.get(name) {
-            Some(s) => {
-                let txt = format!(
-                    "# Skill: {}\n\n{}\n\n---\n\n{}",
-                    s.name, s.description, s.content
-                );
-                ToolResult {
-                    success: true,
-                    content: txt,
-                    error: None,
-                }
-            }
-            None => ToolResult {
-                success: false,
-                content: String::new(),
-                error: Some(format!("Skill '{}' not found", name)),
-            },
-        }
-    }
-}
@@ -0,0 +1,256 @@
+//! Text decoding for the file tools: encoding, BOM and line-ending detection on read, and
+//! the inverse on write so files keep their original format.
+
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1252};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// CRLF if most line breaks in `text` are CRLF.
+    pub fn detect(text: &str) -> Self {
+        let crlf = text.matches("\r\n").count();
+        let lf = text.matches('\n').count() - crlf;
+        if crlf > lf { Self::CrLf } else { Self::Lf }
+    }
+}
+
+/// How a file is stored on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextFormat {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+    pub line_ending: LineEnding,
+}
+
+impl Default for TextFormat {
+    fn default() -> Self {
+        Self {
+            encoding: UTF_8,
+            bom: false,
+            line_ending: LineEnding::Lf,
+        }
+    }
+}
+
+impl TextFormat {
+    /// Plain UTF-8 with LF line endings, which needs no mention to the model.
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// e.g. `UTF-16LE with BOM, CRLF line endings`
+    pub fn describe(&self) -> String {
+        format!(
+            "{}{}, {} line endings",
+            self.encoding.name(),
+            if self.bom { " with BOM" } else { "" },
+            match self.line_ending {
+                LineEnding::Lf => "LF",
+                LineEnding::CrLf => "CRLF",
+            }
+        )
+    }
+
+    /// Encode `text` in this format. Line breaks are converted to the format's style
+    /// whatever style `text` uses; characters the encoding can't represent are an error.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, String> {
+        let mut text = text.replace("\r\n", "\n");
+        if self.line_ending == LineEnding::CrLf {
+            text = text.replace('\n', "\r\n");
+        }
+        let mut out = Vec::new();
+        if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+            // encoding_rs only decodes UTF-16
+            let le = self.encoding == UTF_16LE;
+            if self.bom {
+                out.extend_from_slice(if le { &[0xFF, 0xFE] } else { &[0xFE, 0xFF] });
+            }
+            for unit in text.encode_utf16() {
+                out.extend_from_slice(&if le {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                });
+            }
+            return Ok(out);
+        }
+        if self.bom && self.encoding == UTF_8 {
+            out.extend_from_slice(b"\xEF\xBB\xBF");
+        }
+        let (bytes, _, had_errors) = self.encoding.encode(&text);
+        if had_errors {
+            let bad: String = text
+                .chars()
+                .filter(|c| self.encoding.encode(c.encode_utf8(&mut [0; 4])).2)
+                .take(5)
+                .collect();
+            return Err(format!(
+                "the text contains characters that can't be written in {} ({:?})",
+                self.encoding.name(),
+                bad
+            ));
+        }
+        out.extend_from_slice(&bytes);
+        Ok(out)
+    }
+}
+
+/// Decode file contents into text with `\n` line breaks and the format it was stored in.
+/// Returns a short description of the file type instead if it isn't text.
+pub fn decode(bytes: &[u8]) -> Result<(String, TextFormat), &'static str> {
+    let (encoding, bom) = match Encoding::for_bom(bytes) {
+        Some((enc, _)) => (enc, true),
+        None => {
+            if let Some(enc) = utf16_without_bom(bytes) {
+                (enc, false)
+            } else {
+                if let Some(kind) = binary_kind(bytes) {
+                    return Err(kind);
+                }
+                if std::str::from_utf8(bytes).is_ok() {
+                    (UTF_8, false)
+                } else {
+                    // Latin-1 and its Windows superset are the common non-UTF-8 case
+                    (WINDOWS_1252, false)
+                }
+            }
+        }
+    };
+    let (text, _) = encoding.decode_with_bom_removal(bytes);
+    let line_ending = LineEnding::detect(&text);
+    let text = text.replace("\r\n", "\n");
+    Ok((
+        text,
+        TextFormat {
+            encoding,
+            bom,
+            line_ending,
+        },
+    ))
+}
+
+/// How to decode a file too large to load whole, judged from its first bytes: the encoding
+/// and the length of its BOM, or a short description of the file type if it isn't text.
+pub fn sniff(head: &[u8]) -> Result<(&'static Encoding, usize), &'static str> {
+    if let Some((enc, bom_len)) = Encoding::for_bom(head) {
+        return Ok((enc, bom_len));
+    }
+    if let Some(enc) = utf16_without_bom(head) {
+        return Ok((enc, 0));
+    }
+    if let Some(kind) = binary_kind(head) {
+        return Err(kind);
+    }
+    match std::str::from_utf8(head) {
+        // a character cut off at the end of the prefix is still UTF-8
+        Ok(_) => Ok((UTF_8, 0)),
+        Err(e) if e.error_len().is_none() => Ok((UTF_8, 0)),
+        Err(_) => Ok((WINDOWS_1252, 0)),
+    }
+}
+
+/// UTF-16 text without a BOM shows up as NULs in every other byte of ASCII characters.
+fn utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = &bytes[..bytes.len().min(4096) & !1];
+    if head.len() < 4 {
+        return None;
+    }
+    let pairs = head.len() / 2;
+    let even = head.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd = head.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+    if odd * 10 >= pairs * 9 && even == 0 {
+        Some(UTF_16LE)
+    } else if even * 10 >= pairs * 9 && odd == 0 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// A short description of the file type if `bytes` doesn't look like text.
+fn binary_kind(bytes: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG", "PNG image"),
+        (b"\xFF\xD8\xFF", "JPEG image"),
+        (b"GIF8", "GIF image"),
+        (b"%PDF", "PDF document"),
+        (b"PK\x03\x04", "zip archive"),
+        (b"\x1F\x8B", "gzip archive"),
+        (b"\x7FELF", "ELF executable"),
+        (b"MZ", "Windows executable"),
+        (b"\0asm", "WebAssembly module"),
+        (b"SQLite format 3", "SQLite database"),
+    ];
+    let head = &bytes[..bytes.len().min(8192)];
+    if let Some((_, kind)) = SIGNATURES.iter().find(|(sig, _)| head.starts_with(sig)) {
+        // "MZ" alone is too weak a signal for text files that start with it
+        if *kind != "Windows executable" || head.contains(&0) {
+            return Some(kind);
+        }
+    }
+    if head.contains(&0) {
+        return Some("binary data");
+    }
+    let control = head
+        .iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\n' | b'\r' | b'\t' | 0x0C | 0x1B))
+        .count();
+    if control * 10 > head.len() {
+        return Some("binary data");
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_formats() {
+        let cases: Vec<Vec<u8>> = vec![
+            b"a\nb\n".to_vec(),
+            b"\xEF\xBB\xBFa\r\nb\r\n".to_vec(),
+            b"caf\xE9\r\n".to_vec(),
+            [
+                &[0xFF, 0xFE][..],
+                &"h\u{e9}\r\n"
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes)
+                    .collect::<Vec<_>>(),
+            ]
+            .concat(),
+            "hi\nthere\n"
+                .encode_utf16()
+                .flat_map(u16::to_be_bytes)
+                .collect(),
+        ];
+        for bytes in cases {
+            let (text, format) = decode(&bytes).unwrap();
+            assert!(!text.contains('\r'));
+            assert_eq!(
+                format.encode(&text).unwrap(),
+                bytes,
+                "{}",
+                format.describe()
+            );
+        }
+
+        let (text, format) = decode(b"caf\xE9\r\n").unwrap();
+        assert_eq!(text, "caf\u{e9}\n");
+        assert_eq!(format.describe(), "windows-1252, CRLF line endings");
+        assert!(
+            format
+                .encode("\u{4e2d}")
+                .unwrap_err()
+                .contains("windows-1252")
+        );
+
+        assert_eq!(decode(b"\x89PNG\r\n\x1a\n\0\0"), Err("PNG image"));
+        assert_eq!(decode(b"abc\0def"), Err("binary data"));
+        assert!(decode("MZ is fine in text\n".as_bytes()).is_ok());
+    }
+}
@@ -0,0 +1,300 @@
+//! `list_dir`: a depth-limited tree of a directory with sizes and line counts.
+
+use crate::tools::base::{Tool, ToolResult};
+use crate::tools::paths::PathPolicy;
+use crate::tools::search::{display_path, search_root, walker};
+use crate::tools::text;
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const DEFAULT_DEPTH: usize = 3;
+/// Entries listed per directory before the rest are summarized.
+const DEFAULT_MAX_PER_DIR: usize = 50;
+/// Lines in the whole tree before listing stops.
+const MAX_TREE_LINES: usize = 1000;
+/// Lines are only counted for text files up to this size.
+const MAX_COUNT_BYTES: u64 = 2 * 1024 * 1024;
+
+struct TreeOptions {
+    depth: usize,
+    max_per_dir: usize,
+    include_ignored: bool,
+}
+
+struct Tree {
+    lines: Vec<String>,
+    dirs: usize,
+    files: usize,
+    truncated: bool,
+}
+
+/// Direct children of `dir` that pass the ignore filters: directories first, then files,
+/// each sorted by name.
+fn children(dir: &Path, include_ignored: bool) -> Vec<(PathBuf, bool)> {
+    let mut entries: Vec<(PathBuf, bool)> = walker(dir, include_ignored)
+        .max_depth(Some(1))
+        .build()
+        .filter_map(Result::ok)
+        .filter(|e| e.depth() == 1)
+        .map(|e| {
+            let is_dir = e.file_type().is_some_and(|t| t.is_dir());
+            (e.into_path(), is_dir)
+        })
+        .collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    entries
+}
+
+fn plural(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// `name  1.2 KB, 40 lines` (or `binary`) for a file, `name -> target` for a symlink.
+/// Links are not followed, so nothing outside the listed tree is read.
+fn describe_file(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let Ok(meta) = std::fs::symlink_metadata(path) else {
+        return name.into_owned();
+    };
+    if meta.file_type().is_symlink() {
+        return match std::fs::read_link(path) {
+            Ok(target) => format!("{} -> {}", name, target.display()),
+            Err(_) => format!("{} -> ?", name),
+        };
+    }
+    // reading a FIFO or device could block or never end
+    if !meta.is_file() {
+        return name.into_owned();
+    }
+    let mut info = human_size(meta.len());
+    if meta.len() <= MAX_COUNT_BYTES
+        && let Ok(bytes) = std::fs::read(path)
+    {
+        match text::decode(&bytes) {
+            Ok((content, _)) => info.push_str(&format!(
+                ", {}",
+                plural(content.lines().count(), "line", "lines")
+            )),
+            Err(_) => info.push_str(", binary"),
+        }
+    }
+    format!("{}  {}", name, info)
+}
+
+impl Tree {
+    fn render(dir: &Path, opts: &TreeOptions) -> Self {
+        let mut tree = Tree {
+            lines: Vec::new(),
+            dirs: 0,
+            files: 0,
+            truncated: false,
+        };
+        tree.visit(dir, 1, opts);
+        tree
+    }
+
+    fn push(&mut self, depth: usize, line: String) -> bool {
+        if self.lines.len() >= MAX_TREE_LINES {
+            self.truncated = true;
+            return false;
+        }
+        self.lines.push(format!("{}{}", "  ".repeat(depth), line));
+        true
+    }
+
+    fn visit(&mut self, dir: &Path, depth: usize, opts: &TreeOptions) {
+        let entries = children(dir, opts.include_ignored);
+        for (path, is_dir) in entries.iter().take(opts.max_per_dir) {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if *is_dir {
+                self.dirs += 1;
+                if depth >= opts.depth {
+                    let count = children(path, opts.include_ignored).len();
+                    if !self.push(
+                        depth,
+                        format!(
+                            "{}/  ({}, not expanded)",
+                            name,
+                            plural(count, "entry", "entries")
+                        ),
+                    ) {
+                        return;
+                    }
+                } else {
+                    if !self.push(depth, format!("{}/", name)) {
+                        return;
+                    }
+                    self.visit(path, depth + 1, opts);
+                }
+            } else {
+                self.files += 1;
+                if !self.push(depth, describe_file(path)) {
+                    return;
+                }
+            }
+        }
+        if entries.len() > opts.max_per_dir {
+            self.push(
+                depth,
+                format!("... {} more entries", entries.len() - opts.max_per_dir),
+            );
+        }
+    }
+}
+
+pub struct ListDirTool {
+    pub paths: Arc<PathPolicy>,
+}
+
+#[async_trait]
+impl Tool for ListDirTool {
+    fn name(&self) -> &str {
+        "list_dir"
+    }
+    fn description(&self) -> &str {
+        "Show a directory tree (default: the workspace, 3 levels deep) with file sizes and line counts. Hidden and .gitignore'd entries are skipped unless include_ignored is set; big directories are summarized"
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string", "description": "Directory to list (default: workspace)"},
+                "depth": {"type": "integer", "description": "Levels to expand (default 3)"},
+                "max_entries": {"type": "integer", "description": "Entries shown per directory (default 50)"},
+                "include_ignored": {"type": "boolean"}
+            },
+        })
+    }
+    async fn execute(&self, args: Value) -> ToolResult {
+        let root = match search_root(&self.paths, &args) {
+            Ok(r) if r.is_dir() => r,
+            Ok(r) => {
+                return ToolResult {
+                    success: false,
+                    content: String::new(),
+                    error: Some(format!("{} is not a directory", r.display())),
+                };
+            }
+            Err(e) => {
+                return ToolResult {
+                    success: false,
+                    content: String::new(),
+                    error: Some(e),
+                };
+            }
+        };
+        let opts = TreeOptions {
+            depth: args
+                .get("depth")
+                .and_then(|v| v.as_u64())
+                .map_or(DEFAULT_DEPTH, |n| n.max(1) as usize),
+            max_per_dir: args
+                .get("max_entries")
+                .and_then(|v| v.as_u64())
+                .map_or(DEFAULT_MAX_PER_DIR, |n| n.max(1) as usize),
+            include_ignored: args
+                .get("include_ignored")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+        };
+        let workspace = self.paths.workspace().to_path_buf();
+        let rendered = tokio::task::spawn_blocking(move || {
+            let tree = Tree::render(&root, &opts);
+            let mut out = format!("{}/\n", display_path(&workspace, &root));
+            out.push_str(&tree.lines.join("\n"));
+            if tree.truncated {
+                out.push_str(&format!(
+                    "\n[listing stopped after {} lines; list a subdirectory or lower depth]",
+                    MAX_TREE_LINES
+                ));
+            }
+            out.push_str(&format!(
+                "\n\n{} directories, {} files shown",
+                tree.dirs, tree.files
+            ));
+            out
+        })
+        .await;
+        match rendered {
+            Ok(content) => ToolResult {
+                success: true,
+                content,
+                error: None,
+            },
+            Err(e) => ToolResult {
+                success: false,
+                content: String::new(),
+                error: Some(format!("listing failed: {}", e)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[tokio::test]
+    async fn test_list_dir() {
+        let ws = TempDir::new("tree");
+        std::fs::create_dir_all(ws.join("src/deep/deeper")).unwrap();
+        std::fs::create_dir_all(ws.join("target")).unwrap();
+        std::fs::create_dir_all(ws.join("many")).unwrap();
+        std::fs::write(ws.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(ws.join("src/main.rs"), "fn main() {}\n".repeat(100)).unwrap();
+        std::fs::write(ws.join("src/deep/deeper/x.rs"), "").unwrap();
+        std::fs::write(ws.join("logo.png"), b"\x89PNG\r\n\x1a\n\0").unwrap();
+        for i in 0..5 {
+            std::fs::write(ws.join(format!("many/{i}.txt")), "x").unwrap();
+        }
+        let tool = ListDirTool {
+            paths: Arc::new(PathPolicy::new(&ws)),
+        };
+        let res = tool.execute(json!({"depth": 2, "max_entries": 3})).await;
+        assert_eq!(
+            res.content,
+            "./\n  many/\n    0.txt  1 B, 1 line\n    1.txt  1 B, 1 line\n    2.txt  1 B, 1 line\n    ... 2 more entries\n  src/\n    deep/  (1 entry, not expanded)\n    main.rs  1.3 KB, 100 lines\n  logo.png  9 B, binary\n\n3 directories, 5 files shown"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_list_dir_shows_links_without_following_them() {
+        let root = TempDir::new("tree");
+        let ws = root.join("ws");
+        std::fs::create_dir_all(&ws).unwrap();
+        std::fs::write(root.join("secret.txt"), "a\nb\n").unwrap();
+        std::os::unix::fs::symlink(root.join("secret.txt"), ws.join("secret")).unwrap();
+        std::os::unix::fs::symlink(&root, ws.join("up")).unwrap();
+        std::os::unix::fs::symlink("missing", ws.join("dangling")).unwrap();
+        let tool = ListDirTool {
+            paths: Arc::new(PathPolicy::new(&ws)),
+        };
+        let res = tool.execute(json!({})).await;
+        assert_eq!(
+            res.content,
+            format!(
+                "./\n  dangling -> missing\n  secret -> {}\n  up -> {}\n\n0 directories, 3 files shown",
+                root.join("secret.txt").display(),
+                root.display()
+            )
+        );
+    }
+}